@@ -0,0 +1,2 @@
+ALTER TABLE labels ADD COLUMN color TEXT;
+ALTER TABLE labels ADD COLUMN description TEXT;
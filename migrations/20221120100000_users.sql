@@ -0,0 +1,14 @@
+CREATE TABLE users
+(
+  id            SERIAL PRIMARY KEY,
+  email         TEXT NOT NULL UNIQUE,
+  password_hash TEXT NOT NULL
+);
+
+-- トークンそのものは保存せず、SHA-256のハッシュだけを持つ
+CREATE TABLE sessions
+(
+  token_hash TEXT PRIMARY KEY,
+  user_id    INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+  expires_at TIMESTAMPTZ NOT NULL
+);
@@ -0,0 +1,16 @@
+-- todoとラベルは作成したユーザーのもの
+ALTER TABLE todos ADD COLUMN user_id INTEGER REFERENCES users (id);
+ALTER TABLE labels ADD COLUMN user_id INTEGER REFERENCES users (id);
+
+-- 既存の行は、引き継ぎ用のユーザー（legacy-owner）に割り当てる
+-- メールアドレスの形式ではないので登録では作れず、パスワードのハッシュも不正な値なのでログインできない
+-- 既存のデータを使う場合は、管理者がemailとpassword_hashを設定し直す
+INSERT INTO users (email, password_hash)
+SELECT 'legacy-owner', '!'
+WHERE EXISTS (SELECT 1 FROM todos) OR EXISTS (SELECT 1 FROM labels);
+
+UPDATE todos SET user_id = (SELECT id FROM users WHERE email = 'legacy-owner') WHERE user_id IS NULL;
+UPDATE labels SET user_id = (SELECT id FROM users WHERE email = 'legacy-owner') WHERE user_id IS NULL;
+
+CREATE INDEX todos_user_id_idx ON todos (user_id);
+CREATE INDEX labels_user_id_idx ON labels (user_id);
@@ -0,0 +1,22 @@
+-- 複数のユーザーで共有するtodoのリスト
+-- roleは1: viewer, 2: editor, 3: owner
+CREATE TABLE projects
+(
+  id   SERIAL PRIMARY KEY,
+  name TEXT NOT NULL
+);
+
+CREATE TABLE project_members
+(
+  project_id INTEGER NOT NULL REFERENCES projects (id) ON DELETE CASCADE,
+  user_id    INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+  role       SMALLINT NOT NULL,
+  PRIMARY KEY (project_id, user_id)
+);
+
+CREATE INDEX project_members_user_id_idx ON project_members (user_id);
+
+-- project_idがないtodoは、これまで通り作成したユーザーだけのもの
+ALTER TABLE todos ADD COLUMN project_id INTEGER REFERENCES projects (id);
+
+CREATE INDEX todos_project_id_idx ON todos (project_id);
@@ -0,0 +1,5 @@
+-- サブタスクの親のtodo
+-- 子は親と同じプロジェクトに入り、親を削除すると子孫もまとめて削除される
+ALTER TABLE todos ADD COLUMN parent_id INTEGER REFERENCES todos (id);
+
+CREATE INDEX todos_parent_id_idx ON todos (parent_id);
@@ -0,0 +1,5 @@
+CREATE TABLE todos (
+  id        INTEGER PRIMARY KEY AUTOINCREMENT,
+  text      TEXT NOT NULL,
+  completed BOOLEAN NOT NULL DEFAULT false
+);
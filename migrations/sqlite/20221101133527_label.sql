@@ -0,0 +1,12 @@
+CREATE TABLE labels
+(
+  id   INTEGER PRIMARY KEY AUTOINCREMENT,
+  name TEXT NOT NULL
+);
+
+CREATE TABLE todo_labels
+(
+  id       INTEGER PRIMARY KEY AUTOINCREMENT,
+  todo_id  INTEGER NOT NULL REFERENCES todos (id) DEFERRABLE INITIALLY DEFERRED,
+  label_id INTEGER NOT NULL REFERENCES labels (id) DEFERRABLE INITIALLY DEFERRED
+);
@@ -0,0 +1,2 @@
+-- SQLiteには日時型がないので、RFC 3339形式の文字列で保存する
+ALTER TABLE todos ADD COLUMN due_date TEXT;
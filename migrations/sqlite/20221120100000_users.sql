@@ -0,0 +1,14 @@
+CREATE TABLE users
+(
+  id            INTEGER PRIMARY KEY AUTOINCREMENT,
+  email         TEXT NOT NULL UNIQUE,
+  password_hash TEXT NOT NULL
+);
+
+-- トークンそのものは保存せず、SHA-256のハッシュだけを持つ
+CREATE TABLE sessions
+(
+  token_hash TEXT PRIMARY KEY,
+  user_id    INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+  expires_at TEXT NOT NULL
+);
@@ -0,0 +1,14 @@
+-- スクリプトなどから使う、ユーザーごとのアクセストークン
+-- scopeは1: read-only, 2: read-write, 3: admin
+CREATE TABLE api_tokens
+(
+  id           INTEGER PRIMARY KEY AUTOINCREMENT,
+  user_id      INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+  name         TEXT NOT NULL,
+  scope        INTEGER NOT NULL,
+  token_hash   TEXT NOT NULL UNIQUE,
+  created_at   TEXT NOT NULL,
+  last_used_at TEXT
+);
+
+CREATE INDEX api_tokens_user_id_idx ON api_tokens (user_id);
@@ -0,0 +1,12 @@
+-- todoのチェックリスト
+-- サブタスクにするほどではない手順を、todoの中に並び順（position）付きで持つ
+CREATE TABLE checklist_items
+(
+  id       INTEGER PRIMARY KEY AUTOINCREMENT,
+  todo_id  INTEGER NOT NULL REFERENCES todos (id) ON DELETE CASCADE,
+  text     TEXT NOT NULL,
+  done     BOOLEAN NOT NULL DEFAULT false,
+  position INTEGER NOT NULL
+);
+
+CREATE INDEX checklist_items_todo_id_idx ON checklist_items (todo_id);
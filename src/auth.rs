@@ -0,0 +1,105 @@
+use argon2::{
+    password_hash::{rand_core::{OsRng, RngCore}, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    Argon2,
+};
+use axum::async_trait;
+use sha2::{Digest, Sha256};
+
+use crate::repositories::user::{TokenScope, User, UserRepository};
+
+// アクセストークンの先頭に付ける文字列
+// ログインのセッションと見分けて、どちらのテーブルを引けばいいか分かるようにする
+pub const API_TOKEN_PREFIX: &str = "pat_";
+
+// ソルト付きでハッシュ化し、PHC形式の文字列（ソルトとパラメータを含む）で返す
+// 計算が重いので、非同期のタスクからはspawn_blockingで呼ぶ
+pub fn hash_password(password: &str) -> anyhow::Result<String> {
+    let salt = SaltString::generate(&mut OsRng);
+    let hash = Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map_err(|e| anyhow::anyhow!("fail hash password: {}", e))?;
+    Ok(hash.to_string())
+}
+
+pub fn verify_password(password: &str, password_hash: &str) -> bool {
+    PasswordHash::new(password_hash)
+        .map(|hash| {
+            Argon2::default()
+                .verify_password(password.as_bytes(), &hash)
+                .is_ok()
+        })
+        .unwrap_or(false)
+}
+
+// クライアントに渡すトークン
+// 推測されないように、OSの乱数から256bitを作る
+pub fn generate_token() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    base64::encode_config(bytes, base64::URL_SAFE_NO_PAD)
+}
+
+pub fn generate_api_token() -> String {
+    format!("{}{}", API_TOKEN_PREFIX, generate_token())
+}
+
+// データベースにはトークンのハッシュだけを保存する
+// トークン自体が十分に長い乱数なので、ソルトなしのSHA-256で足りる
+pub fn hash_token(token: &str) -> String {
+    base64::encode_config(Sha256::digest(token.as_bytes()), base64::URL_SAFE_NO_PAD)
+}
+
+// 認証できたユーザーと、そのトークンで許可された操作の範囲
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Authenticated {
+    pub user: User,
+    pub scope: TokenScope,
+}
+
+// Authorizationヘッダーのトークンからユーザーを解決する
+// extractorから使うので、ジェネリクスではなくトレイトオブジェクトとしてExtensionに入れる
+#[async_trait]
+pub trait Authenticator: Send + Sync {
+    async fn authenticate(&self, token: &str) -> anyhow::Result<Option<Authenticated>>;
+}
+
+#[async_trait]
+impl<T: UserRepository> Authenticator for T {
+    async fn authenticate(&self, token: &str) -> anyhow::Result<Option<Authenticated>> {
+        // セッションのトークンがたまたま同じ文字列で始まることもあるので、見つからなければセッションも探す
+        if token.starts_with(API_TOKEN_PREFIX) {
+            if let Some((user, scope)) = self.find_by_api_token(hash_token(token)).await? {
+                return Ok(Some(Authenticated { user, scope }));
+            }
+        }
+        // ログインのセッションはユーザー本人の操作なので、全ての操作を許可する
+        let user = self.find_by_session(hash_token(token)).await?;
+        Ok(user.map(|user| Authenticated { user, scope: TokenScope::Admin }))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn password_hashing() {
+        let hash = hash_password("correct horse").expect("fail hash password");
+        assert!(verify_password("correct horse", &hash));
+        assert!(!verify_password("wrong horse", &hash));
+        assert!(!verify_password("correct horse", "not a hash"));
+
+        // ソルトが毎回変わるので、同じパスワードでもハッシュは一致しない
+        let other = hash_password("correct horse").expect("fail hash password");
+        assert_ne!(hash, other);
+    }
+
+    #[test]
+    fn token_hashing() {
+        let token = generate_token();
+        assert_ne!(token, generate_token());
+        assert_eq!(hash_token(&token), hash_token(&token));
+        assert_ne!(hash_token(&token), token);
+        assert!(generate_api_token().starts_with(API_TOKEN_PREFIX));
+    }
+}
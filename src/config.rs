@@ -0,0 +1,360 @@
+use clap::{ArgEnum, Parser};
+use serde::{Deserialize, Serialize};
+use std::{env, net::SocketAddr, path::PathBuf};
+use tracing_subscriber::{filter::LevelFilter, EnvFilter};
+use validator::{Validate, ValidationError};
+
+// アプリケーションの設定
+// 優先度の低い順に、デフォルト値 → 設定ファイル → DATABASE_URL・RUST_LOG → APP_の環境変数 → コマンドライン引数で上書きする
+// 環境変数はAPP_をつけ、ネストしたキーは__で区切る（例: APP_DATABASE__MAX_CONNECTIONS=20）
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Validate)]
+#[validate(schema(function = "validate_storage"))]
+pub struct Config {
+    pub bind_address: SocketAddr,
+    #[validate(length(min = 1, message = "Can not be empty"))]
+    #[validate(custom = "validate_origins")]
+    pub allowed_origins: Vec<String>,
+    pub storage: Storage,
+    pub migrate: bool,
+    // シャットダウン時に、処理中のリクエストを待つ秒数
+    #[validate(range(min = 1, max = 3600, message = "Out of range"))]
+    pub shutdown_timeout_secs: u64,
+    #[validate]
+    pub database: DatabaseConfig,
+    #[validate]
+    pub log: LogConfig,
+    pub features: Features,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Validate)]
+pub struct DatabaseConfig {
+    pub url: Option<String>,
+    #[validate(range(min = 1, max = 100, message = "Out of range"))]
+    pub max_connections: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Validate)]
+pub struct LogConfig {
+    pub format: LogFormat,
+    // RUST_LOGと同じ書式
+    #[validate(custom = "validate_log_level")]
+    pub level: String,
+}
+
+// 環境ごとに有効・無効を切り替えられる機能
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Features {
+    // /admin以下のエンドポイント
+    pub admin_endpoints: bool,
+    // PATCH /todos、PATCH /labelsでのまとめての更新
+    pub bulk_update: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ArgEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Storage {
+    Database,
+    Memory,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ArgEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    Text,
+    Json,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            bind_address: SocketAddr::from(([0, 0, 0, 0], 3000)),
+            allowed_origins: vec!["http://localhost:3001".to_string()],
+            storage: Storage::Database,
+            migrate: false,
+            shutdown_timeout_secs: 30,
+            database: DatabaseConfig {
+                url: None,
+                max_connections: 10,
+            },
+            log: LogConfig {
+                format: LogFormat::Text,
+                level: "info".to_string(),
+            },
+            features: Features {
+                admin_endpoints: true,
+                bulk_update: true,
+            },
+        }
+    }
+}
+
+// オリジンは`*`（全て許可）か、スキームとホストを持つもの
+fn validate_origins(origins: &[String]) -> Result<(), ValidationError> {
+    let is_valid = |origin: &String| {
+        origin == "*"
+            || ((origin.starts_with("http://") || origin.starts_with("https://"))
+                && !origin.ends_with('/')
+                && origin.parse::<axum::http::HeaderValue>().is_ok())
+    };
+    if origins.iter().all(is_valid) {
+        Ok(())
+    } else {
+        let mut error = ValidationError::new("invalid_origin");
+        error.message = Some("must be `*` or an origin like `https://example.com`".into());
+        Err(error)
+    }
+}
+
+// EnvFilterは単語だけのディレクティブをターゲット名として受け付けるので、
+// `verbos`のような書き間違いも通ってしまう。単語だけのものはレベル名に限る
+fn validate_log_level(level: &str) -> Result<(), ValidationError> {
+    let is_valid_directive = |directive: &str| {
+        directive.contains('=') || directive.contains('[') || directive.parse::<LevelFilter>().is_ok()
+    };
+    let directives_are_valid = level
+        .split(',')
+        .map(str::trim)
+        .filter(|directive| !directive.is_empty())
+        .all(is_valid_directive);
+    if directives_are_valid && EnvFilter::try_new(level).is_ok() {
+        Ok(())
+    } else {
+        let mut error = ValidationError::new("invalid_log_level");
+        error.message = Some("must be a level or directives like `info,rust_todo_app=debug`".into());
+        Err(error)
+    }
+}
+
+fn validate_storage(config: &Config) -> Result<(), ValidationError> {
+    if config.storage == Storage::Memory && !cfg!(feature = "memory-storage") {
+        let mut error = ValidationError::new("memory_storage_disabled");
+        error.message = Some("storage=memory requires the memory-storage feature".into());
+        return Err(error);
+    }
+    if config.storage == Storage::Database && config.database.url.is_none() {
+        let mut error = ValidationError::new("missing_database_url");
+        error.message = Some("database.url (or DATABASE_URL) is required for database storage".into());
+        return Err(error);
+    }
+    Ok(())
+}
+
+// コマンドライン引数
+// 指定されたものだけが設定を上書きする
+#[derive(Debug, Default, Parser)]
+pub struct Args {
+    /// 設定ファイル（TOML）のパス。APP_CONFIGでも指定できる
+    #[clap(long, parse(from_os_str))]
+    pub config: Option<PathBuf>,
+    /// 待ち受けるアドレス
+    #[clap(long)]
+    pub bind_address: Option<SocketAddr>,
+    /// CORSで許可するオリジン（複数指定できる）
+    #[clap(long = "allowed-origin")]
+    pub allowed_origins: Vec<String>,
+    /// データの保存先（memoryはmemory-storage featureを有効にしてビルドした場合のみ使える）
+    #[clap(long, arg_enum)]
+    pub storage: Option<Storage>,
+    /// 起動時に、バイナリに埋め込んだマイグレーションを適用する
+    #[clap(long)]
+    pub migrate: bool,
+    /// シャットダウン時に、処理中のリクエストを待つ秒数
+    #[clap(long)]
+    pub shutdown_timeout_secs: Option<u64>,
+    /// コネクションプールの最大接続数
+    #[clap(long)]
+    pub max_connections: Option<u32>,
+    /// ログの出力形式
+    #[clap(long, arg_enum)]
+    pub log_format: Option<LogFormat>,
+}
+
+impl Config {
+    // プロセスの環境変数とコマンドライン引数から読み込む
+    pub fn load(mut args: Args) -> anyhow::Result<Self> {
+        let file = args.config.take().or_else(|| env::var_os("APP_CONFIG").map(PathBuf::from));
+        Self::load_from(file, env::vars(), args)
+    }
+
+    // 設定ファイル、環境変数、コマンドライン引数を明示的に渡して読み込む
+    // テストではプロセスの環境変数を書き換えずに、この関数に値を渡す
+    pub fn load_from(
+        file: Option<PathBuf>,
+        env: impl IntoIterator<Item = (String, String)>,
+        args: Args,
+    ) -> anyhow::Result<Self> {
+        let env: config::Map<String, String> = env.into_iter().collect();
+        let mut builder = config::Config::builder()
+            .add_source(config::Config::try_from(&Config::default())?);
+
+        if let Some(path) = file {
+            builder = builder.add_source(config::File::from(path).format(config::FileFormat::Toml));
+        }
+
+        // DATABASE_URLとRUST_LOGは、これまで通りAPP_なしの名前でも指定できる
+        // APP_の環境変数やコマンドライン引数が優先されるように、その手前の層として扱う
+        let legacy = config::Config::builder()
+            .set_override_option("database.url", env.get("DATABASE_URL").cloned())?
+            .set_override_option("log.level", env.get("RUST_LOG").cloned())?
+            .build()?;
+        let mut config: Config = builder
+            .add_source(legacy)
+            .add_source(
+                config::Environment::with_prefix("APP")
+                    .prefix_separator("_")
+                    .separator("__")
+                    .list_separator(",")
+                    .with_list_parse_key("allowed_origins")
+                    .try_parsing(true)
+                    .source(Some(env)),
+            )
+            .build()?
+            .try_deserialize()?;
+
+        if let Some(bind_address) = args.bind_address {
+            config.bind_address = bind_address;
+        }
+        if !args.allowed_origins.is_empty() {
+            config.allowed_origins = args.allowed_origins;
+        }
+        if let Some(storage) = args.storage {
+            config.storage = storage;
+        }
+        if args.migrate {
+            config.migrate = true;
+        }
+        if let Some(shutdown_timeout_secs) = args.shutdown_timeout_secs {
+            config.shutdown_timeout_secs = shutdown_timeout_secs;
+        }
+        if let Some(max_connections) = args.max_connections {
+            config.database.max_connections = max_connections;
+        }
+        if let Some(format) = args.log_format {
+            config.log.format = format;
+        }
+
+        config.validate()?;
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn default_config_is_valid() {
+        let config = Config {
+            database: DatabaseConfig {
+                url: Some("postgres://localhost/todos".to_string()),
+                ..Config::default().database
+            },
+            ..Config::default()
+        };
+        assert!(config.validate().is_ok());
+
+        // データベースを使うのに接続先がない
+        assert!(Config::default().validate().is_err());
+    }
+
+    #[test]
+    fn config_validation() {
+        let config = |allowed_origins: Vec<&str>, max_connections: u32| Config {
+            allowed_origins: allowed_origins.into_iter().map(String::from).collect(),
+            database: DatabaseConfig {
+                url: Some("postgres://localhost/todos".to_string()),
+                max_connections,
+            },
+            ..Config::default()
+        };
+        assert!(config(vec!["https://example.com", "http://localhost:3001"], 10).validate().is_ok());
+        assert!(config(vec!["*"], 10).validate().is_ok());
+        assert!(config(vec![], 10).validate().is_err());
+        assert!(config(vec!["example.com"], 10).validate().is_err());
+        assert!(config(vec!["https://example.com/"], 10).validate().is_err());
+        assert!(config(vec!["https://example.com"], 0).validate().is_err());
+
+        let mut shutdown = config(vec!["*"], 10);
+        shutdown.shutdown_timeout_secs = 0;
+        assert!(shutdown.validate().is_err());
+
+        let log = |level: &str| {
+            let mut config = config(vec!["*"], 10);
+            config.log.level = level.to_string();
+            config.validate()
+        };
+        assert!(log("debug").is_ok());
+        assert!(log("info,rust_todo_app=debug,tower_http=warn").is_ok());
+        assert!(log("verbos").is_err());
+        assert!(log("rust_todo_app=verbos").is_err());
+
+        // memoryはmemory-storage featureを有効にしてビルドした場合だけ使える
+        let mut memory = config(vec!["*"], 10);
+        memory.storage = Storage::Memory;
+        assert_eq!(memory.validate().is_ok(), cfg!(feature = "memory-storage"));
+    }
+
+    // 環境変数は並列に実行される他のテストと共有されるので、プロセスの環境変数は書き換えずに渡す
+    #[test]
+    fn config_layers() {
+        let mut file = tempfile();
+        writeln!(
+            file.1,
+            r#"
+                bind_address = "127.0.0.1:8080"
+                allowed_origins = ["https://staging.example.com"]
+
+                [database]
+                max_connections = 5
+
+                [log]
+                format = "json"
+
+                [features]
+                bulk_update = false
+            "#
+        )
+        .unwrap();
+
+        let env = [
+            ("APP_DATABASE__MAX_CONNECTIONS", "20"),
+            ("APP_ALLOWED_ORIGINS", "https://a.example.com,https://b.example.com"),
+            ("APP_LOG__LEVEL", "warn"),
+            ("RUST_LOG", "debug"),
+            ("DATABASE_URL", "postgres://localhost/legacy"),
+        ]
+        .map(|(key, value)| (key.to_string(), value.to_string()));
+        let config = Config::load_from(
+            Some(file.0.clone()),
+            env,
+            Args {
+                bind_address: Some("127.0.0.1:9000".parse().unwrap()),
+                ..Args::default()
+            },
+        );
+        std::fs::remove_file(&file.0).unwrap();
+
+        let config = config.expect("fail load config");
+        // CLI > 環境変数 > ファイル > デフォルト
+        assert_eq!(config.bind_address, "127.0.0.1:9000".parse().unwrap());
+        assert_eq!(config.database.max_connections, 20);
+        assert_eq!(
+            config.allowed_origins,
+            vec!["https://a.example.com", "https://b.example.com"]
+        );
+        assert_eq!(config.storage, Storage::Database);
+        assert_eq!(config.log.format, LogFormat::Json);
+        // APP_の環境変数は、APP_なしの名前より優先される
+        assert_eq!(config.log.level, "warn");
+        assert_eq!(config.database.url.as_deref(), Some("postgres://localhost/legacy"));
+        assert!(!config.features.bulk_update);
+        assert!(config.features.admin_endpoints);
+    }
+
+    fn tempfile() -> (PathBuf, std::fs::File) {
+        let path = env::temp_dir().join(format!("rust-todo-app-config-{}.toml", std::process::id()));
+        let file = std::fs::File::create(&path).unwrap();
+        (path, file)
+    }
+}
@@ -1,14 +1,24 @@
 use axum::{
     async_trait,
-    extract::{FromRequest, RequestParts},
-    http::StatusCode,
+    extract::{FromRequest, Query, RequestParts},
+    http::{header, HeaderMap, HeaderValue, StatusCode, Uri},
+    response::IntoResponse,
     BoxError,
     Json,
 };
-use serde::de::DeserializeOwned;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use validator::Validate;
 
+use crate::repositories::{Cursor, CursorOrder, Page, PageRequest, DEFAULT_PAGE_LIMIT};
+use error::AppError;
+
+pub mod admin;
+pub mod auth;
+pub mod error;
+pub mod health;
 pub mod label;
+pub mod metrics;
+pub mod project;
 pub mod todo;
 
 #[derive(Debug)]
@@ -22,17 +32,102 @@ where
     B::Data: Send,
     B::Error: Into<BoxError>,
 {
-    type Rejection = (StatusCode, String);
+    type Rejection = AppError;
 
     async fn from_request(req: &mut RequestParts<B>) -> Result<Self, Self::Rejection> {
         let Json(value) = Json::<T>::from_request(req).await.map_err(|rejection| {
-            let message = format!("Json parse error: [{}]", rejection);
-            (StatusCode::BAD_REQUEST, message)
-        })?;
-        value.validate().map_err(|rejection| {
-            let message = format!("Validate error: [{}]", rejection).replace('\n', ", ");
-            (StatusCode::BAD_REQUEST, message)
+            AppError::BadRequest(format!("Json parse error: [{}]", rejection))
         })?;
+        value.validate()?;
         Ok(ValidateJson(value))
     }
 }
+
+// クエリの解析に失敗したときのエラー
+// serdeのエラー文にはクエリの型名（rust_todo_app::handlers::...）が入るので、クライアントには返さずにログにだけ出す
+fn query_error(field: Option<&str>, rejection: impl std::fmt::Display) -> AppError {
+    tracing::debug!("query parse error: {}", rejection);
+    match field {
+        Some(field) => AppError::BadRequest(format!("Invalid query parameter: {}", field)),
+        None => AppError::BadRequest("Invalid query parameter".to_string()),
+    }
+}
+
+#[derive(Debug)]
+pub struct ValidateQuery<T>(T);
+
+#[async_trait]
+impl<T, B> FromRequest<B> for ValidateQuery<T>
+where
+    T: DeserializeOwned + Validate,
+    B: Send,
+{
+    type Rejection = AppError;
+
+    async fn from_request(req: &mut RequestParts<B>) -> Result<Self, Self::Rejection> {
+        let Query(value) = Query::<T>::from_request(req)
+            .await
+            .map_err(|rejection| query_error(None, rejection))?;
+        value.validate()?;
+        Ok(ValidateQuery(value))
+    }
+}
+
+#[derive(Debug, Deserialize, Validate)]
+struct PageParams {
+    #[validate(range(min = 1, max = 200, message = "Out of range"))]
+    limit: Option<i64>,
+    cursor: Option<String>,
+}
+
+// `?limit=...&cursor=...`からページの指定を取り出すextractor
+#[derive(Debug)]
+pub struct Pagination(PageRequest);
+
+#[async_trait]
+impl<B: Send> FromRequest<B> for Pagination {
+    type Rejection = AppError;
+
+    async fn from_request(req: &mut RequestParts<B>) -> Result<Self, Self::Rejection> {
+        let ValidateQuery(params) = ValidateQuery::<PageParams>::from_request(req).await?;
+        let limit = params.limit.unwrap_or(DEFAULT_PAGE_LIMIT);
+        let after = match params.cursor {
+            Some(cursor) => Some(Cursor::decode(&cursor).ok_or_else(|| {
+                AppError::BadRequest(format!("Query parse error: [invalid cursor {}]", cursor))
+            })?),
+            None => None,
+        };
+        Ok(Pagination(PageRequest { limit, after }))
+    }
+}
+
+impl Pagination {
+    // 別の並び順で作られたカーソルは、位置の意味が違うので受け付けない
+    fn ordered_by(self, order: CursorOrder) -> Result<PageRequest, AppError> {
+        if !self.0.matches_order(order) {
+            return Err(AppError::BadRequest(
+                "Query parse error: [cursor does not match the requested sort]".to_string(),
+            ));
+        }
+        Ok(self.0)
+    }
+}
+
+// Pageをレスポンスにする
+// 次のページがある場合は、cursorだけ差し替えたURLをLinkヘッダーでも返す
+fn page_response<T: Serialize>(uri: &Uri, page: Page<T>) -> impl IntoResponse {
+    let mut headers = HeaderMap::new();
+    if let Some(next_cursor) = &page.next_cursor {
+        let pairs: Vec<(String, String)> =
+            serde_urlencoded::from_str(uri.query().unwrap_or_default()).unwrap_or_default();
+        let mut pairs: Vec<(String, String)> =
+            pairs.into_iter().filter(|(key, _)| key != "cursor").collect();
+        pairs.push(("cursor".to_string(), next_cursor.clone()));
+        let query = serde_urlencoded::to_string(pairs).unwrap_or_default();
+        let link = format!("<{}?{}>; rel=\"next\"", uri.path(), query);
+        if let Ok(value) = HeaderValue::from_str(&link) {
+            headers.insert(header::LINK, value);
+        }
+    }
+    (StatusCode::OK, headers, Json(page))
+}
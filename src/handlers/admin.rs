@@ -0,0 +1,13 @@
+use axum::{extract::Extension, http::StatusCode, response::IntoResponse, Json};
+use std::sync::Arc;
+
+use crate::repositories::schema::SchemaRepository;
+use super::{auth::AdminUser, error::AppError};
+
+pub async fn schema_version<S: SchemaRepository>(
+    AdminUser(_user): AdminUser,
+    Extension(repository): Extension<Arc<S>>,
+) -> Result<impl IntoResponse, AppError> {
+    let version = repository.version().await?;
+    Ok((StatusCode::OK, Json(version)))
+}
@@ -0,0 +1,285 @@
+use axum::{
+    async_trait,
+    extract::{Extension, FromRequest, Path, RequestParts},
+    http::{header, HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use validator::Validate;
+
+use crate::{
+    auth::{
+        generate_api_token, generate_token, hash_password, hash_token, verify_password,
+        Authenticator,
+    },
+    repositories::{
+        user::{
+            ApiToken, CreateApiToken, CreateSession, CreateUser, TokenScope, User, UserRepository,
+        },
+        RepositoryError,
+    },
+};
+use super::{error::AppError, ValidateJson};
+
+// ログインしてから、セッションが切れるまでの時間
+const SESSION_TTL_DAYS: i64 = 7;
+
+// 登録済みのユーザーがいない場合も同じだけ時間をかけて、メールアドレスの有無を推測されないようにする
+const DUMMY_PASSWORD_HASH: &str =
+    "$argon2id$v=19$m=19456,t=2,p=1$c29tZXNhbHRzb21lc2FsdA$Mh2/pEyxpIflHsRWaTnAsyDW4EYvaAbNS9n/sKm1y5c";
+
+// Authorization: Bearer <token>で認証したユーザー
+// 匿名のリクエストは401、トークンのscopeが足りなければ403にする
+// AuthUser: 読み取り（read-only以上）
+// WriteUser: 作成・更新・削除（read-write以上）
+// AdminUser: アクセストークンの管理や管理用のエンドポイント（admin）
+#[derive(Debug)]
+pub struct AuthUser(pub User);
+
+#[derive(Debug)]
+pub struct WriteUser(pub User);
+
+#[derive(Debug)]
+pub struct AdminUser(pub User);
+
+async fn authorize<B: Send>(req: &mut RequestParts<B>, required: TokenScope) -> Result<User, AppError> {
+    let token = req
+        .headers()
+        .and_then(bearer_token)
+        .ok_or_else(|| AppError::Unauthorized("Missing bearer token".to_string()))?;
+    let Extension(authenticator) =
+        Extension::<Arc<dyn Authenticator>>::from_request(req)
+            .await
+            .map_err(|e| AppError::Internal(e.to_string()))?;
+    let authenticated = authenticator
+        .authenticate(&token)
+        .await?
+        .ok_or_else(|| AppError::Unauthorized("Invalid or expired token".to_string()))?;
+    if authenticated.scope < required {
+        return Err(AppError::Forbidden("Token scope is not sufficient".to_string()));
+    }
+    Ok(authenticated.user)
+}
+
+#[async_trait]
+impl<B: Send> FromRequest<B> for AuthUser {
+    type Rejection = AppError;
+
+    async fn from_request(req: &mut RequestParts<B>) -> Result<Self, Self::Rejection> {
+        authorize(req, TokenScope::ReadOnly).await.map(AuthUser)
+    }
+}
+
+#[async_trait]
+impl<B: Send> FromRequest<B> for WriteUser {
+    type Rejection = AppError;
+
+    async fn from_request(req: &mut RequestParts<B>) -> Result<Self, Self::Rejection> {
+        authorize(req, TokenScope::ReadWrite).await.map(WriteUser)
+    }
+}
+
+#[async_trait]
+impl<B: Send> FromRequest<B> for AdminUser {
+    type Rejection = AppError;
+
+    async fn from_request(req: &mut RequestParts<B>) -> Result<Self, Self::Rejection> {
+        authorize(req, TokenScope::Admin).await.map(AdminUser)
+    }
+}
+
+fn bearer_token(headers: &HeaderMap) -> Option<String> {
+    let value = headers.get(header::AUTHORIZATION)?.to_str().ok()?;
+    let (scheme, token) = value.split_once(' ')?;
+    if scheme.eq_ignore_ascii_case("bearer") && !token.trim().is_empty() {
+        Some(token.trim().to_string())
+    } else {
+        None
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Validate)]
+pub struct Credentials {
+    #[validate(email(message = "Invalid email"))]
+    #[validate(length(max = 254, message = "Over text length"))]
+    email: String,
+    #[validate(length(min = 8, message = "Too short"))]
+    #[validate(length(max = 128, message = "Over text length"))]
+    password: String,
+}
+
+// 大文字小文字の違いで別のユーザーにならないようにする
+pub fn normalize_email(email: &str) -> String {
+    email.trim().to_lowercase()
+}
+
+impl Credentials {
+    fn normalized_email(&self) -> String {
+        normalize_email(&self.email)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Session {
+    pub token: String,
+    pub token_type: String,
+    pub expires_at: DateTime<Utc>,
+    pub user: User,
+}
+
+pub async fn register<T: UserRepository>(
+    ValidateJson(payload): ValidateJson<Credentials>,
+    Extension(repository): Extension<Arc<T>>,
+) -> Result<impl IntoResponse, AppError> {
+    let email = payload.normalized_email();
+    let password_hash =
+        tokio::task::spawn_blocking(move || hash_password(&payload.password))
+            .await
+            .map_err(|e| AppError::Internal(e.to_string()))??;
+    // 既存のユーザーのidは返さない
+    let user = repository
+        .create(CreateUser { email, password_hash })
+        .await
+        .map_err(|e| match e.downcast_ref::<RepositoryError>() {
+            Some(RepositoryError::Duplicate(_)) => {
+                AppError::Conflict("Could not register with this email".to_string())
+            }
+            _ => AppError::from(e),
+        })?;
+    Ok((StatusCode::CREATED, Json(user)))
+}
+
+pub async fn login<T: UserRepository>(
+    ValidateJson(payload): ValidateJson<Credentials>,
+    Extension(repository): Extension<Arc<T>>,
+) -> Result<impl IntoResponse, AppError> {
+    let found = repository.find_by_email(payload.normalized_email()).await?;
+    let password_hash = found
+        .as_ref()
+        .map(|user| user.password_hash.clone())
+        .unwrap_or_else(|| DUMMY_PASSWORD_HASH.to_string());
+    let verified =
+        tokio::task::spawn_blocking(move || verify_password(&payload.password, &password_hash))
+            .await
+            .map_err(|e| AppError::Internal(e.to_string()))?;
+    let user = match found {
+        Some(user) if verified => user.into_user(),
+        _ => return Err(AppError::Unauthorized("Invalid email or password".to_string())),
+    };
+
+    let token = generate_token();
+    let expires_at = Utc::now() + Duration::days(SESSION_TTL_DAYS);
+    repository
+        .create_session(CreateSession {
+            token_hash: hash_token(&token),
+            user_id: user.id,
+            expires_at,
+        })
+        .await?;
+
+    Ok((
+        StatusCode::OK,
+        Json(Session {
+            token,
+            token_type: "Bearer".to_string(),
+            expires_at,
+            user,
+        }),
+    ))
+}
+
+pub async fn logout<T: UserRepository>(
+    AuthUser(_user): AuthUser,
+    headers: HeaderMap,
+    Extension(repository): Extension<Arc<T>>,
+) -> Result<impl IntoResponse, AppError> {
+    if let Some(token) = bearer_token(&headers) {
+        repository.delete_session(hash_token(&token)).await?;
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn me(AuthUser(user): AuthUser) -> impl IntoResponse {
+    (StatusCode::OK, Json(user))
+}
+
+// POST /auth/tokensのリクエストボディ
+#[derive(Debug, Serialize, Deserialize, Validate)]
+pub struct NewApiToken {
+    #[validate(length(min = 1, message = "Can not be empty"))]
+    #[validate(length(max = 100, message = "Over text length"))]
+    name: String,
+    scope: TokenScope,
+}
+
+// トークン自体はハッシュしか保存しないので、作成したときにだけ返す
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreatedApiToken {
+    #[serde(flatten)]
+    pub api_token: ApiToken,
+    pub token: String,
+}
+
+pub async fn create_api_token<T: UserRepository>(
+    AdminUser(user): AdminUser,
+    ValidateJson(payload): ValidateJson<NewApiToken>,
+    Extension(repository): Extension<Arc<T>>,
+) -> Result<impl IntoResponse, AppError> {
+    let token = generate_api_token();
+    let api_token = repository
+        .create_api_token(CreateApiToken {
+            user_id: user.id,
+            name: payload.name,
+            scope: payload.scope,
+            token_hash: hash_token(&token),
+        })
+        .await?;
+    Ok((StatusCode::CREATED, Json(CreatedApiToken { api_token, token })))
+}
+
+pub async fn all_api_tokens<T: UserRepository>(
+    AdminUser(user): AdminUser,
+    Extension(repository): Extension<Arc<T>>,
+) -> Result<impl IntoResponse, AppError> {
+    let tokens = repository.api_tokens(user.id).await?;
+    Ok((StatusCode::OK, Json(tokens)))
+}
+
+pub async fn delete_api_token<T: UserRepository>(
+    AdminUser(user): AdminUser,
+    Path(id): Path<i32>,
+    Extension(repository): Extension<Arc<T>>,
+) -> Result<impl IntoResponse, AppError> {
+    repository.delete_api_token(user.id, id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    #[test]
+    fn should_parse_bearer_token() {
+        let headers = |value: &'static str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(header::AUTHORIZATION, HeaderValue::from_static(value));
+            headers
+        };
+        assert_eq!(bearer_token(&headers("Bearer abc")), Some("abc".to_string()));
+        assert_eq!(bearer_token(&headers("bearer abc")), Some("abc".to_string()));
+        assert_eq!(bearer_token(&headers("Basic abc")), None);
+        assert_eq!(bearer_token(&headers("Bearer ")), None);
+        assert_eq!(bearer_token(&HeaderMap::new()), None);
+    }
+
+    #[test]
+    fn dummy_hash_is_valid() {
+        // 不正な形式だと検証がすぐに終わってしまうので、正しいPHC形式であることを確認する
+        assert!(argon2::PasswordHash::new(DUMMY_PASSWORD_HASH).is_ok());
+        assert!(!verify_password("password", DUMMY_PASSWORD_HASH));
+    }
+}
@@ -0,0 +1,161 @@
+use axum::{
+    http::{header, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use validator::{ValidationErrors, ValidationErrorsKind};
+
+use crate::repositories::RepositoryError;
+
+// ハンドラーが返すエラー
+// RFC 7807（application/problem+json）形式のJSONとしてレスポンスになる
+#[derive(Debug)]
+pub enum AppError {
+    BadRequest(String),
+    Validation(ValidationErrors),
+    Unauthorized(String),
+    Forbidden(String),
+    NotFound(String),
+    Conflict(String),
+    UnknownLabels(Vec<i32>),
+    Internal(String),
+}
+
+#[derive(Debug, Serialize)]
+struct FieldError {
+    code: String,
+    message: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct Problem {
+    #[serde(rename = "type")]
+    problem_type: &'static str,
+    title: String,
+    status: u16,
+    detail: String,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    errors: BTreeMap<String, Vec<FieldError>>,
+}
+
+// ネストした構造体やリストのエラーも、`todos[2].text`のようなパスをキーにして1階層にまとめる
+fn flatten_errors(prefix: &str, errors: &ValidationErrors, fields: &mut BTreeMap<String, Vec<FieldError>>) {
+    for (field, kind) in errors.errors() {
+        let path = if prefix.is_empty() {
+            field.to_string()
+        } else {
+            format!("{}.{}", prefix, field)
+        };
+        match kind {
+            ValidationErrorsKind::Field(errors) => {
+                fields.entry(path).or_default().extend(errors.iter().map(|error| FieldError {
+                    code: error.code.to_string(),
+                    message: error.message.as_ref().map(|message| message.to_string()),
+                }));
+            }
+            ValidationErrorsKind::Struct(errors) => flatten_errors(&path, errors, fields),
+            ValidationErrorsKind::List(errors) => {
+                for (index, errors) in errors {
+                    flatten_errors(&format!("{}[{}]", path, index), errors, fields);
+                }
+            }
+        }
+    }
+}
+
+impl AppError {
+    fn status(&self) -> StatusCode {
+        match self {
+            AppError::BadRequest(_) | AppError::Validation(_) => StatusCode::BAD_REQUEST,
+            AppError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            AppError::Forbidden(_) => StatusCode::FORBIDDEN,
+            AppError::NotFound(_) => StatusCode::NOT_FOUND,
+            AppError::Conflict(_) => StatusCode::CONFLICT,
+            AppError::UnknownLabels(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            AppError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn problem(&self) -> Problem {
+        let status = self.status();
+        let (detail, errors) = match self {
+            AppError::Validation(errors) => {
+                let mut fields = BTreeMap::new();
+                flatten_errors("", errors, &mut fields);
+                ("Validation failed".to_string(), fields)
+            }
+            AppError::UnknownLabels(ids) => {
+                let message = format!("Label not found, ids are {:?}", ids);
+                let fields = BTreeMap::from([(
+                    "labels".to_string(),
+                    vec![FieldError {
+                        code: "unknown_label".to_string(),
+                        message: Some(message.clone()),
+                    }],
+                )]);
+                (message, fields)
+            }
+            AppError::BadRequest(detail)
+            | AppError::Unauthorized(detail)
+            | AppError::Forbidden(detail)
+            | AppError::NotFound(detail)
+            | AppError::Conflict(detail)
+            | AppError::Internal(detail) => (detail.clone(), BTreeMap::new()),
+        };
+        Problem {
+            problem_type: "about:blank",
+            title: status.canonical_reason().unwrap_or_default().to_string(),
+            status: status.as_u16(),
+            detail,
+            errors,
+        }
+    }
+}
+
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        let status = self.status();
+        let mut res = Json(self.problem()).into_response();
+        *res.status_mut() = status;
+        res.headers_mut().insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static("application/problem+json"),
+        );
+        if status == StatusCode::UNAUTHORIZED {
+            res.headers_mut()
+                .insert(header::WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+        }
+        res
+    }
+}
+
+// レポジトリから返ってきたエラーをHTTPのエラーに対応させる
+impl From<anyhow::Error> for AppError {
+    fn from(e: anyhow::Error) -> Self {
+        match e.downcast_ref::<RepositoryError>() {
+            Some(RepositoryError::NotFound(_)) | Some(RepositoryError::UserNotFound) => {
+                AppError::NotFound(e.to_string())
+            }
+            Some(RepositoryError::Duplicate(_))
+            | Some(RepositoryError::InUse(_, _))
+            | Some(RepositoryError::NoOwner(_)) => {
+                AppError::Conflict(e.to_string())
+            }
+            Some(RepositoryError::LabelNotFound(ids)) => AppError::UnknownLabels(ids.clone()),
+            Some(RepositoryError::ChecklistMismatch(_)) => AppError::BadRequest(e.to_string()),
+            // 内部のエラー内容はクライアントには返さない
+            Some(RepositoryError::Unexpected(_)) | None => {
+                tracing::error!("{:?}", e);
+                AppError::Internal("Unexpected error occurred".to_string())
+            }
+        }
+    }
+}
+
+impl From<ValidationErrors> for AppError {
+    fn from(errors: ValidationErrors) -> Self {
+        AppError::Validation(errors)
+    }
+}
@@ -0,0 +1,98 @@
+use axum::{
+    extract::Extension,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use std::{sync::Arc, time::Duration};
+use tokio::time::timeout;
+
+use crate::repositories::{
+    health::{HealthRepository, PoolStatus},
+    schema::{SchemaRepository, SchemaVersion},
+};
+
+// データベースが応答しないときに、プローブ自体が詰まらないようにする
+const CHECK_TIMEOUT: Duration = Duration::from_secs(3);
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Readiness {
+    pub ready: bool,
+    pub database: Check,
+    pub schema: Check,
+    pub pool: Option<PoolStatus>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Check {
+    pub ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<SchemaVersion>,
+}
+
+impl Check {
+    fn ok() -> Self {
+        Self { ok: true, error: None, version: None }
+    }
+
+    fn failed(error: String) -> Self {
+        Self { ok: false, error: Some(error), version: None }
+    }
+}
+
+// プロセスが生きていることだけを返す
+pub async fn healthz() -> impl IntoResponse {
+    (StatusCode::OK, Json(serde_json::json!({ "status": "ok" })))
+}
+
+// データベースにつながり、スキーマが最新のときだけリクエストを受けられる
+// 受けられない場合は503を返し、オーケストレーターにトラフィックを止めてもらう
+// 認証なしで呼ばれるので、エラーの詳細（接続先やドライバーの情報）はログにだけ出す
+pub async fn readyz<H: HealthRepository, S: SchemaRepository>(
+    Extension(health): Extension<Arc<H>>,
+    Extension(schema): Extension<Arc<S>>,
+) -> Response {
+    match timeout(CHECK_TIMEOUT, health.ping()).await {
+        Ok(Ok(())) => {}
+        Ok(Err(e)) => return database_unavailable(&e.to_string()),
+        Err(_) => return database_unavailable("database ping timed out"),
+    };
+
+    let schema = match timeout(CHECK_TIMEOUT, schema.version()).await {
+        Ok(Ok(version)) if version.up_to_date => Check { version: Some(version), ..Check::ok() },
+        Ok(Ok(version)) => Check {
+            version: Some(version),
+            ..Check::failed("database schema is not up to date".to_string())
+        },
+        Ok(Err(e)) => {
+            tracing::warn!("instance is not ready, schema version check failed: {:?}", e);
+            Check::failed("schema version check failed".to_string())
+        }
+        Err(_) => Check::failed("schema version check timed out".to_string()),
+    };
+
+    let ready = schema.ok;
+    if !ready {
+        tracing::warn!("instance is not ready, schema: {:?}", schema);
+    }
+    let status = if ready { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+    let readiness = Readiness {
+        ready,
+        database: Check::ok(),
+        schema,
+        pool: health.pool_status(),
+    };
+    (status, Json(readiness)).into_response()
+}
+
+fn database_unavailable(detail: &str) -> Response {
+    tracing::warn!("instance is not ready, database is unavailable: {}", detail);
+    (
+        StatusCode::SERVICE_UNAVAILABLE,
+        Json(serde_json::json!({ "database": "unavailable" })),
+    )
+        .into_response()
+}
@@ -1,50 +1,110 @@
 use axum::{
     extract::{Extension, Path},
     response::IntoResponse,
-    http::StatusCode,
+    http::{StatusCode, Uri},
     Json,
 };
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
-use validator::Validate;
+use validator::{Validate, ValidationErrors};
 
-use crate::repositories::label::LabelRepository;
-use super::ValidateJson;
+use crate::repositories::{
+    label::{CreateLabel, DeleteLabelMode, Label, LabelRepository, UpdateLabel},
+    unit_of_work::{UnitOfWork, Work},
+};
+use super::{auth::{AuthUser, WriteUser}, error::AppError, page_response, Pagination, ValidateJson, ValidateQuery};
 
 pub async fn create_label<T: LabelRepository>(
+    WriteUser(user): WriteUser,
     ValidateJson(payload): ValidateJson<CreateLabel>,
     Extension(repository): Extension<Arc<T>>,
-) -> Result<impl IntoResponse, StatusCode> {
-    let label = repository
-        .create(payload.name)
-        .await
-        .or(Err(StatusCode::INTERNAL_SERVER_ERROR))?;
+) -> Result<impl IntoResponse, AppError> {
+    let label = repository.create(user.id, payload).await?;
 
     Ok((StatusCode::CREATED, Json(label)))
 }
 
 pub async fn all_label<T: LabelRepository>(
+    AuthUser(user): AuthUser,
+    uri: Uri,
+    pagination: Pagination,
     Extension(repository): Extension<Arc<T>>,
-) -> Result<impl IntoResponse, StatusCode> {
-    let labels = repository.all().await.unwrap();
-    Ok((StatusCode::OK, Json(labels)))
+) -> Result<impl IntoResponse, AppError> {
+    let page = pagination.ordered_by(Label::CURSOR_ORDER)?;
+    let labels = repository.all(user.id, page).await?;
+    Ok(page_response(&uri, labels))
 }
 
-pub async fn delete_label<T: LabelRepository>(
+pub async fn find_label<T: LabelRepository>(
+    AuthUser(user): AuthUser,
     Path(id): Path<i32>,
     Extension(repository): Extension<Arc<T>>,
-) -> StatusCode {
-    repository
-        .delete(id)
-        .await
-        .map(|_| StatusCode::NO_CONTENT)
-        .unwrap_or(StatusCode::INTERNAL_SERVER_ERROR)
+) -> Result<impl IntoResponse, AppError> {
+    let label = repository.find(user.id, id).await?;
+    Ok((StatusCode::OK, Json(label)))
 }
 
+pub async fn update_label<T: LabelRepository>(
+    WriteUser(user): WriteUser,
+    Path(id): Path<i32>,
+    ValidateJson(payload): ValidateJson<UpdateLabel>,
+    Extension(repository): Extension<Arc<T>>,
+) -> Result<impl IntoResponse, AppError> {
+    let label = repository.update(user.id, id, payload).await?;
+    Ok((StatusCode::OK, Json(label)))
+}
 
-#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Validate)]
-pub struct CreateLabel {
+// PATCH /labelsのリクエストボディ
+// 複数のラベルをまとめて更新し、1つでも失敗すれば全ての更新を取り消す
+#[derive(Debug, Serialize, Deserialize, Validate)]
+pub struct BulkUpdateLabel {
     #[validate(length(min = 1, message = "Can not be empty"))]
-    #[validate(length(max = 100, message = "Over text length"))]
-    name: String,
+    #[validate]
+    labels: Vec<BulkUpdateLabelItem>,
 }
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BulkUpdateLabelItem {
+    id: i32,
+    #[serde(flatten)]
+    payload: UpdateLabel,
+}
+
+// payloadはflattenしているので、エラーもidと同じ階層のフィールドとして返す
+impl Validate for BulkUpdateLabelItem {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        self.payload.validate()
+    }
+}
+
+pub async fn bulk_update_label<U: UnitOfWork>(
+    WriteUser(user): WriteUser,
+    ValidateJson(payload): ValidateJson<BulkUpdateLabel>,
+    Extension(unit_of_work): Extension<Arc<U>>,
+) -> Result<impl IntoResponse, AppError> {
+    let work = unit_of_work.begin().await?;
+    let repository = work.labels();
+    let mut labels = Vec::with_capacity(payload.labels.len());
+    for item in payload.labels {
+        labels.push(repository.update(user.id, item.id, item.payload).await?);
+    }
+    work.commit().await?;
+    Ok((StatusCode::OK, Json(labels)))
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct DeleteLabelQuery {
+    #[serde(default)]
+    mode: DeleteLabelMode,
+}
+
+pub async fn delete_label<T: LabelRepository>(
+    WriteUser(user): WriteUser,
+    Path(id): Path<i32>,
+    ValidateQuery(query): ValidateQuery<DeleteLabelQuery>,
+    Extension(repository): Extension<Arc<T>>,
+) -> Result<impl IntoResponse, AppError> {
+    let deleted = repository.delete(user.id, id, query.mode).await?;
+    Ok((StatusCode::OK, Json(deleted)))
+}
+
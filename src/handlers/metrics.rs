@@ -0,0 +1,24 @@
+use axum::{
+    extract::Extension,
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::IntoResponse,
+};
+use std::sync::Arc;
+
+use crate::{metrics::Metrics, repositories::health::HealthRepository};
+use super::error::AppError;
+
+// Prometheusのテキスト形式で返す
+pub async fn scrape_metrics<H: HealthRepository>(
+    Extension(metrics): Extension<Arc<Metrics>>,
+    Extension(health): Extension<Arc<H>>,
+) -> Result<impl IntoResponse, AppError> {
+    metrics.set_pool_status(health.pool_status());
+    let body = metrics.encode()?;
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("text/plain; version=0.0.4"),
+    );
+    Ok((StatusCode::OK, headers, body))
+}
@@ -0,0 +1,137 @@
+use axum::{
+    extract::{Extension, Path},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use validator::Validate;
+
+use crate::repositories::project::{
+    CreateProject, Project, ProjectRepository, ProjectRole, UpdateProject,
+};
+use super::{auth::{normalize_email, AuthUser, WriteUser}, error::AppError, ValidateJson};
+
+// プロジェクトのメンバーでなければ404、roleが足りなければ403にする
+pub async fn authorize_project<P: ProjectRepository>(
+    repository: &P,
+    user_id: i32,
+    id: i32,
+    required: ProjectRole,
+) -> Result<Project, AppError> {
+    let project = repository.find(user_id, id).await?;
+    if project.role < required {
+        return Err(AppError::Forbidden("Project role is not sufficient".to_string()));
+    }
+    Ok(project)
+}
+
+pub async fn create_project<P: ProjectRepository>(
+    WriteUser(user): WriteUser,
+    ValidateJson(payload): ValidateJson<CreateProject>,
+    Extension(repository): Extension<Arc<P>>,
+) -> Result<impl IntoResponse, AppError> {
+    let project = repository.create(user.id, payload).await?;
+    Ok((StatusCode::CREATED, Json(project)))
+}
+
+pub async fn find_project<P: ProjectRepository>(
+    AuthUser(user): AuthUser,
+    Path(id): Path<i32>,
+    Extension(repository): Extension<Arc<P>>,
+) -> Result<impl IntoResponse, AppError> {
+    let project = repository.find(user.id, id).await?;
+    Ok((StatusCode::OK, Json(project)))
+}
+
+pub async fn all_project<P: ProjectRepository>(
+    AuthUser(user): AuthUser,
+    Extension(repository): Extension<Arc<P>>,
+) -> Result<impl IntoResponse, AppError> {
+    let projects = repository.all(user.id).await?;
+    Ok((StatusCode::OK, Json(projects)))
+}
+
+// プロジェクト自体の変更と削除はownerだけができる
+pub async fn update_project<P: ProjectRepository>(
+    WriteUser(user): WriteUser,
+    Path(id): Path<i32>,
+    ValidateJson(payload): ValidateJson<UpdateProject>,
+    Extension(repository): Extension<Arc<P>>,
+) -> Result<impl IntoResponse, AppError> {
+    authorize_project(&*repository, user.id, id, ProjectRole::Owner).await?;
+    let project = repository.update(user.id, id, payload).await?;
+    Ok((StatusCode::OK, Json(project)))
+}
+
+pub async fn delete_project<P: ProjectRepository>(
+    WriteUser(user): WriteUser,
+    Path(id): Path<i32>,
+    Extension(repository): Extension<Arc<P>>,
+) -> Result<impl IntoResponse, AppError> {
+    authorize_project(&*repository, user.id, id, ProjectRole::Owner).await?;
+    repository.delete(user.id, id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn all_project_members<P: ProjectRepository>(
+    AuthUser(user): AuthUser,
+    Path(id): Path<i32>,
+    Extension(repository): Extension<Arc<P>>,
+) -> Result<impl IntoResponse, AppError> {
+    let members = repository.members(user.id, id).await?;
+    Ok((StatusCode::OK, Json(members)))
+}
+
+// POST /projects/:id/membersのリクエストボディ
+// 追加するユーザーは、招待する人が既に知っているメールアドレスで指定する
+#[derive(Debug, Serialize, Deserialize, Validate)]
+pub struct AddProjectMember {
+    #[validate(email(message = "Invalid email"))]
+    email: String,
+    role: ProjectRole,
+}
+
+// PUT /projects/:id/members/:user_idのリクエストボディ
+#[derive(Debug, Serialize, Deserialize, Validate)]
+pub struct UpdateProjectMember {
+    role: ProjectRole,
+}
+
+// メンバーの追加とroleの変更はownerだけができる
+pub async fn add_project_member<P: ProjectRepository>(
+    WriteUser(user): WriteUser,
+    Path(id): Path<i32>,
+    ValidateJson(payload): ValidateJson<AddProjectMember>,
+    Extension(repository): Extension<Arc<P>>,
+) -> Result<impl IntoResponse, AppError> {
+    authorize_project(&*repository, user.id, id, ProjectRole::Owner).await?;
+    let email = normalize_email(&payload.email);
+    let member = repository.add_member(user.id, id, email, payload.role).await?;
+    Ok((StatusCode::CREATED, Json(member)))
+}
+
+pub async fn update_project_member<P: ProjectRepository>(
+    WriteUser(user): WriteUser,
+    Path((id, member_id)): Path<(i32, i32)>,
+    ValidateJson(payload): ValidateJson<UpdateProjectMember>,
+    Extension(repository): Extension<Arc<P>>,
+) -> Result<impl IntoResponse, AppError> {
+    authorize_project(&*repository, user.id, id, ProjectRole::Owner).await?;
+    let member = repository.update_member(user.id, id, member_id, payload.role).await?;
+    Ok((StatusCode::OK, Json(member)))
+}
+
+// 他のメンバーを外せるのはownerだけで、自分はroleに関係なく抜けられる
+pub async fn delete_project_member<P: ProjectRepository>(
+    WriteUser(user): WriteUser,
+    Path((id, member_id)): Path<(i32, i32)>,
+    Extension(repository): Extension<Arc<P>>,
+) -> Result<impl IntoResponse, AppError> {
+    if member_id != user.id {
+        authorize_project(&*repository, user.id, id, ProjectRole::Owner).await?;
+    }
+    repository.delete_member(user.id, id, member_id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -1,63 +1,279 @@
 use axum::{
-    extract::{Extension, Path},
-    http::StatusCode,
+    async_trait,
+    extract::{Extension, FromRequest, Path, RequestParts},
+    http::{StatusCode, Uri},
     response::IntoResponse,
     Json,
 };
+use serde::{Deserialize, Serialize};
 use std::sync::Arc;
+use validator::{Validate, ValidationErrors};
 
-use crate::repositories::todo::{CreateTodo, TodoRepository, UpdateTodo};
-use super::ValidateJson;
+use crate::repositories::{
+    project::{ProjectRepository, ProjectRole},
+    todo::{
+        CreateChecklistItem, CreateTodo, TodoEntity, TodoQuery, TodoRepository,
+        UpdateChecklistItem, UpdateTodo,
+    },
+    unit_of_work::{UnitOfWork, Work},
+};
+use super::{
+    auth::{AuthUser, WriteUser},
+    error::AppError,
+    page_response, query_error,
+    project::authorize_project,
+    Pagination, ValidateJson,
+};
 
 // 各種httpハンドラーを作成
 // ここで作成したハンドラーはルート設定の際に使われる
 
-pub async fn create_todo<T: TodoRepository>(
+// プロジェクトのtodoを作成・変更するには、editor以上のroleが必要
+// プロジェクト外のtodoは作成したユーザーにしか見えないので、見えていれば変更できる
+// 別のプロジェクトに移動するときは、移動先でもeditor以上のroleが必要
+async fn authorize_todo<P: ProjectRepository>(
+    projects: &P,
+    user_id: i32,
+    todo: &TodoEntity,
+    move_to: Option<i32>,
+) -> Result<(), AppError> {
+    if let Some(project_id) = todo.project_id {
+        authorize_project(projects, user_id, project_id, ProjectRole::Editor).await?;
+    }
+    if let Some(project_id) = move_to.filter(|project_id| todo.project_id != Some(*project_id)) {
+        authorize_project(projects, user_id, project_id, ProjectRole::Editor).await?;
+    }
+    Ok(())
+}
+
+pub async fn create_todo<T: TodoRepository, P: ProjectRepository>(
+    WriteUser(user): WriteUser,
     ValidateJson(payload): ValidateJson<CreateTodo>,
     Extension(repository): Extension<Arc<T>>,
-) -> Result<impl IntoResponse, StatusCode> {
-    let todo = repository
-        .create(payload)
-        .await
-        .or(Err(StatusCode::NOT_FOUND))?;
+    Extension(projects): Extension<Arc<P>>,
+) -> Result<impl IntoResponse, AppError> {
+    if let Some(project_id) = payload.project_id() {
+        authorize_project(&*projects, user.id, project_id, ProjectRole::Editor).await?;
+    }
+    // サブタスクは親と同じプロジェクトに入るので、親を変更できる必要がある
+    // project_idを指定する場合は、親のプロジェクトと同じでなければならない
+    if let Some(parent_id) = payload.parent_id() {
+        let parent = repository.find(user.id, parent_id).await?;
+        authorize_todo(&*projects, user.id, &parent, None).await?;
+        if payload.project_id().is_some() && payload.project_id() != parent.project_id {
+            return Err(AppError::BadRequest(
+                "project_id must match the project of the parent todo".to_string(),
+            ));
+        }
+    }
+    let todo = repository.create(user.id, payload).await?;
 
     Ok((StatusCode::CREATED, Json(todo)))
 }
 
 pub async fn find_todo<T: TodoRepository>(
+    AuthUser(user): AuthUser,
     Path(id): Path<i32>,
     Extension(repository): Extension<Arc<T>>,
-) -> Result<impl IntoResponse, StatusCode> {
-    let todo = repository.find(id).await.or(Err(StatusCode::NOT_FOUND))?;
+) -> Result<impl IntoResponse, AppError> {
+    let todo = repository.find(user.id, id).await?;
     Ok((StatusCode::OK, Json(todo)))
 }
 
+// GET /todos/:id/children
+// 子孫のtodoを、todoからの深さ（子が1）を付けて浅い順に返す
+pub async fn find_todo_children<T: TodoRepository>(
+    AuthUser(user): AuthUser,
+    Path(id): Path<i32>,
+    Extension(repository): Extension<Arc<T>>,
+) -> Result<impl IntoResponse, AppError> {
+    let todos = repository.children(user.id, id).await?;
+    Ok((StatusCode::OK, Json(todos)))
+}
+
 pub async fn all_todo<T: TodoRepository>(
+    AuthUser(user): AuthUser,
+    uri: Uri,
+    TodoQueryParams(query): TodoQueryParams,
+    pagination: Pagination,
     Extension(repository): Extension<Arc<T>>,
-) -> Result<impl IntoResponse, StatusCode> {
-    let todo = repository.all().await.unwrap();
-    Ok((StatusCode::OK, Json(todo)))
+) -> Result<impl IntoResponse, AppError> {
+    let page = pagination.ordered_by(query.sort.cursor_order())?;
+    let todos = repository.all(user.id, query, page).await?;
+    Ok(page_response(&uri, todos))
+}
+
+// GET /projects/:id/todos
+// GET /todosと同じ絞り込みとページネーションを、プロジェクトのtodoに対して行う
+pub async fn all_project_todo<T: TodoRepository, P: ProjectRepository>(
+    AuthUser(user): AuthUser,
+    Path(id): Path<i32>,
+    uri: Uri,
+    TodoQueryParams(mut query): TodoQueryParams,
+    pagination: Pagination,
+    Extension(repository): Extension<Arc<T>>,
+    Extension(projects): Extension<Arc<P>>,
+) -> Result<impl IntoResponse, AppError> {
+    authorize_project(&*projects, user.id, id, ProjectRole::Viewer).await?;
+    let page = pagination.ordered_by(query.sort.cursor_order())?;
+    query.project_id = Some(id);
+    let todos = repository.all(user.id, query, page).await?;
+    Ok(page_response(&uri, todos))
 }
 
-pub async fn update_todo<T: TodoRepository>(
+pub async fn update_todo<T: TodoRepository, P: ProjectRepository>(
+    WriteUser(user): WriteUser,
     Path(id): Path<i32>,
     ValidateJson(payload): ValidateJson<UpdateTodo>,
     Extension(repository): Extension<Arc<T>>,
-) -> Result<impl IntoResponse, StatusCode> {
-    let todo = repository
-        .update(id, payload)
-        .await
-        .or(Err(StatusCode::NOT_FOUND))?;
+    Extension(projects): Extension<Arc<P>>,
+) -> Result<impl IntoResponse, AppError> {
+    let todo = repository.find(user.id, id).await?;
+    authorize_todo(&*projects, user.id, &todo, payload.project_id()).await?;
+    let todo = repository.update(user.id, id, payload).await?;
     Ok((StatusCode::CREATED, Json(todo)))
 }
 
-pub async fn delete_todo<T: TodoRepository>(
+// PATCH /todosのリクエストボディ
+// 複数のtodoをまとめて更新し、1つでも失敗すれば全ての更新を取り消す
+#[derive(Debug, Serialize, Deserialize, Validate)]
+pub struct BulkUpdateTodo {
+    #[validate(length(min = 1, message = "Can not be empty"))]
+    #[validate]
+    todos: Vec<BulkUpdateTodoItem>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BulkUpdateTodoItem {
+    id: i32,
+    #[serde(flatten)]
+    payload: UpdateTodo,
+}
+
+// payloadはflattenしているので、エラーもidと同じ階層のフィールドとして返す
+impl Validate for BulkUpdateTodoItem {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        self.payload.validate()
+    }
+}
+
+pub async fn bulk_update_todo<U: UnitOfWork, P: ProjectRepository>(
+    WriteUser(user): WriteUser,
+    ValidateJson(payload): ValidateJson<BulkUpdateTodo>,
+    Extension(unit_of_work): Extension<Arc<U>>,
+    Extension(projects): Extension<Arc<P>>,
+) -> Result<impl IntoResponse, AppError> {
+    let work = unit_of_work.begin().await?;
+    let repository = work.todos();
+    let mut todos = Vec::with_capacity(payload.todos.len());
+    for item in payload.todos {
+        let todo = repository.find(user.id, item.id).await?;
+        authorize_todo(&*projects, user.id, &todo, item.payload.project_id()).await?;
+        todos.push(repository.update(user.id, item.id, item.payload).await?);
+    }
+    work.commit().await?;
+    Ok((StatusCode::OK, Json(todos)))
+}
+
+pub async fn delete_todo<T: TodoRepository, P: ProjectRepository>(
+    WriteUser(user): WriteUser,
     Path(id): Path<i32>,
     Extension(repository): Extension<Arc<T>>,
-) -> StatusCode {
-    repository
-        .delete(id)
-        .await
-        .map(|_| StatusCode::NO_CONTENT)
-        .unwrap_or(StatusCode::NOT_FOUND)
+    Extension(projects): Extension<Arc<P>>,
+) -> Result<impl IntoResponse, AppError> {
+    let todo = repository.find(user.id, id).await?;
+    authorize_todo(&*projects, user.id, &todo, None).await?;
+    repository.delete(user.id, id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// POST /todos/:id/checklist
+// チェックリストの操作には、todoを変更するのと同じroleが必要
+pub async fn add_checklist_item<T: TodoRepository, P: ProjectRepository>(
+    WriteUser(user): WriteUser,
+    Path(id): Path<i32>,
+    ValidateJson(payload): ValidateJson<CreateChecklistItem>,
+    Extension(repository): Extension<Arc<T>>,
+    Extension(projects): Extension<Arc<P>>,
+) -> Result<impl IntoResponse, AppError> {
+    let todo = repository.find(user.id, id).await?;
+    authorize_todo(&*projects, user.id, &todo, None).await?;
+    let todo = repository.add_checklist_item(user.id, id, payload).await?;
+    Ok((StatusCode::CREATED, Json(todo)))
+}
+
+pub async fn update_checklist_item<T: TodoRepository, P: ProjectRepository>(
+    WriteUser(user): WriteUser,
+    Path((id, item_id)): Path<(i32, i32)>,
+    ValidateJson(payload): ValidateJson<UpdateChecklistItem>,
+    Extension(repository): Extension<Arc<T>>,
+    Extension(projects): Extension<Arc<P>>,
+) -> Result<impl IntoResponse, AppError> {
+    let todo = repository.find(user.id, id).await?;
+    authorize_todo(&*projects, user.id, &todo, None).await?;
+    let todo = repository.update_checklist_item(user.id, id, item_id, payload).await?;
+    Ok((StatusCode::OK, Json(todo)))
+}
+
+// PUT /todos/:id/checklistのリクエストボディ
+// チェックリストの全ての項目のidを、並べたい順に1回ずつ指定する
+#[derive(Debug, Serialize, Deserialize, Validate)]
+pub struct ReorderChecklist {
+    item_ids: Vec<i32>,
+}
+
+pub async fn reorder_checklist<T: TodoRepository, P: ProjectRepository>(
+    WriteUser(user): WriteUser,
+    Path(id): Path<i32>,
+    ValidateJson(payload): ValidateJson<ReorderChecklist>,
+    Extension(repository): Extension<Arc<T>>,
+    Extension(projects): Extension<Arc<P>>,
+) -> Result<impl IntoResponse, AppError> {
+    let todo = repository.find(user.id, id).await?;
+    authorize_todo(&*projects, user.id, &todo, None).await?;
+    // item_idsが今の項目と一致するかは、レポジトリがトランザクションの中で確かめる
+    let todo = repository.reorder_checklist(user.id, id, payload.item_ids).await?;
+    Ok((StatusCode::OK, Json(todo)))
+}
+
+// 削除後のtodo（進捗の割合も更新される）を返す
+pub async fn delete_checklist_item<T: TodoRepository, P: ProjectRepository>(
+    WriteUser(user): WriteUser,
+    Path((id, item_id)): Path<(i32, i32)>,
+    Extension(repository): Extension<Arc<T>>,
+    Extension(projects): Extension<Arc<P>>,
+) -> Result<impl IntoResponse, AppError> {
+    let todo = repository.find(user.id, id).await?;
+    authorize_todo(&*projects, user.id, &todo, None).await?;
+    let todo = repository.delete_checklist_item(user.id, id, item_id).await?;
+    Ok((StatusCode::OK, Json(todo)))
+}
+
+// GET /todosのクエリを解析するextractor
+// labelは`?label=1&label=2`のように繰り返し指定できるが、axumのQueryでは扱えないので自前で取り出す
+#[derive(Debug)]
+pub struct TodoQueryParams(TodoQuery);
+
+#[async_trait]
+impl<B: Send> FromRequest<B> for TodoQueryParams {
+    type Rejection = AppError;
+
+    async fn from_request(req: &mut RequestParts<B>) -> Result<Self, Self::Rejection> {
+        let query = req.uri().query().unwrap_or_default();
+        let pairs: Vec<(String, String)> =
+            serde_urlencoded::from_str(query).map_err(|e| query_error(None, e))?;
+        let (label_pairs, rest): (Vec<_>, Vec<_>) =
+            pairs.into_iter().partition(|(key, _)| key == "label");
+
+        let rest = serde_urlencoded::to_string(rest).map_err(|e| query_error(None, e))?;
+        let mut query: TodoQuery =
+            serde_urlencoded::from_str(&rest).map_err(|e| query_error(None, e))?;
+        query.labels = label_pairs
+            .into_iter()
+            .map(|(_, value)| value.parse::<i32>())
+            .collect::<Result<_, _>>()
+            .map_err(|e| query_error(Some("label"), e))?;
+
+        Ok(TodoQueryParams(query))
+    }
 }
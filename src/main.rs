@@ -1,92 +1,406 @@
+mod auth;
+mod config;
 mod handlers;
+mod metrics;
 mod repositories;
+mod server;
+mod trace;
 
 use axum::{
     extract::Extension,
-    routing::{delete, get, post},
+    routing::{delete, get, patch, post, put},
     Router,
 };
+use clap::Parser;
 use dotenv::dotenv;
-use sqlx::PgPool;
-use hyper::header::CONTENT_TYPE;
-use tower_http::cors::{Any, CorsLayer, Origin};
-use std::net::SocketAddr;
-use std::{env, sync::Arc};
+use sqlx::{postgres::PgPoolOptions, sqlite::SqlitePoolOptions, PgPool, SqlitePool};
+use hyper::header::{HeaderName, AUTHORIZATION, CONTENT_TYPE};
+use tower::util::MapRequestLayer;
+use tower_http::{
+    cors::{Any, AnyOr, CorsLayer, Origin},
+    request_id::{PropagateRequestIdLayer, SetRequestIdLayer},
+    trace::TraceLayer,
+};
+use std::{net::TcpListener, sync::Arc, time::Duration};
+use tracing_subscriber::EnvFilter;
 
+use auth::Authenticator;
+use config::{Args, Config, LogFormat, Storage};
+use metrics::{Metrics, MetricsLayer};
+use trace::MakeRequestUuid;
 use handlers::{
-    label::{all_label, create_label, delete_label},
-    todo::{all_todo, create_todo, delete_todo, find_todo, update_todo},
+    admin::schema_version,
+    auth::{all_api_tokens, create_api_token, delete_api_token, login, logout, me, register},
+    health::{healthz, readyz},
+    metrics::scrape_metrics,
+    label::{all_label, bulk_update_label, create_label, delete_label, find_label, update_label},
+    project::{
+        all_project, all_project_members, create_project, delete_project, delete_project_member,
+        add_project_member, find_project, update_project, update_project_member,
+    },
+    todo::{
+        add_checklist_item, all_project_todo, all_todo, bulk_update_todo, create_todo,
+        delete_checklist_item, delete_todo, find_todo, find_todo_children, reorder_checklist,
+        update_checklist_item, update_todo,
+    },
+};
+use repositories::{
+    health::{HealthRepository, HealthRepositoryForDb},
+    instrumented::{InstrumentedLabelRepository, InstrumentedTodoRepository, InstrumentedUnitOfWork},
+    label::{LabelRepository, LabelRepositoryForDb, LabelRepositoryForSqlite},
+    project::{ProjectRepository, ProjectRepositoryForDb, ProjectRepositoryForSqlite},
+    schema::{SchemaRepository, SchemaRepositoryForDb, SchemaRepositoryForSqlite},
+    todo::{TodoRepository, TodoRepositoryForDb, TodoRepositoryForSqlite},
+    unit_of_work::{UnitOfWork, UnitOfWorkForDb, UnitOfWorkForSqlite},
+    user::{UserRepository, UserRepositoryForDb, UserRepositoryForSqlite},
 };
+#[cfg(any(test, feature = "memory-storage"))]
 use repositories::{
-    label::{LabelRepository, LabelRepositoryForDb},
-    todo::{TodoRepository, TodoRepositoryForDb},
+    health::HealthRepositoryForMemory,
+    label::LabelRepositoryForMemory,
+    memory::{MemoryStore, UnitOfWorkForMemory},
+    project::ProjectRepositoryForMemory,
+    schema::SchemaRepositoryForMemory,
+    todo::TodoRepositoryForMemory,
+    user::UserRepositoryForMemory,
 };
 
+const X_REQUEST_ID: HeaderName = HeaderName::from_static("x-request-id");
+
 #[tokio::main]
 async fn main() {
-    // loggingの初期化
-    let log_lavel = env::var("RUST_LOG").unwrap_or("info".to_string());
-    env::set_var("RUST_LOG", log_lavel);
-    tracing_subscriber::fmt::init();
     dotenv().ok();
 
-    let database_url = &env::var("DATABASE_URL").expect("undefined [DATABASE_URL]");
+    // 設定の読み込み
+    // 不正な設定では起動しない
+    let config = Config::load(Args::parse()).unwrap_or_else(|e| {
+        eprintln!("invalid configuration: {:#}", e);
+        std::process::exit(2);
+    });
+
+    // loggingの初期化
+    init_logging(&config);
+
+    let (app, pool) = match config.storage {
+        Storage::Database => {
+            let (app, pool) = create_database_app(&config).await;
+            (app, Some(pool))
+        }
+        Storage::Memory => (create_memory_storage_app(&config), None),
+    };
+
+    // アドレスをバインドする
+    let listener = TcpListener::bind(config.bind_address)
+        .unwrap_or_else(|e| panic!("fail bind {}: {}", config.bind_address, e));
+
+    // ログ情報の出力
+    tracing::debug!("listening on {}", config.bind_address);
+
+    // サーバーを立ち上げ、SIGTERMかSIGINTを受けたら処理中のリクエストを待って止める
+    server::serve(
+        listener,
+        app,
+        server::shutdown_signal(),
+        Duration::from_secs(config.shutdown_timeout_secs),
+    )
+    .await // 非同期タスクはawaitされて初めて実行される
+    .unwrap();
+
+    if let Some(pool) = pool {
+        pool.close().await;
+        tracing::info!("database pool closed");
+    }
+}
+
+// シャットダウン時に閉じるコネクションプール
+enum DatabasePool {
+    Postgres(PgPool),
+    Sqlite(SqlitePool),
+}
+
+impl DatabasePool {
+    // 返却されない接続があっても止まらないように、待つ時間に上限を設ける
+    async fn close(&self) {
+        let close = async {
+            match self {
+                DatabasePool::Postgres(pool) => pool.close().await,
+                DatabasePool::Sqlite(pool) => pool.close().await,
+            }
+        };
+        if tokio::time::timeout(Duration::from_secs(5), close).await.is_err() {
+            tracing::warn!("timed out closing database pool");
+        }
+    }
+}
+
+fn init_logging(config: &Config) {
+    // 設定の検証で、levelがEnvFilterとして正しいことを確認している
+    let subscriber = tracing_subscriber::fmt().with_env_filter(EnvFilter::new(&config.log.level));
+    match config.log.format {
+        LogFormat::Text => subscriber.init(),
+        LogFormat::Json => subscriber.json().init(),
+    }
+}
+
+async fn create_database_app(config: &Config) -> (Router, DatabasePool) {
+    // 設定の検証で、databaseのときはurlがあることを確認している
+    let database_url = config.database.url.as_deref().expect("undefined [DATABASE_URL]");
+    let max_connections = config.database.max_connections;
     tracing::debug!("start connect database...");
-    let pool = PgPool::connect(database_url)
+    // DATABASE_URLのスキームで使うデータベースを切り替える
+    if database_url.starts_with("sqlite:") {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(max_connections)
+            .connect(database_url)
+            .await
+            .unwrap_or_else(|_| panic!("fail connect database, url is [{}]", database_url));
+        let schema_repository = SchemaRepositoryForSqlite::new(pool.clone());
+        prepare_schema(&schema_repository, config.migrate).await;
+        let app = create_app(
+            TodoRepositoryForSqlite::new(pool.clone()),
+            LabelRepositoryForSqlite::new(pool.clone()),
+            UserRepositoryForSqlite::new(pool.clone()),
+            ProjectRepositoryForSqlite::new(pool.clone()),
+            UnitOfWorkForSqlite::new(pool.clone()),
+            schema_repository,
+            HealthRepositoryForDb::new(pool.clone(), max_connections),
+            config,
+        );
+        (app, DatabasePool::Sqlite(pool))
+    } else {
+        let pool = PgPoolOptions::new()
+            .max_connections(max_connections)
+            .connect(database_url)
+            .await
+            .unwrap_or_else(|_| panic!("fail connect database, url is [{}]", database_url));
+        let schema_repository = SchemaRepositoryForDb::new(pool.clone());
+        prepare_schema(&schema_repository, config.migrate).await;
+        let app = create_app(
+            TodoRepositoryForDb::new(pool.clone()),
+            LabelRepositoryForDb::new(pool.clone()),
+            UserRepositoryForDb::new(pool.clone()),
+            ProjectRepositoryForDb::new(pool.clone()),
+            UnitOfWorkForDb::new(pool.clone()),
+            schema_repository,
+            HealthRepositoryForDb::new(pool.clone(), max_connections),
+            config,
+        );
+        (app, DatabasePool::Postgres(pool))
+    }
+}
+
+// 新しいバイナリでマイグレーションされたデータベースでは起動しない
+// migrateの指定があれば、未適用のマイグレーションを適用する
+async fn prepare_schema<Schema: SchemaRepository>(schema_repository: &Schema, migrate: bool) {
+    let version = schema_repository
+        .version()
         .await
-        .expect(&format!("fail connect database, url is [{}]", database_url));
+        .unwrap_or_else(|e| panic!("fail get schema version: {:?}", e));
+    if version.is_newer_than_binary() {
+        panic!(
+            "database schema version {:?} is newer than this binary knows ({:?})",
+            version.current, version.latest
+        );
+    }
 
-    let app = create_app(
-        TodoRepositoryForDb::new(pool.clone()),
-        LabelRepositoryForDb::new(pool.clone()),
-    );
+    if migrate {
+        schema_repository
+            .migrate()
+            .await
+            .unwrap_or_else(|e| panic!("fail migrate database: {:?}", e));
+        tracing::info!("database schema migrated to version {:?}", version.latest);
+    } else if !version.up_to_date {
+        tracing::warn!(
+            "database schema version {:?} is behind {:?}, start with --migrate to apply",
+            version.current, version.latest
+        );
+    }
+}
 
-    // アドレスを作成する
-    let addr = SocketAddr::from(([0, 0, 0, 0], 3000));
+// データはプロセスが終了すると消えるので、デモなどの一時的なインスタンス向け
+#[cfg(feature = "memory-storage")]
+fn create_memory_storage_app(config: &Config) -> Router {
+    tracing::warn!("using in-memory storage, data will be lost on shutdown");
+    let store = MemoryStore::new();
+    create_app(
+        TodoRepositoryForMemory::new(store.clone()),
+        LabelRepositoryForMemory::new(store.clone()),
+        UserRepositoryForMemory::new(store.clone()),
+        ProjectRepositoryForMemory::new(store.clone()),
+        UnitOfWorkForMemory::new(store),
+        SchemaRepositoryForMemory,
+        HealthRepositoryForMemory,
+        config,
+    )
+}
 
-    // ログ情報の出力
-    tracing::debug!("listening on {}", addr);
+#[cfg(not(feature = "memory-storage"))]
+fn create_memory_storage_app(_config: &Config) -> Router {
+    unreachable!("storage=memory is rejected by config validation without the memory-storage feature");
+}
 
-    // アドレスをサーバーにバインドし、サーバーを立ち上げる
-    axum::Server::bind(&addr)
-        .serve(app.into_make_service())
-        .await // 非同期タスクはawaitされて初めて実行される
-        .unwrap();
+// レポジトリの操作にかかった時間を記録するように包んでから、ルーティングを作る
+#[allow(clippy::too_many_arguments)]
+fn create_app<Todo, Label, Users, Projects, Work, Schema, Health>(
+    todo_repository: Todo,
+    label_repository: Label,
+    user_repository: Users,
+    project_repository: Projects,
+    unit_of_work: Work,
+    schema_repository: Schema,
+    health_repository: Health,
+    config: &Config,
+) -> Router
+where
+    Todo: TodoRepository,
+    Label: LabelRepository,
+    Users: UserRepository,
+    Projects: ProjectRepository,
+    Work: UnitOfWork,
+    Schema: SchemaRepository,
+    Health: HealthRepository,
+{
+    let metrics = Arc::new(Metrics::new());
+    create_router(
+        InstrumentedTodoRepository::new(todo_repository, metrics.clone()),
+        InstrumentedLabelRepository::new(label_repository, metrics.clone()),
+        user_repository,
+        project_repository,
+        InstrumentedUnitOfWork::new(unit_of_work, metrics.clone()),
+        schema_repository,
+        health_repository,
+        config,
+    )
+    // ルートのテンプレートごとに記録するので、ルーティング後に呼ばれるroute_layerで追加する
+    .route_layer(MetricsLayer::new(metrics.clone()))
+    .layer(Extension(metrics))
 }
 
 // ルーティング設定の作成
 // 柔軟性をもたせるために、TodoRepositoryトレイトを継承したジェネリクスで引数を型指定
-fn create_app<Todo: TodoRepository, Label: LabelRepository>(
+// 複数のレポジトリ操作をまとめて行うハンドラーには、UnitOfWorkを渡す
+#[allow(clippy::too_many_arguments)]
+fn create_router<Todo, Label, Users, Projects, Work, Schema, Health>(
     todo_repository: Todo,
     label_repository: Label,
-) -> Router {
-    Router::new()
+    user_repository: Users,
+    project_repository: Projects,
+    unit_of_work: Work,
+    schema_repository: Schema,
+    health_repository: Health,
+    config: &Config,
+) -> Router
+where
+    Todo: TodoRepository,
+    Label: LabelRepository,
+    Users: UserRepository,
+    Projects: ProjectRepository,
+    Work: UnitOfWork,
+    Schema: SchemaRepository,
+    Health: HealthRepository,
+{
+    let mut todos = post(create_todo::<Todo, Projects>).get(all_todo::<Todo>);
+    let mut labels = post(create_label::<Label>).get(all_label::<Label>);
+    if config.features.bulk_update {
+        todos = todos.patch(bulk_update_todo::<Work, Projects>);
+        labels = labels.patch(bulk_update_label::<Work>);
+    }
+
+    let mut router = Router::new()
         .route("/", get(root))
+        .route("/healthz", get(healthz))
+        .route("/readyz", get(readyz::<Health, Schema>))
+        .route("/metrics", get(scrape_metrics::<Health>))
+        .route("/auth/register", post(register::<Users>))
+        .route("/auth/login", post(login::<Users>))
+        .route("/auth/logout", post(logout::<Users>))
+        .route("/auth/me", get(me))
         .route(
-            "/todos",
-            post(create_todo::<Todo>)
-                .get(all_todo::<Todo>)
+            "/auth/tokens",
+            post(create_api_token::<Users>).get(all_api_tokens::<Users>),
         )
+        .route("/auth/tokens/:id", delete(delete_api_token::<Users>))
+        .route("/todos", todos)
         .route(
             "/todos/:id",
             get(find_todo::<Todo>)
-                .delete(delete_todo::<Todo>)
-                .patch(update_todo::<Todo>),
+                .delete(delete_todo::<Todo, Projects>)
+                .patch(update_todo::<Todo, Projects>),
         )
+        .route("/todos/:id/children", get(find_todo_children::<Todo>))
         .route(
-            "/labels",
-            post(create_label::<Label>)
-                .get(all_label::<Label>)
+            "/todos/:id/checklist",
+            post(add_checklist_item::<Todo, Projects>).put(reorder_checklist::<Todo, Projects>),
+        )
+        .route(
+            "/todos/:id/checklist/:item_id",
+            patch(update_checklist_item::<Todo, Projects>).delete(delete_checklist_item::<Todo, Projects>),
+        )
+        .route("/labels", labels)
+        .route(
+            "/labels/:id",
+            get(find_label::<Label>)
+                .delete(delete_label::<Label>)
+                .patch(update_label::<Label>),
+        )
+        .route(
+            "/projects",
+            post(create_project::<Projects>).get(all_project::<Projects>),
+        )
+        .route(
+            "/projects/:id",
+            get(find_project::<Projects>)
+                .delete(delete_project::<Projects>)
+                .patch(update_project::<Projects>),
+        )
+        .route("/projects/:id/todos", get(all_project_todo::<Todo, Projects>))
+        .route(
+            "/projects/:id/members",
+            get(all_project_members::<Projects>).post(add_project_member::<Projects>),
+        )
+        .route(
+            "/projects/:id/members/:user_id",
+            put(update_project_member::<Projects>).delete(delete_project_member::<Projects>),
+        );
+    if config.features.admin_endpoints {
+        router = router.route("/admin/schema", get(schema_version::<Schema>));
+    }
+
+    router
+        .route_layer(MapRequestLayer::new(trace::record_route))
+        // ルートに一致しないリクエストもトレースするように、route_layerではなくlayerで追加する
+        .layer(
+            TraceLayer::new_for_http()
+                .make_span_with(trace::make_span)
+                .on_response(trace::on_response),
         )
-        .route("/labels/:id", delete(delete_label::<Label>))
         .layer(Extension(Arc::new(todo_repository)))
         .layer(Extension(Arc::new(label_repository))) // axumアプリ内でrepositoryを共有できるようになる
+        .layer(Extension(Arc::new(user_repository.clone())))
+        // AuthUserから、ユーザーのレポジトリの型を知らずに使えるようにする
+        .layer(Extension(Arc::new(user_repository) as Arc<dyn Authenticator>))
+        .layer(Extension(Arc::new(project_repository)))
+        .layer(Extension(Arc::new(unit_of_work)))
+        .layer(Extension(Arc::new(schema_repository)))
+        .layer(Extension(Arc::new(health_repository)))
         .layer(
             CorsLayer::new()
-                .allow_origin(Origin::exact("http://localhost:3001".parse().unwrap()))
+                .allow_origin(allowed_origins(&config.allowed_origins))
                 .allow_methods(Any)
-                .allow_headers(vec![CONTENT_TYPE])
+                .allow_headers(vec![AUTHORIZATION, CONTENT_TYPE, X_REQUEST_ID])
+                .expose_headers(vec![X_REQUEST_ID])
         )
+        // 受け取ったX-Request-Idを使い、なければ振り直して、レスポンスにも付ける
+        .layer(PropagateRequestIdLayer::new(X_REQUEST_ID))
+        .layer(SetRequestIdLayer::new(X_REQUEST_ID, MakeRequestUuid))
+}
+
+// `*`が含まれていれば全てのオリジンを許可する
+fn allowed_origins(origins: &[String]) -> AnyOr<Origin> {
+    if origins.iter().any(|origin| origin == "*") {
+        return Any.into();
+    }
+    Origin::list(origins.iter().map(|origin| origin.parse().unwrap())).into()
 }
 
 async fn root() -> &'static str {
@@ -98,9 +412,18 @@ async fn root() -> &'static str {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::handlers::{
+        auth::{CreatedApiToken, Session},
+        health::Readiness,
+    };
     use crate::repositories::{
-        todo::{test_utils::TodoRepositoryForMemory, CreateTodo, TodoEntity},
-        label::{test_utils::LabelRepositoryForMemory, Label},
+        test_utils::sqlite_pool,
+        todo::{CreateTodo, TodoEntity, TodoNode},
+        label::{CreateLabel, DeletedLabel, Label},
+        project::{CreateProject, Project, ProjectMember, ProjectRepository, ProjectRole},
+        schema::SchemaVersion,
+        user::{ApiToken, TokenScope, User},
+        Page,
     };
     use axum::{
         body::Body,
@@ -110,15 +433,33 @@ mod test {
     use hyper::StatusCode;
     use tower::ServiceExt;
 
+    // テスト用のユーザーのトークン
+    // build_req_with_json、build_req_with_emptyで作るリクエストには、このトークンが付く
+    const TEST_TOKEN: &str = "test-token";
+    // 新しいストアで最初に作られるユーザーなので、idは1になる
+    const TEST_USER_ID: i32 = 1;
+
+    // メモリ上のストアを共有するレポジトリでアプリを作る
+    fn create_memory_app(store: MemoryStore) -> Router {
+        assert_eq!(store.add_session("test@example.com", TEST_TOKEN), TEST_USER_ID);
+        create_app(
+            TodoRepositoryForMemory::new(store.clone()),
+            LabelRepositoryForMemory::new(store.clone()),
+            UserRepositoryForMemory::new(store.clone()),
+            ProjectRepositoryForMemory::new(store.clone()),
+            UnitOfWorkForMemory::new(store),
+            SchemaRepositoryForMemory,
+            HealthRepositoryForMemory,
+            &Config::default(),
+        )
+    }
+
     // テスト用のlabelを作る関数
     fn label_fixture() -> (Vec<Label>, Vec<i32>) {
         let id = 999;
         (
             vec![
-                Label {
-                    id,
-                    name: String::from("test label")
-                },
+                Label::new(id, String::from("test label")),
             ],
             vec![id]
         )
@@ -132,10 +473,7 @@ mod test {
         // リクエストを作成
         let req = Request::builder().uri("/").body(Body::empty()).unwrap();
         // 作ったリクエストからoneshot関数でレスポンスを得る
-        let res = create_app(
-            TodoRepositoryForMemory::new(labels),
-            LabelRepositoryForMemory::new()
-        ).oneshot(req).await.unwrap();
+        let res = create_memory_app(MemoryStore::with_labels(TEST_USER_ID, labels)).oneshot(req).await.unwrap();
 
         // 得られたレスポンスをBytes型を経てString型に変換する
         let bytes = hyper::body::to_bytes(res.into_body()).await.unwrap();
@@ -143,11 +481,534 @@ mod test {
         assert_eq!(body, "Hello, world!")
     }
 
+    #[tokio::test]
+    async fn should_return_schema_version() {
+        let req = build_req_with_empty(Method::GET, "/admin/schema");
+        let res = create_memory_app(MemoryStore::new()).oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        let bytes = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        let version: SchemaVersion = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(version, SchemaVersion::new(None, None));
+    }
+
+    #[tokio::test]
+    async fn should_return_healthz() {
+        let req = build_req_with_empty(Method::GET, "/healthz");
+        let res = create_memory_app(MemoryStore::new()).oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+    }
+
+    #[tokio::test]
+    async fn should_return_readiness() {
+        let pool = sqlite_pool().await;
+        let app = || {
+            create_app(
+                TodoRepositoryForSqlite::new(pool.clone()),
+                LabelRepositoryForSqlite::new(pool.clone()),
+                UserRepositoryForSqlite::new(pool.clone()),
+                ProjectRepositoryForSqlite::new(pool.clone()),
+                UnitOfWorkForSqlite::new(pool.clone()),
+                SchemaRepositoryForSqlite::new(pool.clone()),
+                HealthRepositoryForDb::new(pool.clone(), 1),
+                &Config::default(),
+            )
+        };
+
+        let req = build_req_with_empty(Method::GET, "/readyz");
+        let res = app().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        let bytes = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        let readiness: Readiness = serde_json::from_slice(&bytes).unwrap();
+        assert!(readiness.ready && readiness.database.ok && readiness.schema.ok);
+        assert_eq!(readiness.pool.map(|pool| pool.max_connections), Some(1));
+
+        // データベースとの接続がなくなったら、トラフィックを止めてもらう
+        pool.close().await;
+        let req = build_req_with_empty(Method::GET, "/readyz");
+        let res = app().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::SERVICE_UNAVAILABLE, res.status());
+        // 認証なしで呼ばれるので、データベースのエラーの詳細は返さない
+        let bytes = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(body, serde_json::json!({ "database": "unavailable" }));
+    }
+
+    #[tokio::test]
+    async fn should_return_metrics_by_route_template() {
+        let app = create_memory_app(MemoryStore::new());
+        let req = build_req_with_empty(Method::GET, "/todos/1");
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::NOT_FOUND, res.status());
+
+        let req = build_req_with_empty(Method::GET, "/metrics");
+        let res = app.oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        let bytes = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        let body = String::from_utf8(bytes.to_vec()).unwrap();
+        assert!(body.contains(r#"http_requests_total{method="GET",route="/todos/:id",status="404"} 1"#));
+        assert!(body.contains(r#"http_request_duration_seconds_count{method="GET",route="/todos/:id"} 1"#));
+        assert!(body.contains(
+            r#"repository_operation_duration_seconds_count{operation="find",repository="todo",result="error"} 1"#
+        ));
+    }
+
+    #[tokio::test]
+    async fn should_set_request_id() {
+        // 指定がなければ振る
+        let req = build_req_with_empty(Method::GET, "/healthz");
+        let res = create_memory_app(MemoryStore::new()).oneshot(req).await.unwrap();
+        let request_id = res.headers().get("x-request-id").expect("no x-request-id header");
+        assert!(uuid::Uuid::parse_str(request_id.to_str().unwrap()).is_ok());
+
+        // 指定があればそのまま返す
+        let req = Request::builder()
+            .uri("/todos/1")
+            .header("x-request-id", "from-upstream")
+            .header(header::AUTHORIZATION, format!("Bearer {}", TEST_TOKEN))
+            .body(Body::empty())
+            .unwrap();
+        let res = create_memory_app(MemoryStore::new()).oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::NOT_FOUND, res.status());
+        assert_eq!(res.headers().get("x-request-id").unwrap(), "from-upstream");
+
+        // どのルートにも一致しないリクエストにも振る
+        let req = build_req_with_empty(Method::GET, "/no-such-route");
+        let res = create_memory_app(MemoryStore::new()).oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::NOT_FOUND, res.status());
+        assert!(res.headers().get("x-request-id").is_some());
+    }
+
+    #[tokio::test]
+    async fn should_register_and_login() {
+        let app = create_memory_app(MemoryStore::new());
+        let credentials = r#"{"email": "New@Example.com", "password": "password123"}"#;
+
+        let req = build_req_with_json("/auth/register", Method::POST, credentials.to_string());
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::CREATED, res.status());
+
+        // 同じメールアドレスでは登録できない
+        let req = build_req_with_json("/auth/register", Method::POST, credentials.to_string());
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::CONFLICT, res.status());
+        let bytes = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        let problem: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(problem["detail"], "Could not register with this email");
+
+        // パスワードが違う
+        let req = build_req_with_json(
+            "/auth/login",
+            Method::POST,
+            r#"{"email": "new@example.com", "password": "wrong-password"}"#.to_string(),
+        );
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::UNAUTHORIZED, res.status());
+
+        let req = build_req_with_json("/auth/login", Method::POST, credentials.to_string());
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        let bytes = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        let session: Session = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(session.user.email, "new@example.com");
+
+        // 発行されたトークンで認証できる
+        let me = || {
+            Request::builder()
+                .uri("/auth/me")
+                .header(header::AUTHORIZATION, format!("Bearer {}", session.token))
+                .body(Body::empty())
+                .unwrap()
+        };
+        let res = app.clone().oneshot(me()).await.unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        let bytes = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        let user: User = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(user, session.user);
+
+        // ログアウトしたトークンは使えない
+        let req = Request::builder()
+            .uri("/auth/logout")
+            .method(Method::POST)
+            .header(header::AUTHORIZATION, format!("Bearer {}", session.token))
+            .body(Body::empty())
+            .unwrap();
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::NO_CONTENT, res.status());
+        let res = app.oneshot(me()).await.unwrap();
+        assert_eq!(StatusCode::UNAUTHORIZED, res.status());
+    }
+
+    #[tokio::test]
+    async fn should_reject_anonymous_requests() {
+        let app = create_memory_app(MemoryStore::new());
+        let req = Request::builder().uri("/todos").body(Body::empty()).unwrap();
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::UNAUTHORIZED, res.status());
+        assert_eq!(res.headers().get(header::WWW_AUTHENTICATE).unwrap(), "Bearer");
+
+        let req = Request::builder()
+            .uri("/labels")
+            .header(header::AUTHORIZATION, "Bearer unknown-token")
+            .body(Body::empty())
+            .unwrap();
+        let res = app.oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::UNAUTHORIZED, res.status());
+    }
+
+    #[tokio::test]
+    async fn should_hide_other_users_data() {
+        let store = MemoryStore::new();
+        let app = create_memory_app(store.clone());
+        let other_id = store.add_session("other@example.com", "other-token");
+        let label = LabelRepositoryForMemory::new(store.clone())
+            .create(other_id, CreateLabel::new("other label".to_string()))
+            .await
+            .expect("failed create label");
+        let todo = TodoRepositoryForMemory::new(store.clone())
+            .create(other_id, CreateTodo::new("other todo".to_string(), vec![label.id]))
+            .await
+            .expect("failed create todo");
+
+        // 他のユーザーのtodoとラベルは、存在しないものとして404になる
+        let path = format!("/todos/{}", todo.id);
+        for req in [
+            build_req_with_empty(Method::GET, &path),
+            build_req_with_json(&path, Method::PATCH, r#"{ "completed": true }"#.to_string()),
+            build_req_with_empty(Method::DELETE, &path),
+            build_req_with_empty(Method::GET, &format!("/labels/{}", label.id)),
+            build_req_with_empty(Method::DELETE, &format!("/labels/{}", label.id)),
+        ] {
+            let res = app.clone().oneshot(req).await.unwrap();
+            assert_eq!(StatusCode::NOT_FOUND, res.status());
+        }
+
+        // 一覧にも含まれず、ラベルをtodoに付けることもできない
+        let req = build_req_with_empty(Method::GET, "/todos");
+        let res = app.clone().oneshot(req).await.unwrap();
+        let bytes = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        let todos: Page<TodoEntity> = serde_json::from_slice(&bytes).unwrap();
+        assert!(todos.items.is_empty());
+        let req = build_req_with_json(
+            "/todos",
+            Method::POST,
+            format!(r#"{{ "text": "mine", "labels": [{}] }}"#, label.id),
+        );
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::UNPROCESSABLE_ENTITY, res.status());
+
+        // 他のユーザーからの操作では何も変わらない
+        let found = TodoRepositoryForMemory::new(store)
+            .find(other_id, todo.id)
+            .await
+            .expect("failed find todo");
+        assert_eq!(found, todo);
+    }
+
+    #[tokio::test]
+    async fn should_use_api_tokens() {
+        let app = create_memory_app(MemoryStore::new());
+
+        // セッションのトークンで読み取り専用のトークンを発行する
+        let req = build_req_with_json(
+            "/auth/tokens",
+            Method::POST,
+            r#"{"name": "backup script", "scope": "read-only"}"#.to_string(),
+        );
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::CREATED, res.status());
+        let bytes = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        let created: CreatedApiToken = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(created.api_token.scope, TokenScope::ReadOnly);
+        assert_eq!(created.api_token.last_used_at, None);
+
+        // 読み取りはできるが、作成はscopeが足りない
+        let req = build_req_with_token(Method::GET, "/todos", &created.token, Body::empty());
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        let req = build_req_with_token(
+            Method::POST,
+            "/todos",
+            &created.token,
+            Body::from(r#"{ "text": "should_use_api_tokens", "labels": [] }"#),
+        );
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::FORBIDDEN, res.status());
+        let req = build_req_with_token(Method::GET, "/auth/tokens", &created.token, Body::empty());
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::FORBIDDEN, res.status());
+
+        // 一覧には最終使用日時が記録され、トークン自体は含まれない
+        let req = build_req_with_empty(Method::GET, "/auth/tokens");
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        let bytes = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        let tokens: Vec<ApiToken> = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(tokens.len(), 1);
+        assert_eq!(tokens[0].id, created.api_token.id);
+        assert!(tokens[0].last_used_at.is_some());
+        assert!(!String::from_utf8(bytes.to_vec()).unwrap().contains(&created.token));
+
+        // 失効させたトークンは使えない
+        let path = format!("/auth/tokens/{}", created.api_token.id);
+        let req = build_req_with_empty(Method::DELETE, &path);
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::NO_CONTENT, res.status());
+        let req = build_req_with_token(Method::GET, "/todos", &created.token, Body::empty());
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::UNAUTHORIZED, res.status());
+        let req = build_req_with_empty(Method::DELETE, &path);
+        let res = app.oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::NOT_FOUND, res.status());
+    }
+
+    #[tokio::test]
+    async fn should_enforce_project_roles() {
+        let store = MemoryStore::new();
+        let app = create_memory_app(store.clone());
+        let member_id = store.add_session("member@example.com", "member-token");
+
+        let req = build_req_with_json("/projects", Method::POST, r#"{"name": "shared"}"#.to_string());
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::CREATED, res.status());
+        let bytes = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        let project: Project = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(project.role, ProjectRole::Owner);
+
+        let req = build_req_with_json(
+            "/todos",
+            Method::POST,
+            format!(r#"{{ "text": "shared todo", "labels": [], "project_id": {} }}"#, project.id),
+        );
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::CREATED, res.status());
+        let todo = res_to_todo(res).await;
+        assert_eq!(todo.project_id, Some(project.id));
+
+        // メンバーでなければ見えない
+        let todo_path = format!("/todos/{}", todo.id);
+        let req = build_req_with_token(Method::GET, &todo_path, "member-token", Body::empty());
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::NOT_FOUND, res.status());
+
+        // idを指定しても、メンバーでないユーザーは追加できない
+        let member_path = format!("/projects/{}/members/{}", project.id, member_id);
+        let req = build_req_with_json(&member_path, Method::PUT, r#"{"role": "viewer"}"#.to_string());
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::NOT_FOUND, res.status());
+        let members_path = format!("/projects/{}/members", project.id);
+        let req = build_req_with_json(
+            &members_path,
+            Method::POST,
+            r#"{"email": "nobody@example.com", "role": "viewer"}"#.to_string(),
+        );
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::NOT_FOUND, res.status());
+
+        // viewerは閲覧だけができる
+        let req = build_req_with_json(
+            &members_path,
+            Method::POST,
+            r#"{"email": "Member@example.com", "role": "viewer"}"#.to_string(),
+        );
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::CREATED, res.status());
+        let bytes = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        let member: ProjectMember = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(member.user_id, member_id);
+        let req = build_req_with_token(Method::GET, &todo_path, "member-token", Body::empty());
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        for req in [
+            build_req_with_token(Method::PATCH, &todo_path, "member-token", Body::from(r#"{ "completed": true }"#)),
+            build_req_with_token(Method::DELETE, &todo_path, "member-token", Body::empty()),
+            build_req_with_token(
+                Method::POST,
+                "/todos",
+                "member-token",
+                Body::from(format!(r#"{{ "text": "viewer todo", "labels": [], "project_id": {} }}"#, project.id)),
+            ),
+            build_req_with_token(Method::PUT, &member_path, "member-token", Body::from(r#"{"role": "owner"}"#)),
+            build_req_with_token(
+                Method::POST,
+                &members_path,
+                "member-token",
+                Body::from(r#"{"email": "test@example.com", "role": "viewer"}"#),
+            ),
+        ] {
+            let res = app.clone().oneshot(req).await.unwrap();
+            assert_eq!(StatusCode::FORBIDDEN, res.status());
+        }
+
+        // editorはtodoを変更できる
+        let req = build_req_with_json(&member_path, Method::PUT, r#"{"role": "editor"}"#.to_string());
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        let req = build_req_with_token(Method::PATCH, &todo_path, "member-token", Body::from(r#"{ "completed": true }"#));
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::CREATED, res.status());
+        assert!(res_to_todo(res).await.completed);
+
+        let req = build_req_with_token(
+            Method::GET,
+            &format!("/projects/{}/members", project.id),
+            "member-token",
+            Body::empty(),
+        );
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        let bytes = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        let members: Vec<ProjectMember> = serde_json::from_slice(&bytes).unwrap();
+        let roles: Vec<_> = members.iter().map(|member| (member.user_id, member.role)).collect();
+        assert_eq!(roles, vec![(TEST_USER_ID, ProjectRole::Owner), (member_id, ProjectRole::Editor)]);
+
+        // 最後のownerはroleを変えられない
+        let owner_path = format!("/projects/{}/members/{}", project.id, TEST_USER_ID);
+        let req = build_req_with_json(&owner_path, Method::PUT, r#"{"role": "editor"}"#.to_string());
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::CONFLICT, res.status());
+
+        // 自分で抜けたプロジェクトのtodoは見えなくなる
+        let req = build_req_with_token(Method::DELETE, &member_path, "member-token", Body::empty());
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::NO_CONTENT, res.status());
+        let req = build_req_with_token(Method::GET, &todo_path, "member-token", Body::empty());
+        let res = app.oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::NOT_FOUND, res.status());
+    }
+
+    #[tokio::test]
+    async fn should_manage_projects() {
+        let store = MemoryStore::new();
+        let app = create_memory_app(store.clone());
+        let other_id = store.add_session("other@example.com", "other-token");
+        let other_project = ProjectRepositoryForMemory::new(store.clone())
+            .create(other_id, CreateProject::new("other"))
+            .await
+            .expect("failed create project");
+
+        let mut projects = vec![];
+        for name in ["inbox", "backlog"] {
+            let req = build_req_with_json("/projects", Method::POST, format!(r#"{{"name": "{}"}}"#, name));
+            let res = app.clone().oneshot(req).await.unwrap();
+            assert_eq!(StatusCode::CREATED, res.status());
+            let bytes = hyper::body::to_bytes(res.into_body()).await.unwrap();
+            projects.push(serde_json::from_slice::<Project>(&bytes).unwrap());
+        }
+        let (inbox, backlog) = (&projects[0], &projects[1]);
+
+        // rename
+        let req = build_req_with_json(
+            &format!("/projects/{}", backlog.id),
+            Method::PATCH,
+            r#"{"name": "sprint"}"#.to_string(),
+        );
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        let bytes = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        let sprint: Project = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(sprint.name, "sprint");
+
+        // 他のユーザーのプロジェクトは一覧に含まれない
+        let req = build_req_with_empty(Method::GET, "/projects");
+        let res = app.clone().oneshot(req).await.unwrap();
+        let bytes = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        let all: Vec<Project> = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(all, vec![inbox.clone(), sprint.clone()]);
+        let req = build_req_with_empty(Method::GET, &format!("/projects/{}", other_project.id));
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::NOT_FOUND, res.status());
+
+        let req = build_req_with_json(
+            "/todos",
+            Method::POST,
+            format!(r#"{{ "text": "move me", "labels": [], "project_id": {} }}"#, inbox.id),
+        );
+        let todo = res_to_todo(app.clone().oneshot(req).await.unwrap()).await;
+        let req = build_req_with_json("/todos", Method::POST, r#"{ "text": "personal", "labels": [] }"#.to_string());
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::CREATED, res.status());
+
+        let project_todos = |id: i32| build_req_with_empty(Method::GET, &format!("/projects/{}/todos", id));
+        let res = app.clone().oneshot(project_todos(inbox.id)).await.unwrap();
+        let bytes = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        let page: Page<TodoEntity> = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(page.items, vec![todo.clone()]);
+        let res = app.clone().oneshot(project_todos(other_project.id)).await.unwrap();
+        assert_eq!(StatusCode::NOT_FOUND, res.status());
+
+        // メンバーでないプロジェクトには移動できない
+        let todo_path = format!("/todos/{}", todo.id);
+        let req = build_req_with_json(
+            &todo_path,
+            Method::PATCH,
+            format!(r#"{{ "project_id": {} }}"#, other_project.id),
+        );
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::NOT_FOUND, res.status());
+        let req = build_req_with_json(&todo_path, Method::PATCH, format!(r#"{{ "project_id": {} }}"#, sprint.id));
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(Some(sprint.id), res_to_todo(res).await.project_id);
+        let res = app.clone().oneshot(project_todos(inbox.id)).await.unwrap();
+        let bytes = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        let page: Page<TodoEntity> = serde_json::from_slice(&bytes).unwrap();
+        assert!(page.items.is_empty());
+
+        // todoが残っているプロジェクトは削除できない
+        let req = build_req_with_empty(Method::DELETE, &format!("/projects/{}", sprint.id));
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::CONFLICT, res.status());
+        let req = build_req_with_empty(Method::DELETE, &format!("/projects/{}", inbox.id));
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::NO_CONTENT, res.status());
+        let res = app.oneshot(project_todos(inbox.id)).await.unwrap();
+        assert_eq!(StatusCode::NOT_FOUND, res.status());
+    }
+
+    #[tokio::test]
+    async fn should_disable_features_by_config() {
+        let mut config = Config::default();
+        config.features.admin_endpoints = false;
+        config.features.bulk_update = false;
+        let store = MemoryStore::new();
+        let app = || {
+            create_app(
+                TodoRepositoryForMemory::new(store.clone()),
+                LabelRepositoryForMemory::new(store.clone()),
+                UserRepositoryForMemory::new(store.clone()),
+                ProjectRepositoryForMemory::new(store.clone()),
+                UnitOfWorkForMemory::new(store.clone()),
+                SchemaRepositoryForMemory,
+                HealthRepositoryForMemory,
+                &config,
+            )
+        };
+
+        let req = build_req_with_empty(Method::GET, "/admin/schema");
+        let res = app().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::NOT_FOUND, res.status());
+
+        let req = build_req_with_json("/todos", Method::PATCH, r#"{"todos": []}"#.to_string());
+        let res = app().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::METHOD_NOT_ALLOWED, res.status());
+    }
+
+    // TEST_TOKEN以外のトークンを付けたリクエストを作る
+    fn build_req_with_token(method: Method, path: &str, token: &str, body: Body) -> Request<Body> {
+        Request::builder()
+            .uri(path)
+            .method(method)
+            .header(header::AUTHORIZATION, format!("Bearer {}", token))
+            .header(header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+            .body(body)
+            .unwrap()
+    }
+
     // メソッドやボディを受けとり、リクエストを作る
     fn build_req_with_json(path: &str, method: Method, json_body: String) -> Request<Body> {
         Request::builder()
             .uri(path)
             .method(method)
+            .header(header::AUTHORIZATION, format!("Bearer {}", TEST_TOKEN))
             .header(header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
             .body(Body::from(json_body))
             .unwrap()
@@ -158,6 +1019,7 @@ mod test {
         Request::builder()
             .uri(path)
             .method(method)
+            .header(header::AUTHORIZATION, format!("Bearer {}", TEST_TOKEN))
             .body(Body::empty())
             .unwrap()
     }
@@ -167,7 +1029,7 @@ mod test {
         let bytes = hyper::body::to_bytes(res.into_body()).await.unwrap();
         let body: String = String::from_utf8(bytes.to_vec()).unwrap();
         let todo: TodoEntity = serde_json::from_str(&body)
-            .expect(&format!("cannot convert Todo instance. body: {}", body));
+            .unwrap_or_else(|_| panic!("cannot convert Todo instance. body: {}", body));
         todo
     }
 
@@ -176,7 +1038,7 @@ mod test {
         let bytes = hyper::body::to_bytes(res.into_body()).await.unwrap();
         let body: String = String::from_utf8(bytes.to_vec()).unwrap();
         let label: Label = serde_json::from_str(&body)
-            .expect(&format!("cannot convert Label instance. body: {}", body));
+            .unwrap_or_else(|_| panic!("cannot convert Label instance. body: {}", body));
         label
     }
 
@@ -191,49 +1053,111 @@ mod test {
             r#"{ "text": "should_return_created_todo", "labels": [999] }"#.to_string(),
         );
         // 疑似リクエストでリクエストの検証
-        let res = create_app(
-            TodoRepositoryForMemory::new(labels.clone()),
-            LabelRepositoryForMemory::new(),
-        ).oneshot(req).await.unwrap();
+        let res = create_memory_app(MemoryStore::with_labels(TEST_USER_ID, labels.clone())).oneshot(req).await.unwrap();
         let todo = res_to_todo(res).await;
         assert_eq!(expected, todo);
     }
 
     #[tokio::test]
     async fn should_create_label() {
-        let (labels, _label_ids) = label_fixture();
-
         let expected = Label::new(1, "should_return_created_label".to_string());
         let req = build_req_with_json(
             "/labels",
             Method::POST,
             r#"{ "name": "should_return_created_label" }"#.to_string(),
         );
-        let res = create_app(
-            TodoRepositoryForMemory::new(labels.clone()),
-            LabelRepositoryForMemory::new(),
-        ).oneshot(req).await.unwrap();
+        let res = create_memory_app(MemoryStore::new()).oneshot(req).await.unwrap();
         let label = res_to_label(res).await;
         assert_eq!(expected, label);
     }
 
+    #[tokio::test]
+    async fn should_return_validation_problem() {
+        let (labels, _label_ids) = label_fixture();
+
+        let req = build_req_with_json(
+            "/todos",
+            Method::POST,
+            r#"{ "text": "", "labels": [] }"#.to_string(),
+        );
+        let res = create_memory_app(MemoryStore::with_labels(TEST_USER_ID, labels)).oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::BAD_REQUEST, res.status());
+        assert_eq!(
+            res.headers()[header::CONTENT_TYPE],
+            "application/problem+json"
+        );
+        let bytes = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        let problem: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(problem["status"], 400);
+        assert_eq!(problem["title"], "Bad Request");
+        assert_eq!(problem["errors"]["text"][0]["code"], "length");
+        assert_eq!(problem["errors"]["text"][0]["message"], "Can not be empty");
+    }
+
+    #[tokio::test]
+    async fn should_return_nested_validation_problem() {
+        let req = build_req_with_json(
+            "/todos",
+            Method::PATCH,
+            r#"{ "todos": [
+                { "id": 1, "completed": true },
+                { "id": 2, "text": "" }
+            ] }"#
+            .to_string(),
+        );
+        let res = create_memory_app(MemoryStore::new()).oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::BAD_REQUEST, res.status());
+        let bytes = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        let problem: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(problem["errors"]["todos[1].text"][0]["code"], "length");
+        assert_eq!(problem["errors"].as_object().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn should_reject_unknown_labels() {
+        let (labels, _label_ids) = label_fixture();
+
+        let req = build_req_with_json(
+            "/todos",
+            Method::POST,
+            r#"{ "text": "should_reject_unknown_labels", "labels": [999, 2, 1] }"#.to_string(),
+        );
+        let res = create_memory_app(MemoryStore::with_labels(TEST_USER_ID, labels)).oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::UNPROCESSABLE_ENTITY, res.status());
+        let bytes = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        let problem: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(problem["detail"], "Label not found, ids are [1, 2]");
+        assert_eq!(problem["errors"]["labels"][0]["code"], "unknown_label");
+    }
+
+    #[tokio::test]
+    async fn should_return_not_found_problem() {
+        let (labels, _label_ids) = label_fixture();
+
+        let req = build_req_with_empty(Method::GET, "/todos/1");
+        let res = create_memory_app(MemoryStore::with_labels(TEST_USER_ID, labels)).oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::NOT_FOUND, res.status());
+        let bytes = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        let problem: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(problem["status"], 404);
+        assert_eq!(problem["detail"], "Not Found, id is [1]");
+    }
+
     #[tokio::test]
     async fn should_find_todo() {
         let (labels, label_ids) = label_fixture();
         let expected = TodoEntity::new(1, "should_find_todo".to_string(), labels.clone());
 
         // repositoryを作成し、1件だけ保存してみる
-        let repository = TodoRepositoryForMemory::new(labels.clone());
+        let store = MemoryStore::with_labels(TEST_USER_ID, labels.clone());
+        let repository = TodoRepositoryForMemory::new(store.clone());
         repository
-            .create(CreateTodo::new("should_find_todo".to_string(), label_ids.clone()))
+            .create(TEST_USER_ID, CreateTodo::new("should_find_todo".to_string(), label_ids.clone()))
             .await
             .expect("failed create todo");
 
         let req = build_req_with_empty(Method::GET, "/todos/1");
-        let res = create_app(
-            repository,
-            LabelRepositoryForMemory::new(),
-        ).oneshot(req).await.unwrap();
+        let res = create_memory_app(store.clone()).oneshot(req).await.unwrap();
         let todo = res_to_todo(res).await;
         assert_eq!(expected, todo);
     }
@@ -243,46 +1167,194 @@ mod test {
         let (labels, label_ids) = label_fixture();
         let expected = TodoEntity::new(1, "should_get_all_todos".to_string(), labels.clone());
 
-        let repository = TodoRepositoryForMemory::new(labels.clone());
+        let store = MemoryStore::with_labels(TEST_USER_ID, labels.clone());
+        let repository = TodoRepositoryForMemory::new(store.clone());
         repository
-            .create(CreateTodo::new("should_get_all_todos".to_string(), label_ids.clone()))
+            .create(TEST_USER_ID, CreateTodo::new("should_get_all_todos".to_string(), label_ids.clone()))
             .await
             .expect("failed create todo");
 
         let req = build_req_with_empty(Method::GET, "/todos");
-        let res = create_app(
-            repository,
-            LabelRepositoryForMemory::new(),
-        ).oneshot(req).await.unwrap();
+        let res = create_memory_app(store.clone()).oneshot(req).await.unwrap();
         let bytes = hyper::body::to_bytes(res.into_body()).await.unwrap();
         let body: String = String::from_utf8(bytes.to_vec()).unwrap();
         // todoはベクトルになることに注意
-        let todo: Vec<TodoEntity> = serde_json::from_str(&body)
-            .expect(&format!("cannot convert Todo instance. body: {}", body));
-        assert_eq!(vec![expected], todo);
+        let todo: Page<TodoEntity> = serde_json::from_str(&body)
+            .unwrap_or_else(|_| panic!("cannot convert Todo instance. body: {}", body));
+        assert_eq!(vec![expected], todo.items);
+    }
+
+    #[tokio::test]
+    async fn should_get_overdue_todos() {
+        let store = MemoryStore::with_labels(TEST_USER_ID, vec![]);
+        let repository = TodoRepositoryForMemory::new(store.clone());
+        repository
+            .create(TEST_USER_ID, CreateTodo::new("should_get_overdue_todos".to_string(), vec![]))
+            .await
+            .expect("failed create todo");
+        let overdue = repository
+            .update(
+                TEST_USER_ID,
+                1,
+                serde_json::from_str(r#"{ "due_date": "2022-01-01T00:00:00Z" }"#).unwrap(),
+            )
+            .await
+            .expect("failed update todo");
+
+        let req = build_req_with_empty(
+            Method::GET,
+            "/todos?due_before=2022-06-01T00:00:00Z&overdue=true",
+        );
+        let res = create_memory_app(store.clone()).oneshot(req).await.unwrap();
+        let bytes = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        let body: String = String::from_utf8(bytes.to_vec()).unwrap();
+        let todo: Page<TodoEntity> = serde_json::from_str(&body)
+            .unwrap_or_else(|_| panic!("cannot convert Todo instance. body: {}", body));
+        assert_eq!(vec![overdue], todo.items);
+    }
+
+    #[tokio::test]
+    async fn should_clear_due_date() {
+        let app = create_memory_app(MemoryStore::new());
+        let req = build_req_with_json(
+            "/todos",
+            Method::POST,
+            r#"{ "text": "should_clear_due_date", "labels": [], "due_date": "2022-01-01T00:00:00Z" }"#.to_string(),
+        );
+        let todo = res_to_todo(app.clone().oneshot(req).await.unwrap()).await;
+        assert!(todo.due_date.is_some());
+
+        // 省略したフィールドは変わらず、nullを指定すると消える
+        let path = format!("/todos/{}", todo.id);
+        let req = build_req_with_json(&path, Method::PATCH, r#"{ "completed": true }"#.to_string());
+        let todo = res_to_todo(app.clone().oneshot(req).await.unwrap()).await;
+        assert!(todo.due_date.is_some());
+        let req = build_req_with_json(&path, Method::PATCH, r#"{ "due_date": null }"#.to_string());
+        let todo = res_to_todo(app.oneshot(req).await.unwrap()).await;
+        assert_eq!(todo.due_date, None);
+        assert!(todo.completed);
+    }
+
+    #[tokio::test]
+    async fn should_filter_todos_by_query() {
+        let (labels, label_ids) = label_fixture();
+        let expected = TodoEntity::new(1, "should_filter_todos".to_string(), labels.clone());
+
+        let store = MemoryStore::with_labels(TEST_USER_ID, labels.clone());
+        let repository = TodoRepositoryForMemory::new(store.clone());
+        repository
+            .create(TEST_USER_ID, CreateTodo::new("should_filter_todos".to_string(), label_ids.clone()))
+            .await
+            .expect("failed create todo");
+        repository
+            .create(TEST_USER_ID, CreateTodo::new("without label".to_string(), vec![]))
+            .await
+            .expect("failed create todo");
+
+        let req = build_req_with_empty(
+            Method::GET,
+            "/todos?completed=false&label=999&label=999&label_match=all&text=FILTER",
+        );
+        let res = create_memory_app(store.clone()).oneshot(req).await.unwrap();
+        let bytes = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        let body: String = String::from_utf8(bytes.to_vec()).unwrap();
+        let todo: Page<TodoEntity> = serde_json::from_str(&body)
+            .unwrap_or_else(|_| panic!("cannot convert Todo instance. body: {}", body));
+        assert_eq!(vec![expected], todo.items);
+
+        // 不正なクエリは400を返し、内部の型名はdetailに含めない
+        let req = build_req_with_empty(Method::GET, "/todos?label=abc");
+        let res = create_memory_app(store.clone()).oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::BAD_REQUEST, res.status());
+        let bytes = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        let problem: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(problem["detail"], "Invalid query parameter: label");
+        for (method, path) in [
+            (Method::GET, "/todos?sort=unknown"),
+            (Method::GET, "/labels?limit=abc"),
+            (Method::DELETE, "/labels/1?mode=unknown"),
+        ] {
+            let req = build_req_with_empty(method, path);
+            let res = create_memory_app(store.clone()).oneshot(req).await.unwrap();
+            assert_eq!(StatusCode::BAD_REQUEST, res.status());
+            let bytes = hyper::body::to_bytes(res.into_body()).await.unwrap();
+            let problem: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+            assert_eq!(problem["detail"], "Invalid query parameter");
+        }
+    }
+
+    #[tokio::test]
+    async fn should_paginate_todos() {
+        let store = MemoryStore::with_labels(TEST_USER_ID, vec![]);
+        let repository = TodoRepositoryForMemory::new(store.clone());
+        for i in 1..=3 {
+            repository
+                .create(TEST_USER_ID, CreateTodo::new(format!("should_paginate_todos {}", i), vec![]))
+                .await
+                .expect("failed create todo");
+        }
+
+        let req = build_req_with_empty(Method::GET, "/todos?limit=2&completed=false");
+        let res = create_memory_app(store.clone()).oneshot(req).await.unwrap();
+        let link = res.headers()[header::LINK].to_str().unwrap().to_string();
+        let bytes = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        let page: Page<TodoEntity> = serde_json::from_slice(&bytes).unwrap();
+        let ids: Vec<i32> = page.items.iter().map(|todo| todo.id).collect();
+        assert_eq!(ids, vec![3, 2]);
+        let next_cursor = page.next_cursor.expect("next_cursor is missing");
+        assert_eq!(
+            link,
+            format!("</todos?limit=2&completed=false&cursor={}>; rel=\"next\"", next_cursor)
+        );
+
+        // 最後のページにはnext_cursorもLinkヘッダーもない
+        let req = build_req_with_empty(
+            Method::GET,
+            &format!("/todos?limit=2&completed=false&cursor={}", next_cursor),
+        );
+        let res = create_memory_app(store.clone()).oneshot(req).await.unwrap();
+        assert!(res.headers().get(header::LINK).is_none());
+        let bytes = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        let page: Page<TodoEntity> = serde_json::from_slice(&bytes).unwrap();
+        let ids: Vec<i32> = page.items.iter().map(|todo| todo.id).collect();
+        assert_eq!(ids, vec![1]);
+        assert_eq!(page.next_cursor, None);
+
+        // 不正なcursorは400を返す
+        let req = build_req_with_empty(Method::GET, "/todos?cursor=invalid");
+        let res = create_memory_app(store.clone()).oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::BAD_REQUEST, res.status());
+
+        // 別の並び順で作られたcursorも400を返す
+        let req = build_req_with_empty(
+            Method::GET,
+            &format!("/todos?limit=2&sort=priority&cursor={}", next_cursor),
+        );
+        let res = create_memory_app(store.clone()).oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::BAD_REQUEST, res.status());
+        let req = build_req_with_empty(Method::GET, &format!("/labels?cursor={}", next_cursor));
+        let res = create_memory_app(store.clone()).oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::BAD_REQUEST, res.status());
     }
 
     #[tokio::test]
     async fn should_get_all_labels() {
-        let (labels, _label_ids) = label_fixture();
         let expected = Label::new(1, "should_get_all_labels".to_string());
 
-        let repository = LabelRepositoryForMemory::new();
+        let store = MemoryStore::new();
+        let repository = LabelRepositoryForMemory::new(store.clone());
         repository
-            .create("should_get_all_labels".to_string())
+            .create(TEST_USER_ID, CreateLabel::new("should_get_all_labels".to_string()))
             .await
             .expect("failed create label");
 
         let req = build_req_with_empty(Method::GET, "/labels");
-        let res = create_app(
-            TodoRepositoryForMemory::new(labels.clone()),
-            repository,
-        ).oneshot(req).await.unwrap();
+        let res = create_memory_app(store.clone()).oneshot(req).await.unwrap();
         let bytes = hyper::body::to_bytes(res.into_body()).await.unwrap();
         let body: String = String::from_utf8(bytes.to_vec()).unwrap();
-        let label: Vec<Label> = serde_json::from_str(&body)
-            .expect(&format!("cannot convert Label instance. body: {}", body));
-        assert_eq!(vec![expected], label);
+        let label: Page<Label> = serde_json::from_str(&body)
+            .unwrap_or_else(|_| panic!("cannot convert Label instance. body: {}", body));
+        assert_eq!(vec![expected], label.items);
     }
 
     #[tokio::test]
@@ -290,9 +1362,10 @@ mod test {
         let (labels, label_ids) = label_fixture();
         let expected = TodoEntity::new(1, "should_update_todo".to_string(), labels.clone());
 
-        let repository = TodoRepositoryForMemory::new(labels.clone());
+        let store = MemoryStore::with_labels(TEST_USER_ID, labels.clone());
+        let repository = TodoRepositoryForMemory::new(store.clone());
         repository
-            .create(CreateTodo::new("should_update_todo".to_string(), label_ids.clone()))
+            .create(TEST_USER_ID, CreateTodo::new("should_update_todo".to_string(), label_ids.clone()))
             .await
             .expect("failed create todo");
 
@@ -306,47 +1379,389 @@ mod test {
             }"#
             .to_string(),
         );
-        let res = create_app(
-            repository,
-            LabelRepositoryForMemory::new(),
-        ).oneshot(req).await.unwrap();
+        let res = create_memory_app(store.clone()).oneshot(req).await.unwrap();
         let todo = res_to_todo(res).await;
         assert_eq!(expected, todo);
     }
 
+    #[tokio::test]
+    async fn should_bulk_update_todos() {
+        let (labels, label_ids) = label_fixture();
+
+        let store = MemoryStore::with_labels(TEST_USER_ID, labels.clone());
+        let repository = TodoRepositoryForMemory::new(store.clone());
+        for text in ["first", "second"] {
+            repository
+                .create(TEST_USER_ID, CreateTodo::new(text.to_string(), label_ids.clone()))
+                .await
+                .expect("failed create todo");
+        }
+
+        let req = build_req_with_json(
+            "/todos",
+            Method::PATCH,
+            r#"{ "todos": [
+                { "id": 1, "completed": true },
+                { "id": 2, "completed": true }
+            ] }"#
+            .to_string(),
+        );
+        let res = create_memory_app(store.clone()).oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        let bytes = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        let todos: Vec<TodoEntity> = serde_json::from_slice(&bytes).unwrap();
+        assert!(todos.iter().all(|todo| todo.completed));
+
+        // 存在しないtodoが含まれていれば、それまでの更新も取り消される
+        let req = build_req_with_json(
+            "/todos",
+            Method::PATCH,
+            r#"{ "todos": [
+                { "id": 1, "completed": false },
+                { "id": 3, "completed": false }
+            ] }"#
+            .to_string(),
+        );
+        let res = create_memory_app(store.clone()).oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::NOT_FOUND, res.status());
+        let todo = repository.find(TEST_USER_ID, 1).await.expect("failed find todo");
+        assert!(todo.completed);
+
+        // 一部のtodoだけが検証に失敗した場合も、どのtodoも更新されない
+        let req = build_req_with_json(
+            "/todos",
+            Method::PATCH,
+            r#"{ "todos": [
+                { "id": 1, "text": "valid text" },
+                { "id": 2, "text": "" }
+            ] }"#
+            .to_string(),
+        );
+        let res = create_memory_app(store.clone()).oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::BAD_REQUEST, res.status());
+        let bytes = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        let problem: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(problem["errors"]["todos[1].text"][0]["code"], "length");
+        let todo = repository.find(TEST_USER_ID, 1).await.expect("failed find todo");
+        assert_eq!(todo.text, "first");
+    }
+
+    #[tokio::test]
+    async fn should_bulk_update_labels() {
+        let store = MemoryStore::new();
+        let repository = LabelRepositoryForMemory::new(store.clone());
+        for name in ["first", "second"] {
+            repository
+                .create(TEST_USER_ID, CreateLabel::new(name.to_string()))
+                .await
+                .expect("failed create label");
+        }
+
+        let req = build_req_with_json(
+            "/labels",
+            Method::PATCH,
+            r##"{ "labels": [
+                { "id": 1, "color": "#ff0000" },
+                { "id": 2, "name": "renamed" }
+            ] }"##
+            .to_string(),
+        );
+        let res = create_memory_app(store.clone()).oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        let bytes = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        let labels: Vec<Label> = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(labels[0].color.as_deref(), Some("#ff0000"));
+        assert_eq!(labels[1].name, "renamed");
+
+        // 一部のラベルだけが検証に失敗した場合も、どのラベルも更新されない
+        let req = build_req_with_json(
+            "/labels",
+            Method::PATCH,
+            r#"{ "labels": [
+                { "id": 1, "name": "valid name" },
+                { "id": 2, "color": "red" }
+            ] }"#
+            .to_string(),
+        );
+        let res = create_memory_app(store.clone()).oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::BAD_REQUEST, res.status());
+        let bytes = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        let problem: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(problem["errors"]["labels[1].color"][0]["code"], "hex_color");
+        assert_eq!(problem["errors"].as_object().unwrap().len(), 1);
+
+        // 検証を通っても、途中で重複エラーになればそれまでの更新は取り消される
+        let req = build_req_with_json(
+            "/labels",
+            Method::PATCH,
+            r#"{ "labels": [
+                { "id": 1, "name": "valid name" },
+                { "id": 2, "name": "valid name" }
+            ] }"#
+            .to_string(),
+        );
+        let res = create_memory_app(store.clone()).oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::CONFLICT, res.status());
+        let label = repository.find(TEST_USER_ID, 1).await.expect("failed find label");
+        assert_eq!(label.name, "first");
+        assert_eq!(label.color.as_deref(), Some("#ff0000"));
+    }
+
+    #[tokio::test]
+    async fn should_find_label() {
+        let expected = Label::new(1, "should_find_label".to_string());
+
+        let store = MemoryStore::new();
+        let repository = LabelRepositoryForMemory::new(store.clone());
+        repository
+            .create(TEST_USER_ID, CreateLabel::new("should_find_label".to_string()))
+            .await
+            .expect("failed create label");
+
+        let req = build_req_with_empty(Method::GET, "/labels/1");
+        let res = create_memory_app(store.clone()).oneshot(req).await.unwrap();
+        let label = res_to_label(res).await;
+        assert_eq!(expected, label);
+    }
+
+    #[tokio::test]
+    async fn should_update_label() {
+        let expected = Label::new(1, "should_update_label".to_string());
+
+        let store = MemoryStore::new();
+        let repository = LabelRepositoryForMemory::new(store.clone());
+        repository
+            .create(TEST_USER_ID, CreateLabel::new("before update".to_string()))
+            .await
+            .expect("failed create label");
+        repository
+            .create(TEST_USER_ID, CreateLabel::new("other label".to_string()))
+            .await
+            .expect("failed create label");
+
+        let req = build_req_with_json(
+            "/labels/1",
+            Method::PATCH,
+            r#"{ "name": "should_update_label" }"#.to_string(),
+        );
+        let res = create_memory_app(store.clone()).oneshot(req).await.unwrap();
+        let label = res_to_label(res).await;
+        assert_eq!(expected, label);
+
+        // 他のラベルと同じ名前には変更できない
+        let req = build_req_with_json(
+            "/labels/1",
+            Method::PATCH,
+            r#"{ "name": "other label" }"#.to_string(),
+        );
+        let res = create_memory_app(store.clone()).oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::CONFLICT, res.status());
+    }
+
+    #[tokio::test]
+    async fn should_clear_label_color_and_description() {
+        let app = create_memory_app(MemoryStore::new());
+        let req = build_req_with_json(
+            "/labels",
+            Method::POST,
+            r##"{ "name": "should_clear_label", "color": "#ff0000", "description": "desc" }"##.to_string(),
+        );
+        let label = res_to_label(app.clone().oneshot(req).await.unwrap()).await;
+        assert_eq!(label.color.as_deref(), Some("#ff0000"));
+
+        // 省略したフィールドは変わらず、nullを指定すると消える
+        let path = format!("/labels/{}", label.id);
+        let req = build_req_with_json(&path, Method::PATCH, r#"{ "color": null }"#.to_string());
+        let label = res_to_label(app.clone().oneshot(req).await.unwrap()).await;
+        assert_eq!(label.color, None);
+        assert_eq!(label.description.as_deref(), Some("desc"));
+        let req = build_req_with_json(&path, Method::PATCH, r#"{ "description": null }"#.to_string());
+        let label = res_to_label(app.clone().oneshot(req).await.unwrap()).await;
+        assert_eq!(label.description, None);
+
+        // 値を指定した場合は今まで通り検証する
+        let req = build_req_with_json(&path, Method::PATCH, r#"{ "color": "red" }"#.to_string());
+        let res = app.oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::BAD_REQUEST, res.status());
+    }
+
+    #[tokio::test]
+    async fn should_manage_subtasks() {
+        let app = create_memory_app(MemoryStore::new());
+        let create = |text: &str, parent_id: Option<i32>| {
+            let parent_id = parent_id.map_or("null".to_string(), |id| id.to_string());
+            build_req_with_json(
+                "/todos",
+                Method::POST,
+                format!(r#"{{ "text": "{}", "labels": [], "parent_id": {} }}"#, text, parent_id),
+            )
+        };
+        let root = res_to_todo(app.clone().oneshot(create("root", None)).await.unwrap()).await;
+        let child = res_to_todo(app.clone().oneshot(create("child", Some(root.id))).await.unwrap()).await;
+        let grandchild = res_to_todo(app.clone().oneshot(create("grandchild", Some(child.id))).await.unwrap()).await;
+        assert_eq!(grandchild.parent_id, Some(child.id));
+
+        // 存在しない親は404
+        let res = app.clone().oneshot(create("orphan", Some(-1))).await.unwrap();
+        assert_eq!(StatusCode::NOT_FOUND, res.status());
+
+        let children_path = format!("/todos/{}/children", root.id);
+        let req = build_req_with_empty(Method::GET, &children_path);
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        let bytes = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        let nodes: Vec<TodoNode> = serde_json::from_slice(&bytes).unwrap();
+        let nodes: Vec<(i32, i32)> = nodes.iter().map(|node| (node.todo.id, node.depth)).collect();
+        assert_eq!(nodes, vec![(child.id, 1), (grandchild.id, 2)]);
+
+        // cascade_completedを指定すると、子孫もまとめて完了になる
+        let root_path = format!("/todos/{}", root.id);
+        let req = build_req_with_json(
+            &root_path,
+            Method::PATCH,
+            r#"{ "completed": true, "cascade_completed": true }"#.to_string(),
+        );
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::CREATED, res.status());
+        let req = build_req_with_empty(Method::GET, &format!("/todos/{}", grandchild.id));
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert!(res_to_todo(res).await.completed);
+
+        // 親を削除すると子孫も削除される
+        let req = build_req_with_empty(Method::DELETE, &root_path);
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::NO_CONTENT, res.status());
+        let req = build_req_with_empty(Method::GET, &format!("/todos/{}", grandchild.id));
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::NOT_FOUND, res.status());
+        let req = build_req_with_empty(Method::GET, &children_path);
+        let res = app.oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::NOT_FOUND, res.status());
+    }
+
+    #[tokio::test]
+    async fn should_reject_subtask_in_another_project() {
+        let app = create_memory_app(MemoryStore::new());
+        let mut project_ids = vec![];
+        for name in ["first", "second"] {
+            let req = build_req_with_json("/projects", Method::POST, format!(r#"{{"name": "{}"}}"#, name));
+            let res = app.clone().oneshot(req).await.unwrap();
+            let bytes = hyper::body::to_bytes(res.into_body()).await.unwrap();
+            let project: Project = serde_json::from_slice(&bytes).unwrap();
+            project_ids.push(project.id);
+        }
+        let create = |text: &str, parent_id: i32, project_id: Option<i32>| {
+            let project_id = project_id.map_or("null".to_string(), |id| id.to_string());
+            build_req_with_json(
+                "/todos",
+                Method::POST,
+                format!(
+                    r#"{{ "text": "{}", "labels": [], "parent_id": {}, "project_id": {} }}"#,
+                    text, parent_id, project_id
+                ),
+            )
+        };
+        let req = build_req_with_json(
+            "/todos",
+            Method::POST,
+            format!(r#"{{ "text": "root", "labels": [], "project_id": {} }}"#, project_ids[0]),
+        );
+        let root = res_to_todo(app.clone().oneshot(req).await.unwrap()).await;
+
+        // 親と別のプロジェクトは指定できない
+        let res = app.clone().oneshot(create("other", root.id, Some(project_ids[1]))).await.unwrap();
+        assert_eq!(StatusCode::BAD_REQUEST, res.status());
+
+        // 親と同じプロジェクトか、指定しなければ親のプロジェクトに入る
+        for project_id in [Some(project_ids[0]), None] {
+            let res = app.clone().oneshot(create("child", root.id, project_id)).await.unwrap();
+            assert_eq!(StatusCode::CREATED, res.status());
+            assert_eq!(res_to_todo(res).await.project_id, Some(project_ids[0]));
+        }
+    }
+
+    #[tokio::test]
+    async fn should_manage_checklist() {
+        let app = create_memory_app(MemoryStore::new());
+        let req = build_req_with_json(
+            "/todos",
+            Method::POST,
+            r#"{ "text": "should_manage_checklist", "labels": [] }"#.to_string(),
+        );
+        let todo = res_to_todo(app.clone().oneshot(req).await.unwrap()).await;
+        assert_eq!(todo.progress, None);
+
+        let checklist_path = format!("/todos/{}/checklist", todo.id);
+        for text in ["first", "second"] {
+            let req = build_req_with_json(&checklist_path, Method::POST, format!(r#"{{ "text": "{}" }}"#, text));
+            let res = app.clone().oneshot(req).await.unwrap();
+            assert_eq!(StatusCode::CREATED, res.status());
+        }
+        let req = build_req_with_json(&checklist_path, Method::POST, r#"{ "text": "" }"#.to_string());
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::BAD_REQUEST, res.status());
+
+        let req = build_req_with_json(&format!("{}/1", checklist_path), Method::PATCH, r#"{ "done": true }"#.to_string());
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        let todo = res_to_todo(res).await;
+        assert_eq!(todo.progress, Some(0.5));
+
+        // 並べ替えには全ての項目を指定する
+        let req = build_req_with_json(&checklist_path, Method::PUT, r#"{ "item_ids": [2] }"#.to_string());
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::BAD_REQUEST, res.status());
+        let req = build_req_with_json(&checklist_path, Method::PUT, r#"{ "item_ids": [2, 1] }"#.to_string());
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        let todo = res_to_todo(res).await;
+        let items: Vec<(i32, bool)> = todo.checklist.iter().map(|item| (item.id, item.done)).collect();
+        assert_eq!(items, vec![(2, false), (1, true)]);
+
+        let req = build_req_with_empty(Method::DELETE, &format!("{}/2", checklist_path));
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        assert_eq!(res_to_todo(res).await.progress, Some(1.0));
+        let req = build_req_with_empty(Method::DELETE, &format!("{}/2", checklist_path));
+        let res = app.oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::NOT_FOUND, res.status());
+    }
+
     #[tokio::test]
     async fn should_delete_todo() {
         let (labels, label_ids) = label_fixture();
 
-        let repository = TodoRepositoryForMemory::new(labels.clone());
+        let store = MemoryStore::with_labels(TEST_USER_ID, labels.clone());
+        let repository = TodoRepositoryForMemory::new(store.clone());
         repository
-            .create(CreateTodo::new("should_delete_todo".to_string(), label_ids.clone()))
+            .create(TEST_USER_ID, CreateTodo::new("should_delete_todo".to_string(), label_ids.clone()))
             .await
             .expect("failed create todo");
 
         let req = build_req_with_empty(Method::DELETE, "/todos/1");
-        let res = create_app(
-            repository,
-            LabelRepositoryForMemory::new(),
-        ).oneshot(req).await.unwrap();
+        let res = create_memory_app(store.clone()).oneshot(req).await.unwrap();
         assert_eq!(StatusCode::NO_CONTENT, res.status());
     }
 
     #[tokio::test]
     async fn should_delete_label() {
-        let (labels, _label_ids) = label_fixture();
-
-        let repository = LabelRepositoryForMemory::new();
+        let store = MemoryStore::new();
+        let repository = LabelRepositoryForMemory::new(store.clone());
         repository
-            .create("should_delete_label".to_string())
+            .create(TEST_USER_ID, CreateLabel::new("should_delete_label".to_string()))
             .await
             .expect("failed create label");
 
+        let req = build_req_with_empty(Method::DELETE, "/labels/1?mode=detach");
+        let res = create_memory_app(store.clone()).oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        let bytes = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        let deleted: DeletedLabel = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(DeletedLabel { id: 1, affected_todos: 0 }, deleted);
+
+        // 削除済みのラベルは404
         let req = build_req_with_empty(Method::DELETE, "/labels/1");
-        let res = create_app(
-            TodoRepositoryForMemory::new(labels.clone()),
-            repository,
-        ).oneshot(req).await.unwrap();
-        assert_eq!(StatusCode::NO_CONTENT, res.status());
+        let res = create_memory_app(store.clone()).oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::NOT_FOUND, res.status());
     }
 }
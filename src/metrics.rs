@@ -0,0 +1,200 @@
+use axum::{
+    extract::MatchedPath,
+    http::{Request, Response},
+};
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounterVec, IntGaugeVec, Opts, Registry, TextEncoder,
+};
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+use tower::{Layer, Service};
+
+use crate::repositories::health::PoolStatus;
+
+// /metricsで公開する、Prometheus形式のメトリクス
+#[derive(Debug, Clone)]
+pub struct Metrics {
+    registry: Registry,
+    http_requests: IntCounterVec,
+    http_request_duration: HistogramVec,
+    repository_duration: HistogramVec,
+    pool_connections: IntGaugeVec,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        let registry = Registry::new();
+        let http_requests = IntCounterVec::new(
+            Opts::new("http_requests_total", "Number of HTTP requests"),
+            &["method", "route", "status"],
+        )
+        .unwrap();
+        let http_request_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "http_request_duration_seconds",
+                "HTTP request latency in seconds",
+            ),
+            &["method", "route"],
+        )
+        .unwrap();
+        let repository_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "repository_operation_duration_seconds",
+                "Repository operation latency in seconds",
+            ),
+            &["repository", "operation", "result"],
+        )
+        .unwrap();
+        let pool_connections = IntGaugeVec::new(
+            Opts::new("db_pool_connections", "Database pool connections by state"),
+            &["state"],
+        )
+        .unwrap();
+
+        registry.register(Box::new(http_requests.clone())).unwrap();
+        registry.register(Box::new(http_request_duration.clone())).unwrap();
+        registry.register(Box::new(repository_duration.clone())).unwrap();
+        registry.register(Box::new(pool_connections.clone())).unwrap();
+
+        Self {
+            registry,
+            http_requests,
+            http_request_duration,
+            repository_duration,
+            pool_connections,
+        }
+    }
+
+    // routeは`/todos/:id`のようなテンプレートを渡し、idごとに系列が増えないようにする
+    pub fn observe_request(&self, method: &str, route: &str, status: u16, elapsed: Duration) {
+        self.http_requests
+            .with_label_values(&[method, route, &status.to_string()])
+            .inc();
+        self.http_request_duration
+            .with_label_values(&[method, route])
+            .observe(elapsed.as_secs_f64());
+    }
+
+    pub fn observe_repository(&self, repository: &str, operation: &str, ok: bool, elapsed: Duration) {
+        let result = if ok { "ok" } else { "error" };
+        self.repository_duration
+            .with_label_values(&[repository, operation, result])
+            .observe(elapsed.as_secs_f64());
+    }
+
+    // プールの状態は、スクレイプのたびに取り直す
+    pub fn set_pool_status(&self, status: Option<PoolStatus>) {
+        if let Some(status) = status {
+            let gauges = [
+                ("idle", status.idle),
+                ("in_use", status.in_use),
+                ("max", status.max_connections),
+            ];
+            for (state, value) in gauges {
+                self.pool_connections
+                    .with_label_values(&[state])
+                    .set(value as i64);
+            }
+        }
+    }
+
+    pub fn encode(&self) -> anyhow::Result<String> {
+        let mut buffer = Vec::new();
+        TextEncoder::new().encode(&self.registry.gather(), &mut buffer)?;
+        Ok(String::from_utf8(buffer)?)
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// ルートごとにリクエスト数とレイテンシを記録するミドルウェア
+// MatchedPathを使うので、Router::route_layerで追加する
+#[derive(Debug, Clone)]
+pub struct MetricsLayer {
+    metrics: Arc<Metrics>,
+}
+
+impl MetricsLayer {
+    pub fn new(metrics: Arc<Metrics>) -> Self {
+        Self { metrics }
+    }
+}
+
+impl<S> Layer<S> for MetricsLayer {
+    type Service = MetricsService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        MetricsService {
+            inner,
+            metrics: self.metrics.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MetricsService<S> {
+    inner: S,
+    metrics: Arc<Metrics>,
+}
+
+impl<S, ReqBody, ResBody> Service<Request<ReqBody>> for MetricsService<S>
+where
+    S: Service<Request<ReqBody>, Response = Response<ResBody>> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+    ReqBody: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<ReqBody>) -> Self::Future {
+        let method = req.method().to_string();
+        let route = req
+            .extensions()
+            .get::<MatchedPath>()
+            .map(|path| path.as_str().to_string())
+            .unwrap_or_else(|| "unknown".to_string());
+        let metrics = self.metrics.clone();
+        let started = Instant::now();
+        let future = self.inner.call(req);
+        Box::pin(async move {
+            let res = future.await?;
+            metrics.observe_request(&method, &route, res.status().as_u16(), started.elapsed());
+            Ok(res)
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn should_encode_metrics() {
+        let metrics = Metrics::new();
+        metrics.observe_request("GET", "/todos/:id", 200, Duration::from_millis(5));
+        metrics.observe_repository("todo", "find", true, Duration::from_millis(1));
+        metrics.set_pool_status(Some(PoolStatus::new(3, 1, 10)));
+
+        let text = metrics.encode().expect("fail encode metrics");
+        assert!(text.contains(r#"http_requests_total{method="GET",route="/todos/:id",status="200"} 1"#));
+        assert!(text.contains(
+            r#"repository_operation_duration_seconds_count{operation="find",repository="todo",result="ok"} 1"#
+        ));
+        assert!(text.contains(r#"db_pool_connections{state="in_use"} 2"#));
+        assert!(text.contains(r#"db_pool_connections{state="max"} 10"#));
+    }
+}
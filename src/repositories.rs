@@ -1,14 +1,228 @@
+pub mod health;
+pub mod instrumented;
 pub mod label;
+#[cfg(any(test, feature = "memory-storage"))]
+pub mod memory;
+pub mod project;
+pub mod schema;
 pub mod todo;
+pub mod unit_of_work;
+pub mod user;
 
+use serde::{Deserialize, Deserializer, Serialize};
 use thiserror::Error;
 
 #[derive(Debug, Error)]
-enum RepositoryError {
+pub enum RepositoryError {
     #[error("Unexpected Error: [{0}]")]
     Unexpected(String),
     #[error("Not Found, id is [{0}]")]
     NotFound(i32),
     #[error("Duplicate data, id is {0}")]
     Duplicate(i32),
+    #[error("Data is in use by {1} rows, id is [{0}]")]
+    InUse(i32, i64),
+    #[error("Label not found, ids are {0:?}")]
+    LabelNotFound(Vec<i32>),
+    #[error("Project must have at least one owner, id is [{0}]")]
+    NoOwner(i32),
+    #[error("item_ids must contain every checklist item exactly once, id is [{0}]")]
+    ChecklistMismatch(i32),
+    // 見つからなかったメールアドレスやidは返さない
+    #[error("User not found")]
+    UserNotFound,
+}
+
+// PATCHのペイロードで、フィールドがなければNone、nullならSome(None)として受け取る
+// Option<T>のままだと、値を消す指定（null）とフィールドの省略を区別できない
+// #[serde(default, deserialize_with = "nullable")]と合わせて使う
+pub fn nullable<'de, T, D>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    T: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+// 一意制約の違反かどうか（Postgresは23505、SQLiteはSQLITE_CONSTRAINT_UNIQUEの2067）
+fn is_unique_violation(e: &sqlx::Error) -> bool {
+    match e {
+        sqlx::Error::Database(e) => matches!(e.code().as_deref(), Some("23505") | Some("2067")),
+        _ => false,
+    }
+}
+
+pub const DEFAULT_PAGE_LIMIT: i64 = 50;
+
+// カーソルを作ったときの並び順（並べるフィールドと向き）
+// 別の並び順のリクエストにカーソルを使うと、keyの意味が変わってしまうので区別する
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CursorOrder {
+    IdAsc,
+    IdDesc,
+    PriorityDesc,
+}
+
+impl CursorOrder {
+    fn as_str(self) -> &'static str {
+        match self {
+            CursorOrder::IdAsc => "id.asc",
+            CursorOrder::IdDesc => "id.desc",
+            CursorOrder::PriorityDesc => "priority.desc",
+        }
+    }
+
+    fn parse(order: &str) -> Option<Self> {
+        [CursorOrder::IdAsc, CursorOrder::IdDesc, CursorOrder::PriorityDesc]
+            .into_iter()
+            .find(|candidate| candidate.as_str() == order)
+    }
+}
+
+// キーセットページネーションの位置
+// keyは並び順に使う値（優先度など）、idは同じkeyの中での位置を表す
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cursor {
+    pub order: CursorOrder,
+    pub key: i32,
+    pub id: i32,
+}
+
+impl Cursor {
+    // クライアントには中身を意識させないようにbase64で包んで渡す
+    pub fn encode(&self) -> String {
+        base64::encode_config(
+            format!("{}:{}:{}", self.order.as_str(), self.key, self.id),
+            base64::URL_SAFE_NO_PAD,
+        )
+    }
+
+    pub fn decode(cursor: &str) -> Option<Self> {
+        let bytes = base64::decode_config(cursor, base64::URL_SAFE_NO_PAD).ok()?;
+        let text = String::from_utf8(bytes).ok()?;
+        let mut parts = text.split(':');
+        let cursor = Cursor {
+            order: CursorOrder::parse(parts.next()?)?,
+            key: parts.next()?.parse().ok()?,
+            id: parts.next()?.parse().ok()?,
+        };
+        match parts.next() {
+            Some(_) => None,
+            None => Some(cursor),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PageRequest {
+    pub limit: i64,
+    pub after: Option<Cursor>,
+}
+
+impl PageRequest {
+    // カーソルが、今のリクエストと同じ並び順で作られたものかどうか
+    pub fn matches_order(&self, order: CursorOrder) -> bool {
+        self.after.is_none_or(|cursor| cursor.order == order)
+    }
+}
+
+impl Default for PageRequest {
+    fn default() -> Self {
+        PageRequest {
+            limit: DEFAULT_PAGE_LIMIT,
+            after: None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub next_cursor: Option<String>,
+}
+
+impl<T> Page<T> {
+    // limit + 1件取得したitemsから、次のページがあるかどうかを判定してPageを作る
+    fn from_overfetched(mut items: Vec<T>, limit: i64, cursor_of: impl Fn(&T) -> Cursor) -> Self {
+        let limit = limit as usize;
+        let next_cursor = if items.len() > limit {
+            items.truncate(limit);
+            items.last().map(|item| cursor_of(item).encode())
+        } else {
+            None
+        };
+        Page { items, next_cursor }
+    }
+}
+
+#[cfg(test)]
+pub mod test_utils {
+    use sqlx::{sqlite::SqlitePoolOptions, SqlitePool};
+
+    use super::{
+        schema::{SchemaRepository, SchemaRepositoryForSqlite},
+        user::{CreateUser, UserRepository, UserRepositoryForSqlite},
+    };
+
+    // マイグレーション済みのインメモリSQLiteを作る
+    // インメモリのDBはコネクションごとに別物になるので、コネクションは1本に絞る
+    pub async fn sqlite_pool() -> SqlitePool {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .expect("fail connect in-memory sqlite");
+        SchemaRepositoryForSqlite::new(pool.clone())
+            .migrate()
+            .await
+            .expect("fail migrate in-memory sqlite");
+        pool
+    }
+
+    // todoとラベルの持ち主になるユーザーを作り、そのidを返す
+    pub async fn sqlite_user(pool: &SqlitePool, email: &str) -> i32 {
+        UserRepositoryForSqlite::new(pool.clone())
+            .create(CreateUser::new(email))
+            .await
+            .expect("fail create user")
+            .id
+    }
+
+    // 同じデータベースで何度実行しても重複しないように、メールアドレスに時刻を付ける
+    #[cfg(feature = "database-test")]
+    pub async fn postgres_user(pool: &sqlx::PgPool, name: &str) -> i32 {
+        let email = format!("{}_{}@example.com", name, chrono::Utc::now().timestamp_micros());
+        super::user::UserRepositoryForDb::new(pool.clone())
+            .create(CreateUser::new(&email))
+            .await
+            .expect("fail create user")
+            .id
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn cursor_round_trip() {
+        let cursor = Cursor { order: CursorOrder::PriorityDesc, key: 3, id: 42 };
+        assert_eq!(Cursor::decode(&cursor.encode()), Some(cursor));
+        assert_eq!(Cursor::decode("not a cursor"), None);
+        // 並び順を含まない古い形式のカーソルは受け付けない
+        let legacy = base64::encode_config("3:42", base64::URL_SAFE_NO_PAD);
+        assert_eq!(Cursor::decode(&legacy), None);
+    }
+
+    #[test]
+    fn page_from_overfetched() {
+        let cursor_of = |id: &i32| Cursor { order: CursorOrder::IdDesc, key: 0, id: *id };
+        let page = Page::from_overfetched(vec![3, 2, 1], 2, cursor_of);
+        assert_eq!(page.items, vec![3, 2]);
+        assert_eq!(page.next_cursor, Some(cursor_of(&2).encode()));
+
+        let page = Page::from_overfetched(vec![3, 2], 2, cursor_of);
+        assert_eq!(page.items, vec![3, 2]);
+        assert_eq!(page.next_cursor, None);
+    }
 }
@@ -0,0 +1,118 @@
+use axum::async_trait;
+use serde::{Deserialize, Serialize};
+use sqlx::{Connection, Database, Pool, Postgres};
+
+// インスタンスがリクエストを受けられる状態かを確認する
+#[async_trait]
+pub trait HealthRepository: Clone + Send + Sync + 'static {
+    async fn ping(&self) -> anyhow::Result<()>;
+    fn pool_status(&self) -> Option<PoolStatus>;
+}
+
+// コネクションプールの使用状況
+// saturationは最大接続数に対する使用中の接続の割合（0.0〜1.0）
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct PoolStatus {
+    pub size: u32,
+    pub idle: u32,
+    pub in_use: u32,
+    pub max_connections: u32,
+    pub saturation: f64,
+}
+
+impl PoolStatus {
+    pub fn new(size: u32, idle: u32, max_connections: u32) -> Self {
+        let in_use = size.saturating_sub(idle);
+        Self {
+            size,
+            idle,
+            in_use,
+            max_connections,
+            saturation: in_use as f64 / max_connections.max(1) as f64,
+        }
+    }
+}
+
+// sqlxのプールからは最大接続数を取り出せないので、作成時の値を一緒に持つ
+#[derive(Debug)]
+pub struct HealthRepositoryForDb<DB: Database = Postgres> {
+    pool: Pool<DB>,
+    max_connections: u32,
+}
+
+// derive(Clone)だとDB: Cloneを要求されるので手で実装する
+impl<DB: Database> Clone for HealthRepositoryForDb<DB> {
+    fn clone(&self) -> Self {
+        Self {
+            pool: self.pool.clone(),
+            max_connections: self.max_connections,
+        }
+    }
+}
+
+impl<DB: Database> HealthRepositoryForDb<DB> {
+    pub fn new(pool: Pool<DB>, max_connections: u32) -> Self {
+        Self { pool, max_connections }
+    }
+}
+
+#[async_trait]
+impl<DB: Database> HealthRepository for HealthRepositoryForDb<DB> {
+    async fn ping(&self) -> anyhow::Result<()> {
+        let mut conn = self.pool.acquire().await?;
+        conn.ping().await?;
+        Ok(())
+    }
+
+    fn pool_status(&self) -> Option<PoolStatus> {
+        Some(PoolStatus::new(
+            self.pool.size(),
+            self.pool.num_idle() as u32,
+            self.max_connections,
+        ))
+    }
+}
+
+// メモリ上のストアは接続が切れることがないので、常に正常
+#[cfg(any(test, feature = "memory-storage"))]
+#[derive(Debug, Clone, Default)]
+pub struct HealthRepositoryForMemory;
+
+#[cfg(any(test, feature = "memory-storage"))]
+#[async_trait]
+impl HealthRepository for HealthRepositoryForMemory {
+    async fn ping(&self) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn pool_status(&self) -> Option<PoolStatus> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::repositories::test_utils::sqlite_pool;
+
+    #[test]
+    fn pool_saturation() {
+        assert_eq!(PoolStatus::new(4, 1, 10).in_use, 3);
+        assert_eq!(PoolStatus::new(4, 1, 10).saturation, 0.3);
+        assert_eq!(PoolStatus::new(10, 0, 10).saturation, 1.0);
+        assert_eq!(PoolStatus::new(0, 0, 10).saturation, 0.0);
+    }
+
+    #[tokio::test]
+    async fn sqlite_ping_scenario() {
+        let pool = sqlite_pool().await;
+        let repository = HealthRepositoryForDb::new(pool.clone(), 1);
+        repository.ping().await.expect("[ping] returned Err");
+        let status = repository.pool_status().expect("[pool_status] returned None");
+        assert_eq!(status.max_connections, 1);
+
+        // 閉じたプールには接続できない
+        pool.close().await;
+        assert!(repository.ping().await.is_err());
+    }
+}
@@ -0,0 +1,220 @@
+use axum::async_trait;
+use std::{future::Future, sync::Arc, time::Instant};
+use tracing::Instrument;
+
+use super::{
+    label::{CreateLabel, DeleteLabelMode, DeletedLabel, Label, LabelRepository, UpdateLabel},
+    todo::{
+        CreateChecklistItem, CreateTodo, TodoEntity, TodoNode, TodoQuery, TodoRepository,
+        UpdateChecklistItem, UpdateTodo,
+    },
+    unit_of_work::{UnitOfWork, Work},
+    Page, PageRequest,
+};
+use crate::metrics::Metrics;
+
+// レポジトリの各メソッドにかかった時間を記録する
+// リクエストのspanの子spanの中で実行するので、SQLのログやエラーもリクエストと結びつく
+async fn observe<T, F>(
+    metrics: &Metrics,
+    repository: &str,
+    operation: &str,
+    f: F,
+) -> anyhow::Result<T>
+where
+    F: Future<Output = anyhow::Result<T>>,
+{
+    let started = Instant::now();
+    let result = f
+        .instrument(tracing::info_span!("repository", repository, operation))
+        .await;
+    metrics.observe_repository(repository, operation, result.is_ok(), started.elapsed());
+    result
+}
+
+// どのバックエンドのレポジトリでも包めるように、トレイトの実装に処理を任せる
+#[derive(Debug, Clone)]
+pub struct InstrumentedTodoRepository<T: TodoRepository> {
+    inner: T,
+    metrics: Arc<Metrics>,
+}
+
+impl<T: TodoRepository> InstrumentedTodoRepository<T> {
+    pub fn new(inner: T, metrics: Arc<Metrics>) -> Self {
+        Self { inner, metrics }
+    }
+}
+
+#[async_trait]
+impl<T: TodoRepository> TodoRepository for InstrumentedTodoRepository<T> {
+    async fn create(&self, user_id: i32, payload: CreateTodo) -> anyhow::Result<TodoEntity> {
+        observe(&self.metrics, "todo", "create", self.inner.create(user_id, payload)).await
+    }
+
+    async fn find(&self, user_id: i32, id: i32) -> anyhow::Result<TodoEntity> {
+        observe(&self.metrics, "todo", "find", self.inner.find(user_id, id)).await
+    }
+
+    async fn all(&self, user_id: i32, query: TodoQuery, page: PageRequest) -> anyhow::Result<Page<TodoEntity>> {
+        observe(&self.metrics, "todo", "all", self.inner.all(user_id, query, page)).await
+    }
+
+    async fn children(&self, user_id: i32, id: i32) -> anyhow::Result<Vec<TodoNode>> {
+        observe(&self.metrics, "todo", "children", self.inner.children(user_id, id)).await
+    }
+
+    async fn update(&self, user_id: i32, id: i32, payload: UpdateTodo) -> anyhow::Result<TodoEntity> {
+        observe(&self.metrics, "todo", "update", self.inner.update(user_id, id, payload)).await
+    }
+
+    async fn delete(&self, user_id: i32, id: i32) -> anyhow::Result<()> {
+        observe(&self.metrics, "todo", "delete", self.inner.delete(user_id, id)).await
+    }
+
+    async fn add_checklist_item(&self, user_id: i32, id: i32, payload: CreateChecklistItem) -> anyhow::Result<TodoEntity> {
+        observe(&self.metrics, "todo", "add_checklist_item", self.inner.add_checklist_item(user_id, id, payload)).await
+    }
+
+    async fn update_checklist_item(
+        &self,
+        user_id: i32,
+        id: i32,
+        item_id: i32,
+        payload: UpdateChecklistItem,
+    ) -> anyhow::Result<TodoEntity> {
+        observe(
+            &self.metrics,
+            "todo",
+            "update_checklist_item",
+            self.inner.update_checklist_item(user_id, id, item_id, payload),
+        )
+        .await
+    }
+
+    async fn reorder_checklist(&self, user_id: i32, id: i32, item_ids: Vec<i32>) -> anyhow::Result<TodoEntity> {
+        observe(&self.metrics, "todo", "reorder_checklist", self.inner.reorder_checklist(user_id, id, item_ids)).await
+    }
+
+    async fn delete_checklist_item(&self, user_id: i32, id: i32, item_id: i32) -> anyhow::Result<TodoEntity> {
+        observe(&self.metrics, "todo", "delete_checklist_item", self.inner.delete_checklist_item(user_id, id, item_id)).await
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct InstrumentedLabelRepository<T: LabelRepository> {
+    inner: T,
+    metrics: Arc<Metrics>,
+}
+
+impl<T: LabelRepository> InstrumentedLabelRepository<T> {
+    pub fn new(inner: T, metrics: Arc<Metrics>) -> Self {
+        Self { inner, metrics }
+    }
+}
+
+#[async_trait]
+impl<T: LabelRepository> LabelRepository for InstrumentedLabelRepository<T> {
+    async fn create(&self, user_id: i32, payload: CreateLabel) -> anyhow::Result<Label> {
+        observe(&self.metrics, "label", "create", self.inner.create(user_id, payload)).await
+    }
+
+    async fn find(&self, user_id: i32, id: i32) -> anyhow::Result<Label> {
+        observe(&self.metrics, "label", "find", self.inner.find(user_id, id)).await
+    }
+
+    async fn all(&self, user_id: i32, page: PageRequest) -> anyhow::Result<Page<Label>> {
+        observe(&self.metrics, "label", "all", self.inner.all(user_id, page)).await
+    }
+
+    async fn update(&self, user_id: i32, id: i32, payload: UpdateLabel) -> anyhow::Result<Label> {
+        observe(&self.metrics, "label", "update", self.inner.update(user_id, id, payload)).await
+    }
+
+    async fn delete(&self, user_id: i32, id: i32, mode: DeleteLabelMode) -> anyhow::Result<DeletedLabel> {
+        observe(&self.metrics, "label", "delete", self.inner.delete(user_id, id, mode)).await
+    }
+}
+
+// 作業中のレポジトリも同じように記録する
+#[derive(Debug, Clone)]
+pub struct InstrumentedUnitOfWork<U: UnitOfWork> {
+    inner: U,
+    metrics: Arc<Metrics>,
+}
+
+impl<U: UnitOfWork> InstrumentedUnitOfWork<U> {
+    pub fn new(inner: U, metrics: Arc<Metrics>) -> Self {
+        Self { inner, metrics }
+    }
+}
+
+#[async_trait]
+impl<U: UnitOfWork> UnitOfWork for InstrumentedUnitOfWork<U> {
+    type Work = InstrumentedWork<U::Work>;
+
+    async fn begin(&self) -> anyhow::Result<Self::Work> {
+        let work = self.inner.begin().await?;
+        Ok(InstrumentedWork {
+            inner: work,
+            metrics: self.metrics.clone(),
+        })
+    }
+}
+
+pub struct InstrumentedWork<W: Work> {
+    inner: W,
+    metrics: Arc<Metrics>,
+}
+
+#[async_trait]
+impl<W: Work> Work for InstrumentedWork<W> {
+    type Todo = InstrumentedTodoRepository<W::Todo>;
+    type Label = InstrumentedLabelRepository<W::Label>;
+
+    fn todos(&self) -> Self::Todo {
+        InstrumentedTodoRepository::new(self.inner.todos(), self.metrics.clone())
+    }
+
+    fn labels(&self) -> Self::Label {
+        InstrumentedLabelRepository::new(self.inner.labels(), self.metrics.clone())
+    }
+
+    async fn commit(self) -> anyhow::Result<()> {
+        self.inner.commit().await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::repositories::{
+        memory::MemoryStore,
+        todo::TodoRepositoryForMemory,
+    };
+
+    #[tokio::test]
+    async fn should_record_repository_timings() {
+        let metrics = Arc::new(Metrics::new());
+        let repository = InstrumentedTodoRepository::new(
+            TodoRepositoryForMemory::new(MemoryStore::new()),
+            metrics.clone(),
+        );
+        let todo = repository
+            .create(1, CreateTodo::new("todo".to_string(), vec![]))
+            .await
+            .expect("[create] returned Err");
+        repository.find(1, todo.id).await.expect("[find] returned Err");
+        assert!(repository.find(1, todo.id + 1).await.is_err());
+
+        let text = metrics.encode().expect("fail encode metrics");
+        assert!(text.contains(
+            r#"repository_operation_duration_seconds_count{operation="create",repository="todo",result="ok"} 1"#
+        ));
+        assert!(text.contains(
+            r#"repository_operation_duration_seconds_count{operation="find",repository="todo",result="ok"} 1"#
+        ));
+        assert!(text.contains(
+            r#"repository_operation_duration_seconds_count{operation="find",repository="todo",result="error"} 1"#
+        ));
+    }
+}
@@ -1,97 +1,277 @@
+#[cfg(any(test, feature = "memory-storage"))]
+pub mod memory;
+pub mod sqlite;
+
 use axum::async_trait;
 use serde::{Deserialize, Serialize};
-use sqlx::PgPool;
+use sqlx::{PgConnection, PgPool};
+use validator::{Validate, ValidationError};
+
+use super::{nullable, unit_of_work::DbConnection, Cursor, CursorOrder, Page, PageRequest, RepositoryError};
 
-use super::RepositoryError;
+#[cfg(any(test, feature = "memory-storage"))]
+pub use memory::LabelRepositoryForMemory;
+pub use sqlite::LabelRepositoryForSqlite;
 
+// ラベルもtodoと同じくユーザーごとに持ち、名前の重複はそのユーザーのラベルの中で判定する
 #[async_trait]
 pub trait LabelRepository: Clone + std::marker::Send + std::marker::Sync + 'static {
-    async fn create(&self, name: String) -> anyhow::Result<Label>;
-    async fn all(&self) -> anyhow::Result<Vec<Label>>;
-    async fn delete(&self, id: i32) -> anyhow::Result<()>;
+    async fn create(&self, user_id: i32, payload: CreateLabel) -> anyhow::Result<Label>;
+    async fn find(&self, user_id: i32, id: i32) -> anyhow::Result<Label>;
+    async fn all(&self, user_id: i32, page: PageRequest) -> anyhow::Result<Page<Label>>;
+    async fn update(&self, user_id: i32, id: i32, payload: UpdateLabel) -> anyhow::Result<Label>;
+    async fn delete(&self, user_id: i32, id: i32, mode: DeleteLabelMode) -> anyhow::Result<DeletedLabel>;
+}
+
+// ラベルを使っているtodoがある場合の削除方法
+// reject: 削除せずにエラーを返す
+// detach: todoからラベルを外してから削除する
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DeleteLabelMode {
+    #[default]
+    Reject,
+    Detach,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct DeletedLabel {
+    pub id: i32,
+    pub affected_todos: i64,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, sqlx::FromRow)]
 pub struct Label {
     pub id: i32,
     pub name: String,
+    pub color: Option<String>,
+    pub description: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
-pub struct UpdateLabel {
-    id: i32,
+impl Label {
+    // ラベルはidの昇順に並べる
+    pub const CURSOR_ORDER: CursorOrder = CursorOrder::IdAsc;
+
+    fn cursor(&self) -> Cursor {
+        Cursor { order: Self::CURSOR_ORDER, key: 0, id: self.id }
+    }
+}
+
+// 色は`#rgb`か`#rrggbb`形式の16進数で指定する
+fn validate_hex_color(color: &str) -> Result<(), ValidationError> {
+    let hex = color.strip_prefix('#').unwrap_or_default();
+    if (hex.len() == 3 || hex.len() == 6) && hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        Ok(())
+    } else {
+        let mut error = ValidationError::new("hex_color");
+        error.message = Some("Invalid hex color".into());
+        Err(error)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Validate)]
+pub struct CreateLabel {
+    #[validate(length(min = 1, message = "Can not be empty"))]
+    #[validate(length(max = 100, message = "Over text length"))]
     name: String,
+    #[validate(custom = "validate_hex_color")]
+    color: Option<String>,
+    #[validate(length(max = 500, message = "Over text length"))]
+    description: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Validate)]
+pub struct UpdateLabel {
+    #[validate(length(min = 1, message = "Can not be empty"))]
+    #[validate(length(max = 100, message = "Over text length"))]
+    name: Option<String>,
+    // nullを指定すると色や説明を消す
+    #[serde(default, deserialize_with = "nullable")]
+    #[validate(custom = "validate_hex_color")]
+    color: Option<Option<String>>,
+    #[serde(default, deserialize_with = "nullable")]
+    #[validate(length(max = 500, message = "Over text length"))]
+    description: Option<Option<String>>,
 }
 
 #[derive(Debug, Clone)]
 pub struct LabelRepositoryForDb {
-    pool: PgPool,
+    conn: DbConnection,
 }
 
 impl LabelRepositoryForDb {
     pub fn new(pool: PgPool) -> Self {
-        Self { pool }
+        Self { conn: DbConnection::Pool(pool) }
+    }
+
+    pub fn with_connection(conn: DbConnection) -> Self {
+        Self { conn }
     }
 }
 
+async fn find_label(conn: &mut PgConnection, user_id: i32, id: i32) -> anyhow::Result<Label> {
+    let label = sqlx::query_as::<_, Label>(
+        r#"
+            select * from labels where id = $1 and user_id = $2
+        "#
+    )
+    .bind(id)
+    .bind(user_id)
+    .fetch_one(conn)
+    .await
+    .map_err(|e| match e {
+        sqlx::Error::RowNotFound => RepositoryError::NotFound(id),
+        _ => RepositoryError::Unexpected(e.to_string()),
+    })?;
+
+    Ok(label)
+}
+
 #[async_trait]
 impl LabelRepository for LabelRepositoryForDb {
-    async fn create(&self, name: String) -> anyhow::Result<Label> {
-        let optional_label = sqlx::query_as::<_, Label>(
-            r#"
-                select * from labels where name = $1
-            "#
-        )
-        .bind(name.clone())
-        .fetch_optional(&self.pool)
-        .await?;
+    async fn create(&self, user_id: i32, payload: CreateLabel) -> anyhow::Result<Label> {
+        self.conn.transaction(move |conn| Box::pin(async move {
+            let optional_label = sqlx::query_as::<_, Label>(
+                r#"
+                    select * from labels where name = $1 and user_id = $2
+                "#
+            )
+            .bind(payload.name.clone())
+            .bind(user_id)
+            .fetch_optional(&mut *conn)
+            .await?;
+
+            if let Some(label) = optional_label {
+                return Err(RepositoryError::Duplicate(label.id).into());
+            }
 
-        if let Some(label) = optional_label {
-            return Err(RepositoryError::Duplicate(label.id).into());
-        }
+            let label = sqlx::query_as::<_, Label>(
+                r#"
+                    insert into labels ( name, color, description, user_id )
+                    values ( $1, $2, $3, $4 )
+                    returning *
+                "#
+            )
+            .bind(payload.name)
+            .bind(payload.color)
+            .bind(payload.description)
+            .bind(user_id)
+            .fetch_one(&mut *conn)
+            .await?;
 
-        let label = sqlx::query_as::<_, Label>(
-            r#"
-                insert into labels ( name )
-                values ( $1 )
-                returning *
-            "#
-        )
-        .bind(name.clone())
-        .fetch_one(&self.pool)
-        .await?;
+            Ok(label)
+        })).await
+    }
 
-        Ok(label)
+    async fn find(&self, user_id: i32, id: i32) -> anyhow::Result<Label> {
+        self.conn.run(move |conn| Box::pin(find_label(conn, user_id, id))).await
     }
 
-    async fn all(&self) -> anyhow::Result<Vec<Label>> {
-        let labels = sqlx::query_as::<_, Label>(
-            r#"
-                select * from labels
-                order by labels.id asc;
-            "#
-        )
-        .fetch_all(&self.pool)
-        .await?;
+    async fn all(&self, user_id: i32, page: PageRequest) -> anyhow::Result<Page<Label>> {
+        let labels = self.conn.run(move |conn| Box::pin(async move {
+            let labels = sqlx::query_as::<_, Label>(
+                r#"
+                    select * from labels
+                    where labels.user_id = $3 and ($1::integer is null or labels.id > $1)
+                    order by labels.id asc
+                    limit $2;
+                "#
+            )
+            .bind(page.after.map(|cursor| cursor.id))
+            .bind(page.limit + 1)
+            .bind(user_id)
+            .fetch_all(conn)
+            .await?;
+            Ok(labels)
+        })).await?;
+
+        Ok(Page::from_overfetched(labels, page.limit, Label::cursor))
+    }
+
+    async fn update(&self, user_id: i32, id: i32, payload: UpdateLabel) -> anyhow::Result<Label> {
+        self.conn.transaction(move |conn| Box::pin(async move {
+            let old_label = find_label(conn, user_id, id).await?;
+            let name = payload.name.unwrap_or(old_label.name);
+
+            // 同じ名前の別のラベルがあれば重複エラーにする
+            let optional_label = sqlx::query_as::<_, Label>(
+                r#"
+                    select * from labels where name = $1 and id <> $2 and user_id = $3
+                "#
+            )
+            .bind(name.clone())
+            .bind(id)
+            .bind(user_id)
+            .fetch_optional(&mut *conn)
+            .await?;
+
+            if let Some(label) = optional_label {
+                return Err(RepositoryError::Duplicate(label.id).into());
+            }
 
-        Ok(labels)
+            let label = sqlx::query_as::<_, Label>(
+                r#"
+                    update labels set name = $1, color = $2, description = $3
+                    where id = $4
+                    returning *
+                "#
+            )
+            .bind(name)
+            .bind(payload.color.unwrap_or(old_label.color))
+            .bind(payload.description.unwrap_or(old_label.description))
+            .bind(id)
+            .fetch_one(&mut *conn)
+            .await?;
+
+            Ok(label)
+        })).await
     }
 
-    async fn delete(&self, id: i32) -> anyhow::Result<()> {
-        sqlx::query(
-            r#"
-                delete from labels where id=$1
-            "#
-        )
-        .bind(id)
-        .execute(&self.pool)
-        .await
-        .map_err(|e| match e {
-            sqlx::Error::RowNotFound => RepositoryError::NotFound(id),
-            _ => RepositoryError::Unexpected(e.to_string()),
-        })?;
+    async fn delete(&self, user_id: i32, id: i32, mode: DeleteLabelMode) -> anyhow::Result<DeletedLabel> {
+        self.conn.transaction(move |conn| Box::pin(async move {
+            // 他のユーザーのラベルは、使われているかどうかも分からないようにする
+            find_label(conn, user_id, id).await?;
+
+            // ラベルを使っているtodoの数
+            let (affected_todos,): (i64,) = sqlx::query_as(
+                r#"
+                    select count(distinct todo_id) from todo_labels where label_id=$1
+                "#
+            )
+            .bind(id)
+            .fetch_one(&mut *conn)
+            .await?;
+
+            if affected_todos > 0 {
+                if mode == DeleteLabelMode::Reject {
+                    return Err(RepositoryError::InUse(id, affected_todos).into());
+                }
+                sqlx::query(
+                    r#"
+                        delete from todo_labels where label_id=$1
+                    "#
+                )
+                .bind(id)
+                .execute(&mut *conn)
+                .await?;
+            }
 
-        Ok(())
+            let result = sqlx::query(
+                r#"
+                    delete from labels where id=$1 and user_id=$2
+                "#
+            )
+            .bind(id)
+            .bind(user_id)
+            .execute(&mut *conn)
+            .await
+            .map_err(|e| RepositoryError::Unexpected(e.to_string()))?;
+            if result.rows_affected() == 0 {
+                return Err(RepositoryError::NotFound(id).into());
+            }
+
+            Ok(DeletedLabel { id, affected_todos })
+        })).await
     }
 }
 
@@ -99,6 +279,7 @@ impl LabelRepository for LabelRepositoryForDb {
 #[cfg(feature = "database-test")]
 mod test {
     use super::*;
+    use crate::repositories::test_utils::postgres_user;
     use dotenv::dotenv;
     use sqlx::PgPool;
     use std::env;
@@ -106,126 +287,181 @@ mod test {
     #[tokio::test]
     async fn crud_scenario() {
         dotenv().ok();
-        // ここのテストコケる
-        // 本番のデータベースを使ってテストしているので、ラベルの重複エラーが発生する
         let database_url = &env::var("DATABASE_URL").expect("undefined [DATABASE_URL]");
         let pool = PgPool::connect(database_url)
             .await
-            .expect(&format!("fail connect database, url is [{}]", database_url));
+            .unwrap_or_else(|_| panic!("fail connect database, url is [{}]", database_url));
+        let user_id = postgres_user(&pool, "crud_scenario").await;
 
         let repository = LabelRepositoryForDb::new(pool);
         let label_text = "test_label";
 
         // create
         let label = repository
-            .create(label_text.to_string())
+            .create(user_id, CreateLabel {
+                color: Some("#ff0000".to_string()),
+                ..CreateLabel::new(label_text.to_string())
+            })
             .await
             .expect("[create] returned Err");
         assert_eq!(label.name, label_text);
+        assert_eq!(label.color.as_deref(), Some("#ff0000"));
 
         // all
-        let labels = repository.all().await.expect("[all] returned Err");
-        let label = labels.last().unwrap();
+        let page = PageRequest {
+            limit: 1,
+            after: Some(Cursor { order: Label::CURSOR_ORDER, key: 0, id: label.id - 1 }),
+        };
+        let labels = repository.all(user_id, page).await.expect("[all] returned Err");
+        let label = labels.items.first().unwrap();
         assert_eq!(label.name, label_text);
 
+        // find
+        let found = repository.find(user_id, label.id).await.expect("[find] returned Err");
+        assert_eq!(*label, found);
+
+        // update
+        let updated_text = "test_label_updated";
+        let label = repository
+            .update(user_id, label.id, UpdateLabel::new(updated_text.to_string()))
+            .await
+            .expect("[update] returned Err");
+        assert_eq!(label.name, updated_text);
+        assert_eq!(label.color.as_deref(), Some("#ff0000"));
+        let res = repository
+            .update(user_id, label.id, UpdateLabel::new(updated_text.to_string()))
+            .await;
+        assert!(res.is_ok(), "renaming to its own name is not a duplicate");
+
         // delete
         repository
-            .delete(label.id)
+            .delete(user_id, label.id, DeleteLabelMode::Reject)
+            .await
+            .expect("[delete] returned Err");
+        let res = repository.delete(user_id, label.id, DeleteLabelMode::Reject).await;
+        assert!(matches!(
+            res.unwrap_err().downcast_ref::<RepositoryError>(),
+            Some(RepositoryError::NotFound(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn delete_mode_scenario() {
+        dotenv().ok();
+        let database_url = &env::var("DATABASE_URL").expect("undefined [DATABASE_URL]");
+        let pool = PgPool::connect(database_url)
+            .await
+            .unwrap_or_else(|_| panic!("fail connect database, url is [{}]", database_url));
+        let user_id = postgres_user(&pool, "delete_mode_scenario").await;
+
+        // ラベルとそれを使うtodoを用意する
+        let repository = LabelRepositoryForDb::new(pool.clone());
+        let label = repository
+            .create(user_id, CreateLabel::new("delete_mode_scenario".to_string()))
+            .await
+            .expect("[create] returned Err");
+        let (todo_id,): (i32,) = sqlx::query_as(
+            r#"
+                insert into todos (text, user_id) values ('[delete_mode_scenario] text', $1) returning id
+            "#
+        )
+        .bind(user_id)
+        .fetch_one(&pool)
+        .await
+        .expect("Failed to insert todo data.");
+        sqlx::query(
+            r#"
+                insert into todo_labels (todo_id, label_id) values ($1, $2)
+            "#
+        )
+        .bind(todo_id)
+        .bind(label.id)
+        .execute(&pool)
+        .await
+        .expect("Failed to insert todo_labels data.");
+
+        // rejectでは削除されない
+        let res = repository.delete(user_id, label.id, DeleteLabelMode::Reject).await;
+        assert!(matches!(
+            res.unwrap_err().downcast_ref::<RepositoryError>(),
+            Some(RepositoryError::InUse(_, 1))
+        ));
+        repository.find(user_id, label.id).await.expect("[find] returned Err");
+
+        // detachではtodoからラベルが外れて削除される
+        let deleted = repository
+            .delete(user_id, label.id, DeleteLabelMode::Detach)
             .await
             .expect("[delete] returned Err");
+        assert_eq!(deleted, DeletedLabel { id: label.id, affected_todos: 1 });
+        let rows = sqlx::query(
+            r#"
+                select * from todo_labels where todo_id=$1
+            "#
+        )
+        .bind(todo_id)
+        .fetch_all(&pool)
+        .await
+        .expect("[delete] todo_labels fetch error");
+        assert!(rows.is_empty());
+
+        sqlx::query("delete from todos where id=$1")
+            .bind(todo_id)
+            .execute(&pool)
+            .await
+            .expect("Failed to clean up todo data.");
     }
 }
 
 #[cfg(test)]
 pub mod test_utils {
-    use anyhow::Ok;
-    use axum::async_trait;
-    use std::{
-        collections::HashMap,
-        sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard},
-    };
-
     use super::*;
 
     impl Label {
         pub fn new(id: i32, name: String) -> Self {
-            Self { id, name, }
+            Self {
+                id,
+                name,
+                color: None,
+                description: None,
+            }
         }
     }
 
-    type LabelDatas = HashMap<i32, Label>;
-
-    #[derive(Debug, Clone)]
-    pub struct LabelRepositoryForMemory {
-        store: Arc<RwLock<LabelDatas>>
-    }
-
-    impl LabelRepositoryForMemory {
-        pub fn new() -> Self {
-            LabelRepositoryForMemory {
-                store: Arc::default(),
+    impl CreateLabel {
+        pub fn new(name: String) -> Self {
+            Self {
+                name,
+                color: None,
+                description: None,
             }
         }
-
-        fn write_store_ref(&self) -> RwLockWriteGuard<LabelDatas> {
-            self.store.write().unwrap()
-        }
-
-        fn read_store_ref(&self) -> RwLockReadGuard<LabelDatas> {
-            self.store.read().unwrap()
-        }
     }
 
-    #[async_trait]
-    impl LabelRepository for LabelRepositoryForMemory {
-        async fn create(&self, name: String) -> anyhow::Result<Label> {
-            let mut store = self.write_store_ref();
-            if let Some((_key, label)) = store.iter().find(|(_key, label)| label.name == name) {
-                return Ok(label.clone());
+    impl UpdateLabel {
+        pub fn new(name: String) -> Self {
+            Self {
+                name: Some(name),
+                color: None,
+                description: None,
             }
-
-            let id: i32 = (store.len() + 1) as i32;
-            let label = Label::new(id, name.clone());
-            store.insert(id, label.clone());
-            Ok(label)
-        }
-
-        async fn all(&self) -> anyhow::Result<Vec<Label>> {
-            let store = self.read_store_ref();
-            Ok(Vec::from_iter(store.values().map(| label| label.clone())))
-        }
-
-        async fn delete(&self, id: i32) -> anyhow::Result<()> {
-            let mut store = self.write_store_ref();
-            store.remove(&id).ok_or(RepositoryError::NotFound(id))?;
-            Ok(())
         }
     }
 
     mod test {
         use super::*;
 
-        #[tokio::test]
-        async fn label_crud_scenario() {
-            let text = "label text".to_string();
-            let id = 1;
-            let expected = Label::new(id, text.clone());
-
-            // create
-            let repository = LabelRepositoryForMemory::new();
-            let label = repository
-                .create(text)
-                .await
-                .expect("failed create label");
-            assert_eq!(expected, label);
-
-            // all
-            let label = repository.all().await.expect("failed get all labels");
-            assert_eq!(vec![expected], label);
-
-            // delete
-            let res = repository.delete(id).await;
-            assert!(res.is_ok());
+        #[test]
+        fn label_color_validation() {
+            let label = |color: &str| CreateLabel {
+                color: Some(color.to_string()),
+                ..CreateLabel::new("label".to_string())
+            };
+            assert!(label("#fff").validate().is_ok());
+            assert!(label("#00ff7F").validate().is_ok());
+            assert!(label("00ff7f").validate().is_err());
+            assert!(label("#00ff7").validate().is_err());
+            assert!(label("#ggg").validate().is_err());
         }
     }
 }
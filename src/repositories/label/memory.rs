@@ -0,0 +1,251 @@
+use axum::async_trait;
+
+use super::{CreateLabel, DeleteLabelMode, DeletedLabel, Label, LabelRepository, UpdateLabel};
+use crate::repositories::{
+    memory::{LabelRecord, MemoryStore, Tables},
+    Page, PageRequest, RepositoryError,
+};
+
+// メモリ上にデータを保存するレポジトリ
+// todoとストアを共有しているので、削除時にはtodoからの参照も確認する
+#[derive(Debug, Clone)]
+pub struct LabelRepositoryForMemory {
+    store: MemoryStore,
+}
+
+impl LabelRepositoryForMemory {
+    pub fn new(store: MemoryStore) -> Self {
+        LabelRepositoryForMemory { store }
+    }
+}
+
+// 他のユーザーのラベルは、存在しないものとして扱う
+fn find_record(tables: &Tables, user_id: i32, id: i32) -> Option<&LabelRecord> {
+    tables.labels.get(&id).filter(|record| record.user_id == user_id)
+}
+
+fn find_by_name(tables: &Tables, user_id: i32, name: &str) -> Option<i32> {
+    tables
+        .labels
+        .values()
+        .find(|record| record.user_id == user_id && record.label.name == name)
+        .map(|record| record.label.id)
+}
+
+#[async_trait]
+impl LabelRepository for LabelRepositoryForMemory {
+    async fn create(&self, user_id: i32, payload: CreateLabel) -> anyhow::Result<Label> {
+        let mut tables = self.store.write();
+        if let Some(id) = find_by_name(&tables, user_id, &payload.name) {
+            return Err(RepositoryError::Duplicate(id).into());
+        }
+
+        let label = Label {
+            id: self.store.next_label_id(),
+            name: payload.name,
+            color: payload.color,
+            description: payload.description,
+        };
+        tables.labels.insert(label.id, LabelRecord { user_id, label: label.clone() });
+        Ok(label)
+    }
+
+    async fn find(&self, user_id: i32, id: i32) -> anyhow::Result<Label> {
+        let tables = self.store.read();
+        let record = find_record(&tables, user_id, id).ok_or(RepositoryError::NotFound(id))?;
+        Ok(record.label.clone())
+    }
+
+    async fn all(&self, user_id: i32, page: PageRequest) -> anyhow::Result<Page<Label>> {
+        let tables = self.store.read();
+        let mut labels = Vec::from_iter(
+            tables
+                .labels
+                .values()
+                .filter(|record| record.user_id == user_id)
+                .map(|record| &record.label)
+                .filter(|label| page.after.is_none_or(|after| label.id > after.id))
+                .cloned(),
+        );
+        labels.sort_by_key(|label| label.id);
+        labels.truncate(page.limit as usize + 1);
+        Ok(Page::from_overfetched(labels, page.limit, Label::cursor))
+    }
+
+    async fn update(&self, user_id: i32, id: i32, payload: UpdateLabel) -> anyhow::Result<Label> {
+        let mut tables = self.store.write();
+        let old_label = &find_record(&tables, user_id, id)
+            .ok_or(RepositoryError::NotFound(id))?
+            .label;
+        let name = payload.name.unwrap_or_else(|| old_label.name.clone());
+        if let Some(dup_id) = find_by_name(&tables, user_id, &name).filter(|dup_id| *dup_id != id) {
+            return Err(RepositoryError::Duplicate(dup_id).into());
+        }
+
+        let label = Label {
+            id,
+            name,
+            color: payload.color.unwrap_or_else(|| old_label.color.clone()),
+            description: payload.description.unwrap_or_else(|| old_label.description.clone()),
+        };
+        tables.labels.insert(id, LabelRecord { user_id, label: label.clone() });
+        Ok(label)
+    }
+
+    async fn delete(&self, user_id: i32, id: i32, mode: DeleteLabelMode) -> anyhow::Result<DeletedLabel> {
+        let mut tables = self.store.write();
+        if find_record(&tables, user_id, id).is_none() {
+            return Err(RepositoryError::NotFound(id).into());
+        }
+
+        // ラベルを使っているtodoの数
+        let affected_todos = tables
+            .todos
+            .values()
+            .filter(|todo| todo.labels.contains(&id))
+            .count() as i64;
+        if affected_todos > 0 {
+            if mode == DeleteLabelMode::Reject {
+                return Err(RepositoryError::InUse(id, affected_todos).into());
+            }
+            for todo in tables.todos.values_mut() {
+                todo.labels.retain(|label_id| *label_id != id);
+            }
+        }
+
+        tables.labels.remove(&id);
+        Ok(DeletedLabel { id, affected_todos })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::repositories::todo::{CreateTodo, TodoRepository, TodoRepositoryForMemory};
+
+    #[tokio::test]
+    async fn label_crud_scenario() {
+        let user_id = 1;
+        let text = "label text".to_string();
+        let id = 1;
+        let expected = Label::new(id, text.clone());
+
+        // create
+        let repository = LabelRepositoryForMemory::new(MemoryStore::new());
+        let label = repository
+            .create(user_id, CreateLabel::new(text.clone()))
+            .await
+            .expect("failed create label");
+        assert_eq!(expected, label);
+        let res = repository.create(user_id, CreateLabel::new(text)).await;
+        assert!(matches!(
+            res.unwrap_err().downcast_ref::<RepositoryError>(),
+            Some(RepositoryError::Duplicate(dup_id)) if *dup_id == id
+        ));
+
+        // all
+        let label = repository
+            .all(user_id, PageRequest::default())
+            .await
+            .expect("failed get all labels");
+        assert_eq!(vec![expected.clone()], label.items);
+
+        // find
+        let label = repository.find(user_id, id).await.expect("failed find label");
+        assert_eq!(expected, label);
+
+        // update
+        let other = repository
+            .create(user_id, CreateLabel::new("other label".to_string()))
+            .await
+            .expect("failed create label");
+        let label = repository
+            .update(user_id, id, UpdateLabel::new("updated label".to_string()))
+            .await
+            .expect("failed update label");
+        assert_eq!(Label::new(id, "updated label".to_string()), label);
+        let res = repository
+            .update(user_id, id, UpdateLabel::new(other.name.clone()))
+            .await;
+        assert!(matches!(
+            res.unwrap_err().downcast_ref::<RepositoryError>(),
+            Some(RepositoryError::Duplicate(dup_id)) if *dup_id == other.id
+        ));
+
+        // delete
+        let res = repository.delete(user_id, id, DeleteLabelMode::Reject).await;
+        assert!(res.is_ok());
+    }
+
+    #[tokio::test]
+    async fn label_delete_mode() {
+        let user_id = 1;
+        let store = MemoryStore::new();
+        let repository = LabelRepositoryForMemory::new(store.clone());
+        let todos = TodoRepositoryForMemory::new(store);
+        let label = repository
+            .create(user_id, CreateLabel::new("label".to_string()))
+            .await
+            .expect("failed create label");
+        let todo = todos
+            .create(user_id, CreateTodo::new("todo".to_string(), vec![label.id]))
+            .await
+            .expect("failed create todo");
+
+        // 使われているラベルはrejectでは消せない
+        let res = repository.delete(user_id, label.id, DeleteLabelMode::Reject).await;
+        assert!(matches!(
+            res.unwrap_err().downcast_ref::<RepositoryError>(),
+            Some(RepositoryError::InUse(_, 1))
+        ));
+
+        // detachならtodoから外してから消す
+        let deleted = repository
+            .delete(user_id, label.id, DeleteLabelMode::Detach)
+            .await
+            .expect("failed delete label");
+        assert_eq!(deleted, DeletedLabel { id: label.id, affected_todos: 1 });
+        let todo = todos.find(user_id, todo.id).await.expect("failed find todo");
+        assert!(todo.labels.is_empty());
+    }
+
+    #[tokio::test]
+    async fn label_owner_isolation() {
+        let (owner, other) = (1, 2);
+        let repository = LabelRepositoryForMemory::new(MemoryStore::new());
+        let label = repository
+            .create(owner, CreateLabel::new("label".to_string()))
+            .await
+            .expect("failed create label");
+
+        // 名前の重複はユーザーごとに判定する
+        let others = repository
+            .create(other, CreateLabel::new("label".to_string()))
+            .await
+            .expect("failed create label");
+        assert_ne!(label.id, others.id);
+
+        // 他のユーザーのラベルは見えず、更新も削除もできない
+        assert!(repository.find(other, label.id).await.is_err());
+        let res = repository
+            .update(other, label.id, UpdateLabel::new("renamed".to_string()))
+            .await;
+        assert!(matches!(
+            res.unwrap_err().downcast_ref::<RepositoryError>(),
+            Some(RepositoryError::NotFound(_))
+        ));
+        let res = repository.delete(other, label.id, DeleteLabelMode::Detach).await;
+        assert!(matches!(
+            res.unwrap_err().downcast_ref::<RepositoryError>(),
+            Some(RepositoryError::NotFound(_))
+        ));
+        let labels = repository
+            .all(other, PageRequest::default())
+            .await
+            .expect("failed get all labels");
+        assert_eq!(labels.items, vec![others]);
+
+        let found = repository.find(owner, label.id).await.expect("failed find label");
+        assert_eq!(found, label);
+    }
+}
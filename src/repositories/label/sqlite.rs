@@ -0,0 +1,257 @@
+use axum::async_trait;
+use sqlx::{Sqlite, SqliteConnection, SqlitePool};
+
+use super::{CreateLabel, DeleteLabelMode, DeletedLabel, Label, LabelRepository, UpdateLabel};
+use crate::repositories::{unit_of_work::DbConnection, Page, PageRequest, RepositoryError};
+
+#[derive(Debug, Clone)]
+pub struct LabelRepositoryForSqlite {
+    conn: DbConnection<Sqlite>,
+}
+
+impl LabelRepositoryForSqlite {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { conn: DbConnection::Pool(pool) }
+    }
+
+    pub fn with_connection(conn: DbConnection<Sqlite>) -> Self {
+        Self { conn }
+    }
+}
+
+async fn find_label(conn: &mut SqliteConnection, user_id: i32, id: i32) -> anyhow::Result<Label> {
+    let label = sqlx::query_as::<_, Label>(
+        r#"
+            select * from labels where id = $1 and user_id = $2
+        "#
+    )
+    .bind(id)
+    .bind(user_id)
+    .fetch_one(conn)
+    .await
+    .map_err(|e| match e {
+        sqlx::Error::RowNotFound => RepositoryError::NotFound(id),
+        _ => RepositoryError::Unexpected(e.to_string()),
+    })?;
+
+    Ok(label)
+}
+
+#[async_trait]
+impl LabelRepository for LabelRepositoryForSqlite {
+    async fn create(&self, user_id: i32, payload: CreateLabel) -> anyhow::Result<Label> {
+        self.conn.transaction(move |conn| Box::pin(async move {
+            let optional_label = sqlx::query_as::<_, Label>(
+                r#"
+                    select * from labels where name = $1 and user_id = $2
+                "#
+            )
+            .bind(payload.name.clone())
+            .bind(user_id)
+            .fetch_optional(&mut *conn)
+            .await?;
+
+            if let Some(label) = optional_label {
+                return Err(RepositoryError::Duplicate(label.id).into());
+            }
+
+            let label = sqlx::query_as::<_, Label>(
+                r#"
+                    insert into labels ( name, color, description, user_id )
+                    values ( $1, $2, $3, $4 )
+                    returning *
+                "#
+            )
+            .bind(payload.name)
+            .bind(payload.color)
+            .bind(payload.description)
+            .bind(user_id)
+            .fetch_one(&mut *conn)
+            .await?;
+
+            Ok(label)
+        })).await
+    }
+
+    async fn find(&self, user_id: i32, id: i32) -> anyhow::Result<Label> {
+        self.conn.run(move |conn| Box::pin(find_label(conn, user_id, id))).await
+    }
+
+    async fn all(&self, user_id: i32, page: PageRequest) -> anyhow::Result<Page<Label>> {
+        let labels = self.conn.run(move |conn| Box::pin(async move {
+            let labels = sqlx::query_as::<_, Label>(
+                r#"
+                    select * from labels
+                    where labels.user_id = $3 and ($1 is null or labels.id > $1)
+                    order by labels.id asc
+                    limit $2;
+                "#
+            )
+            .bind(page.after.map(|cursor| cursor.id))
+            .bind(page.limit + 1)
+            .bind(user_id)
+            .fetch_all(conn)
+            .await?;
+            Ok(labels)
+        })).await?;
+
+        Ok(Page::from_overfetched(labels, page.limit, Label::cursor))
+    }
+
+    async fn update(&self, user_id: i32, id: i32, payload: UpdateLabel) -> anyhow::Result<Label> {
+        self.conn.transaction(move |conn| Box::pin(async move {
+            let old_label = find_label(conn, user_id, id).await?;
+            let name = payload.name.unwrap_or(old_label.name);
+
+            // 同じ名前の別のラベルがあれば重複エラーにする
+            let optional_label = sqlx::query_as::<_, Label>(
+                r#"
+                    select * from labels where name = $1 and id <> $2 and user_id = $3
+                "#
+            )
+            .bind(name.clone())
+            .bind(id)
+            .bind(user_id)
+            .fetch_optional(&mut *conn)
+            .await?;
+
+            if let Some(label) = optional_label {
+                return Err(RepositoryError::Duplicate(label.id).into());
+            }
+
+            let label = sqlx::query_as::<_, Label>(
+                r#"
+                    update labels set name = $1, color = $2, description = $3
+                    where id = $4
+                    returning *
+                "#
+            )
+            .bind(name)
+            .bind(payload.color.unwrap_or(old_label.color))
+            .bind(payload.description.unwrap_or(old_label.description))
+            .bind(id)
+            .fetch_one(&mut *conn)
+            .await?;
+
+            Ok(label)
+        })).await
+    }
+
+    async fn delete(&self, user_id: i32, id: i32, mode: DeleteLabelMode) -> anyhow::Result<DeletedLabel> {
+        self.conn.transaction(move |conn| Box::pin(async move {
+            // 他のユーザーのラベルは、使われているかどうかも分からないようにする
+            find_label(conn, user_id, id).await?;
+
+            // ラベルを使っているtodoの数
+            let (affected_todos,): (i64,) = sqlx::query_as(
+                r#"
+                    select count(distinct todo_id) from todo_labels where label_id=$1
+                "#
+            )
+            .bind(id)
+            .fetch_one(&mut *conn)
+            .await?;
+
+            if affected_todos > 0 {
+                if mode == DeleteLabelMode::Reject {
+                    return Err(RepositoryError::InUse(id, affected_todos).into());
+                }
+                sqlx::query(
+                    r#"
+                        delete from todo_labels where label_id=$1
+                    "#
+                )
+                .bind(id)
+                .execute(&mut *conn)
+                .await?;
+            }
+
+            let result = sqlx::query(
+                r#"
+                    delete from labels where id=$1 and user_id=$2
+                "#
+            )
+            .bind(id)
+            .bind(user_id)
+            .execute(&mut *conn)
+            .await
+            .map_err(|e| RepositoryError::Unexpected(e.to_string()))?;
+            if result.rows_affected() == 0 {
+                return Err(RepositoryError::NotFound(id).into());
+            }
+
+            Ok(DeletedLabel { id, affected_todos })
+        })).await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::repositories::{
+        test_utils::{sqlite_pool, sqlite_user},
+        todo::{CreateTodo, TodoRepository, TodoRepositoryForSqlite},
+    };
+
+    #[tokio::test]
+    async fn crud_scenario() {
+        let pool = sqlite_pool().await;
+        let user_id = sqlite_user(&pool, "crud_scenario@example.com").await;
+        let repository = LabelRepositoryForSqlite::new(pool.clone());
+        let todos = TodoRepositoryForSqlite::new(pool.clone());
+
+        // create
+        let label = repository
+            .create(user_id, CreateLabel {
+                color: Some("#ff0000".to_string()),
+                ..CreateLabel::new("test label".to_string())
+            })
+            .await
+            .expect("[create] returned Err");
+        assert_eq!(label.color.as_deref(), Some("#ff0000"));
+        let res = repository.create(user_id, CreateLabel::new("test label".to_string())).await;
+        assert!(res.is_err());
+
+        // find / all
+        let found = repository.find(user_id, label.id).await.expect("[find] returned Err");
+        assert_eq!(label, found);
+        let page = repository
+            .all(user_id, PageRequest::default())
+            .await
+            .expect("[all] returned Err");
+        assert_eq!(page.items, vec![label.clone()]);
+
+        // update
+        let updated = repository
+            .update(user_id, label.id, UpdateLabel::new("updated label".to_string()))
+            .await
+            .expect("[update] returned Err");
+        assert_eq!(updated.name, "updated label");
+        assert_eq!(updated.color, label.color);
+        let cleared = repository
+            .update(user_id, label.id, UpdateLabel {
+                color: Some(None),
+                ..UpdateLabel::new("updated label".to_string())
+            })
+            .await
+            .expect("[update] returned Err");
+        assert_eq!(cleared.color, None);
+
+        // 使われているラベルはrejectでは消せず、detachなら外してから消せる
+        let todo = todos
+            .create(user_id, CreateTodo::new("[crud_scenario] todo".to_string(), vec![label.id]))
+            .await
+            .expect("[create todo] returned Err");
+        let res = repository.delete(user_id, label.id, DeleteLabelMode::Reject).await;
+        assert!(res.is_err());
+        let deleted = repository
+            .delete(user_id, label.id, DeleteLabelMode::Detach)
+            .await
+            .expect("[delete] returned Err");
+        assert_eq!(deleted, DeletedLabel { id: label.id, affected_todos: 1 });
+        let todo = todos.find(user_id, todo.id).await.expect("[find todo] returned Err");
+        assert!(todo.labels.is_empty());
+        let res = repository.find(user_id, label.id).await;
+        assert!(res.is_err());
+    }
+}
@@ -0,0 +1,330 @@
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+use std::{
+    collections::HashMap,
+    hash::Hash,
+    sync::{
+        atomic::{AtomicI32, Ordering},
+        Arc, RwLock, RwLockReadGuard, RwLockWriteGuard,
+    },
+};
+
+use super::{
+    label::{Label, LabelRepositoryForMemory},
+    project::ProjectRole,
+    todo::{ChecklistItem, Priority, TodoRepositoryForMemory},
+    unit_of_work::{UnitOfWork, Work},
+    user::{ApiToken, UserWithPassword},
+};
+
+// メモリ上に保存するtodo
+// ラベルはidだけを持ち、読み出すときにラベルのテーブルから解決する
+// チェックリストの項目は並び順に持つ
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TodoRecord {
+    pub id: i32,
+    pub user_id: i32,
+    pub project_id: Option<i32>,
+    pub parent_id: Option<i32>,
+    pub text: String,
+    pub completed: bool,
+    pub due_date: Option<DateTime<Utc>>,
+    pub priority: Priority,
+    pub labels: Vec<i32>,
+    pub checklist: Vec<ChecklistItem>,
+}
+
+// メモリ上に保存するラベル
+// 持ち主はレスポンスに含めないので、Labelとは別に持つ
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LabelRecord {
+    pub user_id: i32,
+    pub label: Label,
+}
+
+// ログインセッション
+// キーはトークンのハッシュ
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionRecord {
+    pub user_id: i32,
+    pub expires_at: DateTime<Utc>,
+}
+
+// アクセストークン
+// レスポンスに含めない持ち主とハッシュは、ApiTokenとは別に持つ
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApiTokenRecord {
+    pub user_id: i32,
+    pub token_hash: String,
+    pub token: ApiToken,
+}
+
+// プロジェクト
+// メンバーとroleは、(project_id, user_id)をキーにしたproject_membersに持つ
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProjectRecord {
+    pub id: i32,
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Tables {
+    pub todos: HashMap<i32, TodoRecord>,
+    pub labels: HashMap<i32, LabelRecord>,
+    pub users: HashMap<i32, UserWithPassword>,
+    pub sessions: HashMap<String, SessionRecord>,
+    pub api_tokens: HashMap<i32, ApiTokenRecord>,
+    pub projects: HashMap<i32, ProjectRecord>,
+    pub project_members: HashMap<(i32, i32), ProjectRole>,
+}
+
+impl Tables {
+    // DBのproject_membersと同じく、メンバーでなければNone
+    pub fn project_role(&self, user_id: i32, project_id: i32) -> Option<ProjectRole> {
+        self.project_members.get(&(project_id, user_id)).copied()
+    }
+}
+
+// DBのシーケンスと同じく、削除やロールバックがあっても同じidは二度と使わない
+#[derive(Debug, Default)]
+struct Sequences {
+    todo: AtomicI32,
+    label: AtomicI32,
+    user: AtomicI32,
+    api_token: AtomicI32,
+    project: AtomicI32,
+    checklist_item: AtomicI32,
+}
+
+// todoとラベルのレポジトリが共有する、メモリ上のデータベース
+// 再起動するとデータは消えるので、デモやテストのための一時的なインスタンスで使う
+#[derive(Debug, Clone, Default)]
+pub struct MemoryStore {
+    tables: Arc<RwLock<Tables>>,
+    sequences: Arc<Sequences>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn read(&self) -> RwLockReadGuard<'_, Tables> {
+        self.tables.read().unwrap()
+    }
+
+    pub fn write(&self) -> RwLockWriteGuard<'_, Tables> {
+        self.tables.write().unwrap()
+    }
+
+    pub fn next_todo_id(&self) -> i32 {
+        self.sequences.todo.fetch_add(1, Ordering::SeqCst) + 1
+    }
+
+    pub fn next_label_id(&self) -> i32 {
+        self.sequences.label.fetch_add(1, Ordering::SeqCst) + 1
+    }
+
+    pub fn next_user_id(&self) -> i32 {
+        self.sequences.user.fetch_add(1, Ordering::SeqCst) + 1
+    }
+
+    pub fn next_api_token_id(&self) -> i32 {
+        self.sequences.api_token.fetch_add(1, Ordering::SeqCst) + 1
+    }
+
+    pub fn next_project_id(&self) -> i32 {
+        self.sequences.project.fetch_add(1, Ordering::SeqCst) + 1
+    }
+
+    pub fn next_checklist_item_id(&self) -> i32 {
+        self.sequences.checklist_item.fetch_add(1, Ordering::SeqCst) + 1
+    }
+
+    // 同じシーケンスを使う、データだけを複製したストア
+    fn fork(&self) -> (Tables, MemoryStore) {
+        let base = self.read().clone();
+        let fork = MemoryStore {
+            tables: Arc::new(RwLock::new(base.clone())),
+            sequences: self.sequences.clone(),
+        };
+        (base, fork)
+    }
+
+    // forkしてからの変更だけを取り込む
+    // 作業中に他のリクエストが変更した行は、作業で触っていなければそのまま残る
+    fn merge(&self, base: &Tables, fork: &MemoryStore) {
+        let changed = fork.read();
+        let mut tables = self.write();
+        merge_table(&mut tables.todos, &base.todos, &changed.todos);
+        merge_table(&mut tables.labels, &base.labels, &changed.labels);
+        merge_table(&mut tables.users, &base.users, &changed.users);
+        merge_table(&mut tables.sessions, &base.sessions, &changed.sessions);
+        merge_table(&mut tables.api_tokens, &base.api_tokens, &changed.api_tokens);
+        merge_table(&mut tables.projects, &base.projects, &changed.projects);
+        merge_table(&mut tables.project_members, &base.project_members, &changed.project_members);
+    }
+}
+
+fn merge_table<K, V>(target: &mut HashMap<K, V>, base: &HashMap<K, V>, changed: &HashMap<K, V>)
+where
+    K: Eq + Hash + Clone,
+    V: Clone + PartialEq,
+{
+    for (key, value) in changed {
+        if base.get(key) != Some(value) {
+            target.insert(key.clone(), value.clone());
+        }
+    }
+    for key in base.keys() {
+        if !changed.contains_key(key) {
+            target.remove(key);
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct UnitOfWorkForMemory {
+    store: MemoryStore,
+}
+
+impl UnitOfWorkForMemory {
+    pub fn new(store: MemoryStore) -> Self {
+        UnitOfWorkForMemory { store }
+    }
+}
+
+#[async_trait]
+impl UnitOfWork for UnitOfWorkForMemory {
+    type Work = WorkForMemory;
+
+    async fn begin(&self) -> anyhow::Result<WorkForMemory> {
+        let (base, fork) = self.store.fork();
+        Ok(WorkForMemory {
+            store: self.store.clone(),
+            base,
+            fork,
+        })
+    }
+}
+
+// 作業中の操作は複製したストアに対して行い、commitで元のストアに反映する
+// commitせずにdropされた場合は、複製ごと捨てられる
+pub struct WorkForMemory {
+    store: MemoryStore,
+    base: Tables,
+    fork: MemoryStore,
+}
+
+#[async_trait]
+impl Work for WorkForMemory {
+    type Todo = TodoRepositoryForMemory;
+    type Label = LabelRepositoryForMemory;
+
+    fn todos(&self) -> TodoRepositoryForMemory {
+        TodoRepositoryForMemory::new(self.fork.clone())
+    }
+
+    fn labels(&self) -> LabelRepositoryForMemory {
+        LabelRepositoryForMemory::new(self.fork.clone())
+    }
+
+    async fn commit(self) -> anyhow::Result<()> {
+        self.store.merge(&self.base, &self.fork);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub mod test_utils {
+    use super::*;
+
+    impl MemoryStore {
+        // 指定したidのラベルが、user_idのユーザーのものとして登録済みのストアを作る
+        pub fn with_labels(user_id: i32, labels: Vec<Label>) -> Self {
+            let store = MemoryStore::new();
+            let max_id = labels.iter().map(|label| label.id).max().unwrap_or(0);
+            store.sequences.label.store(max_id, Ordering::SeqCst);
+            store.write().labels = labels
+                .into_iter()
+                .map(|label| (label.id, LabelRecord { user_id, label }))
+                .collect();
+            store
+        }
+
+        // ログイン済みのユーザーを用意し、そのidを返す
+        pub fn add_session(&self, email: &str, token: &str) -> i32 {
+            let mut tables = self.write();
+            let user_id = match tables.users.values().find(|user| user.email == email) {
+                Some(user) => user.id,
+                None => {
+                    let user = UserWithPassword {
+                        id: self.next_user_id(),
+                        email: email.to_string(),
+                        password_hash: String::new(),
+                    };
+                    tables.users.insert(user.id, user.clone());
+                    user.id
+                }
+            };
+            tables.sessions.insert(
+                crate::auth::hash_token(token),
+                SessionRecord {
+                    user_id,
+                    expires_at: Utc::now() + chrono::Duration::days(1),
+                },
+            );
+            user_id
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::repositories::{
+        label::{CreateLabel, LabelRepository},
+        todo::{CreateTodo, TodoRepository},
+    };
+
+    #[tokio::test]
+    async fn unit_of_work_scenario() {
+        let user_id = 1;
+        let store = MemoryStore::new();
+        let unit_of_work = UnitOfWorkForMemory::new(store.clone());
+        let todos = TodoRepositoryForMemory::new(store.clone());
+
+        // commitしなければ、作業中の操作はすべて取り消される
+        let work = unit_of_work.begin().await.expect("[begin] returned Err");
+        let label = work
+            .labels()
+            .create(user_id, CreateLabel::new("rollback".to_string()))
+            .await
+            .expect("[create label] returned Err");
+        let todo = work
+            .todos()
+            .create(user_id, CreateTodo::new("rollback".to_string(), vec![label.id]))
+            .await
+            .expect("[create todo] returned Err");
+        drop(work);
+        assert!(todos.find(user_id, todo.id).await.is_err());
+
+        // 作業中に作業の外で行われた変更は、commitしても消えない
+        let work = unit_of_work.begin().await.expect("[begin] returned Err");
+        let outside = todos
+            .create(user_id, CreateTodo::new("outside".to_string(), vec![]))
+            .await
+            .expect("[create todo] returned Err");
+        let inside = work
+            .todos()
+            .create(user_id, CreateTodo::new("inside".to_string(), vec![]))
+            .await
+            .expect("[create todo] returned Err");
+        work.commit().await.expect("[commit] returned Err");
+
+        // ロールバックされたidも含めて、idは重複しない
+        assert!(todo.id < outside.id && outside.id < inside.id);
+        assert!(todos.find(user_id, outside.id).await.is_ok());
+        assert!(todos.find(user_id, inside.id).await.is_ok());
+    }
+}
@@ -0,0 +1,494 @@
+#[cfg(any(test, feature = "memory-storage"))]
+pub mod memory;
+pub mod sqlite;
+
+use axum::async_trait;
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgConnection, PgPool};
+use validator::Validate;
+
+use super::{unit_of_work::DbConnection, RepositoryError};
+
+#[cfg(any(test, feature = "memory-storage"))]
+pub use memory::ProjectRepositoryForMemory;
+pub use sqlite::ProjectRepositoryForSqlite;
+
+// プロジェクトは複数のユーザーで共有するtodoのリスト
+// user_idは操作するユーザーで、メンバーでないプロジェクトは存在しないものとして扱う（NotFound）
+// roleによる操作の制限はハンドラーで行う
+#[async_trait]
+pub trait ProjectRepository: Clone + Send + Sync + 'static {
+    // 作成したユーザーがownerになる
+    async fn create(&self, user_id: i32, payload: CreateProject) -> anyhow::Result<Project>;
+    async fn find(&self, user_id: i32, id: i32) -> anyhow::Result<Project>;
+    async fn all(&self, user_id: i32) -> anyhow::Result<Vec<Project>>;
+    async fn update(&self, user_id: i32, id: i32, payload: UpdateProject) -> anyhow::Result<Project>;
+    // todoが残っているプロジェクトはInUseになる
+    async fn delete(&self, user_id: i32, id: i32) -> anyhow::Result<()>;
+    async fn members(&self, user_id: i32, id: i32) -> anyhow::Result<Vec<ProjectMember>>;
+    // 追加するユーザーはメールアドレスで指定し、idを総当たりして他のユーザーを調べられないようにする
+    // 見つからなければUserNotFound、既にメンバーであればDuplicateになる
+    async fn add_member(&self, user_id: i32, id: i32, email: String, role: ProjectRole) -> anyhow::Result<ProjectMember>;
+    // メンバーのroleを変更する（メンバーでなければNotFound）
+    // ownerがいなくなる変更はNoOwnerになる
+    async fn update_member(&self, user_id: i32, id: i32, member_id: i32, role: ProjectRole) -> anyhow::Result<ProjectMember>;
+    async fn delete_member(&self, user_id: i32, id: i32, member_id: i32) -> anyhow::Result<()>;
+}
+
+// プロジェクトのメンバーの権限
+// データベースにはsmallintとして保存し、値が大きいほど多くの操作ができる
+// viewer: todoの閲覧
+// editor: todoの作成・更新・削除
+// owner: メンバーの管理
+#[derive(
+    Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, sqlx::Type,
+)]
+#[serde(rename_all = "lowercase")]
+#[repr(i16)]
+pub enum ProjectRole {
+    Viewer = 1,
+    Editor = 2,
+    Owner = 3,
+}
+
+// roleは操作したユーザーのrole
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, FromRow)]
+pub struct Project {
+    pub id: i32,
+    pub name: String,
+    pub role: ProjectRole,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, FromRow)]
+pub struct ProjectMember {
+    pub user_id: i32,
+    pub email: String,
+    pub role: ProjectRole,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Validate)]
+pub struct CreateProject {
+    #[validate(length(min = 1, message = "Can not be empty"))]
+    #[validate(length(max = 100, message = "Over text length"))]
+    name: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Validate)]
+pub struct UpdateProject {
+    #[validate(length(min = 1, message = "Can not be empty"))]
+    #[validate(length(max = 100, message = "Over text length"))]
+    name: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ProjectRepositoryForDb {
+    conn: DbConnection,
+}
+
+impl ProjectRepositoryForDb {
+    pub fn new(pool: PgPool) -> Self {
+        Self { conn: DbConnection::Pool(pool) }
+    }
+}
+
+async fn find_project(conn: &mut PgConnection, user_id: i32, id: i32) -> anyhow::Result<Project> {
+    let project = sqlx::query_as::<_, Project>(
+        r#"
+            select projects.id, projects.name, pm.role from projects
+                inner join project_members pm on pm.project_id = projects.id and pm.user_id = $2
+            where projects.id = $1
+        "#
+    )
+    .bind(id)
+    .bind(user_id)
+    .fetch_optional(conn)
+    .await?
+    .ok_or(RepositoryError::NotFound(id))?;
+
+    Ok(project)
+}
+
+async fn find_member(conn: &mut PgConnection, id: i32, member_id: i32) -> anyhow::Result<ProjectMember> {
+    let member = sqlx::query_as::<_, ProjectMember>(
+        r#"
+            select users.id as user_id, users.email, pm.role from project_members pm
+                inner join users on users.id = pm.user_id
+            where pm.project_id = $1 and pm.user_id = $2
+        "#
+    )
+    .bind(id)
+    .bind(member_id)
+    .fetch_optional(conn)
+    .await?
+    .ok_or(RepositoryError::NotFound(member_id))?;
+
+    Ok(member)
+}
+
+async fn ensure_owner_exists(conn: &mut PgConnection, id: i32) -> anyhow::Result<()> {
+    let (owners,): (i64,) = sqlx::query_as(
+        r#"
+            select count(*) from project_members where project_id = $1 and role = $2
+        "#
+    )
+    .bind(id)
+    .bind(ProjectRole::Owner)
+    .fetch_one(conn)
+    .await?;
+
+    if owners == 0 {
+        return Err(RepositoryError::NoOwner(id).into());
+    }
+    Ok(())
+}
+
+#[async_trait]
+impl ProjectRepository for ProjectRepositoryForDb {
+    async fn create(&self, user_id: i32, payload: CreateProject) -> anyhow::Result<Project> {
+        self.conn.transaction(move |conn| Box::pin(async move {
+            let (id,): (i32,) = sqlx::query_as(
+                r#"
+                    insert into projects ( name ) values ( $1 )
+                    returning id
+                "#
+            )
+            .bind(payload.name)
+            .fetch_one(&mut *conn)
+            .await?;
+
+            sqlx::query(
+                r#"
+                    insert into project_members ( project_id, user_id, role )
+                    values ( $1, $2, $3 )
+                "#
+            )
+            .bind(id)
+            .bind(user_id)
+            .bind(ProjectRole::Owner)
+            .execute(&mut *conn)
+            .await?;
+
+            find_project(conn, user_id, id).await
+        })).await
+    }
+
+    async fn find(&self, user_id: i32, id: i32) -> anyhow::Result<Project> {
+        self.conn.run(move |conn| Box::pin(find_project(conn, user_id, id))).await
+    }
+
+    async fn all(&self, user_id: i32) -> anyhow::Result<Vec<Project>> {
+        self.conn.run(move |conn| Box::pin(async move {
+            let projects = sqlx::query_as::<_, Project>(
+                r#"
+                    select projects.id, projects.name, pm.role from projects
+                        inner join project_members pm on pm.project_id = projects.id and pm.user_id = $1
+                    order by projects.id asc
+                "#
+            )
+            .bind(user_id)
+            .fetch_all(conn)
+            .await?;
+            Ok(projects)
+        })).await
+    }
+
+    async fn update(&self, user_id: i32, id: i32, payload: UpdateProject) -> anyhow::Result<Project> {
+        self.conn.transaction(move |conn| Box::pin(async move {
+            let old_project = find_project(conn, user_id, id).await?;
+            sqlx::query(
+                r#"
+                    update projects set name = $1 where id = $2
+                "#
+            )
+            .bind(payload.name.unwrap_or(old_project.name))
+            .bind(id)
+            .execute(&mut *conn)
+            .await?;
+
+            find_project(conn, user_id, id).await
+        })).await
+    }
+
+    async fn delete(&self, user_id: i32, id: i32) -> anyhow::Result<()> {
+        self.conn.transaction(move |conn| Box::pin(async move {
+            find_project(conn, user_id, id).await?;
+
+            // todoが残っているプロジェクトは削除しない
+            let (todos,): (i64,) = sqlx::query_as(
+                r#"
+                    select count(*) from todos where project_id = $1
+                "#
+            )
+            .bind(id)
+            .fetch_one(&mut *conn)
+            .await?;
+            if todos > 0 {
+                return Err(RepositoryError::InUse(id, todos).into());
+            }
+
+            // メンバーはon delete cascadeで削除される
+            sqlx::query(
+                r#"
+                    delete from projects where id = $1
+                "#
+            )
+            .bind(id)
+            .execute(&mut *conn)
+            .await?;
+            Ok(())
+        })).await
+    }
+
+    async fn members(&self, user_id: i32, id: i32) -> anyhow::Result<Vec<ProjectMember>> {
+        self.conn.run(move |conn| Box::pin(async move {
+            find_project(conn, user_id, id).await?;
+            let members = sqlx::query_as::<_, ProjectMember>(
+                r#"
+                    select users.id as user_id, users.email, pm.role from project_members pm
+                        inner join users on users.id = pm.user_id
+                    where pm.project_id = $1
+                    order by users.id asc
+                "#
+            )
+            .bind(id)
+            .fetch_all(conn)
+            .await?;
+            Ok(members)
+        })).await
+    }
+
+    async fn add_member(&self, user_id: i32, id: i32, email: String, role: ProjectRole) -> anyhow::Result<ProjectMember> {
+        self.conn.transaction(move |conn| Box::pin(async move {
+            find_project(conn, user_id, id).await?;
+
+            let (member_id,): (i32,) = sqlx::query_as(
+                r#"
+                    select id from users where email = $1
+                "#
+            )
+            .bind(email)
+            .fetch_optional(&mut *conn)
+            .await?
+            .ok_or(RepositoryError::UserNotFound)?;
+
+            let result = sqlx::query(
+                r#"
+                    insert into project_members ( project_id, user_id, role )
+                    values ( $1, $2, $3 )
+                    on conflict ( project_id, user_id ) do nothing
+                "#
+            )
+            .bind(id)
+            .bind(member_id)
+            .bind(role)
+            .execute(&mut *conn)
+            .await?;
+            if result.rows_affected() == 0 {
+                return Err(RepositoryError::Duplicate(member_id).into());
+            }
+
+            find_member(conn, id, member_id).await
+        })).await
+    }
+
+    async fn update_member(&self, user_id: i32, id: i32, member_id: i32, role: ProjectRole) -> anyhow::Result<ProjectMember> {
+        self.conn.transaction(move |conn| Box::pin(async move {
+            find_project(conn, user_id, id).await?;
+
+            let result = sqlx::query(
+                r#"
+                    update project_members set role = $3 where project_id = $1 and user_id = $2
+                "#
+            )
+            .bind(id)
+            .bind(member_id)
+            .bind(role)
+            .execute(&mut *conn)
+            .await?;
+            if result.rows_affected() == 0 {
+                return Err(RepositoryError::NotFound(member_id).into());
+            }
+
+            ensure_owner_exists(conn, id).await?;
+            find_member(conn, id, member_id).await
+        })).await
+    }
+
+    async fn delete_member(&self, user_id: i32, id: i32, member_id: i32) -> anyhow::Result<()> {
+        self.conn.transaction(move |conn| Box::pin(async move {
+            find_project(conn, user_id, id).await?;
+
+            let result = sqlx::query(
+                r#"
+                    delete from project_members where project_id = $1 and user_id = $2
+                "#
+            )
+            .bind(id)
+            .bind(member_id)
+            .execute(&mut *conn)
+            .await?;
+            if result.rows_affected() == 0 {
+                return Err(RepositoryError::NotFound(member_id).into());
+            }
+
+            ensure_owner_exists(conn, id).await
+        })).await
+    }
+}
+
+#[cfg(test)]
+pub mod test_utils {
+    use super::*;
+
+    impl CreateProject {
+        pub fn new(name: &str) -> Self {
+            Self { name: name.to_string() }
+        }
+    }
+
+    impl UpdateProject {
+        pub fn new(name: &str) -> Self {
+            Self { name: Some(name.to_string()) }
+        }
+    }
+
+    // どのバックエンドでも同じように動くことを確認するシナリオ
+    // owner, otherは作成済みのユーザーで、other_emailはotherのメールアドレス
+    pub async fn project_scenario<R: ProjectRepository>(repository: R, owner: i32, other: i32, other_email: &str) {
+        // create
+        let project = repository
+            .create(owner, CreateProject::new("shared backlog"))
+            .await
+            .expect("[create] returned Err");
+        assert_eq!(project.name, "shared backlog");
+        assert_eq!(project.role, ProjectRole::Owner);
+
+        // メンバーでなければ見えない
+        let res = repository.find(other, project.id).await;
+        assert!(matches!(
+            res.unwrap_err().downcast_ref::<RepositoryError>(),
+            Some(RepositoryError::NotFound(id)) if *id == project.id
+        ));
+
+        // メンバーでないユーザーのroleは変更できない
+        let res = repository.update_member(owner, project.id, other, ProjectRole::Viewer).await;
+        assert!(matches!(
+            res.unwrap_err().downcast_ref::<RepositoryError>(),
+            Some(RepositoryError::NotFound(id)) if *id == other
+        ));
+
+        // add_member / update_member
+        let member = repository
+            .add_member(owner, project.id, other_email.to_string(), ProjectRole::Viewer)
+            .await
+            .expect("[add_member] returned Err");
+        assert_eq!(member.user_id, other);
+        assert_eq!(member.email, other_email);
+        assert_eq!(member.role, ProjectRole::Viewer);
+        let found = repository.find(other, project.id).await.expect("[find] returned Err");
+        assert_eq!(found.role, ProjectRole::Viewer);
+        let res = repository
+            .add_member(owner, project.id, other_email.to_string(), ProjectRole::Owner)
+            .await;
+        assert!(matches!(
+            res.unwrap_err().downcast_ref::<RepositoryError>(),
+            Some(RepositoryError::Duplicate(id)) if *id == other
+        ));
+        let member = repository
+            .update_member(owner, project.id, other, ProjectRole::Editor)
+            .await
+            .expect("[update_member] returned Err");
+        assert_eq!(member.role, ProjectRole::Editor);
+
+        // 存在しないユーザーは追加できない
+        let res = repository
+            .add_member(owner, project.id, "nobody@example.com".to_string(), ProjectRole::Viewer)
+            .await;
+        assert!(matches!(
+            res.unwrap_err().downcast_ref::<RepositoryError>(),
+            Some(RepositoryError::UserNotFound)
+        ));
+
+        // members
+        let members = repository
+            .members(other, project.id)
+            .await
+            .expect("[members] returned Err");
+        let roles: Vec<_> = members.iter().map(|member| (member.user_id, member.role)).collect();
+        assert_eq!(roles, vec![(owner, ProjectRole::Owner), (other, ProjectRole::Editor)]);
+
+        // ownerがいなくなる変更はできない
+        let res = repository.update_member(owner, project.id, owner, ProjectRole::Editor).await;
+        assert!(matches!(
+            res.unwrap_err().downcast_ref::<RepositoryError>(),
+            Some(RepositoryError::NoOwner(id)) if *id == project.id
+        ));
+        let res = repository.delete_member(owner, project.id, owner).await;
+        assert!(matches!(
+            res.unwrap_err().downcast_ref::<RepositoryError>(),
+            Some(RepositoryError::NoOwner(id)) if *id == project.id
+        ));
+        let found = repository.find(owner, project.id).await.expect("[find] returned Err");
+        assert_eq!(found.role, ProjectRole::Owner);
+
+        // delete_member
+        repository
+            .delete_member(owner, project.id, other)
+            .await
+            .expect("[delete_member] returned Err");
+        assert!(repository.find(other, project.id).await.is_err());
+        let res = repository.delete_member(owner, project.id, other).await;
+        assert!(matches!(
+            res.unwrap_err().downcast_ref::<RepositoryError>(),
+            Some(RepositoryError::NotFound(id)) if *id == other
+        ));
+
+        // update / all
+        let updated = repository
+            .update(owner, project.id, UpdateProject::new("renamed backlog"))
+            .await
+            .expect("[update] returned Err");
+        assert_eq!(updated.name, "renamed backlog");
+        assert!(repository.update(other, project.id, UpdateProject::new("other")).await.is_err());
+        let projects = repository.all(owner).await.expect("[all] returned Err");
+        assert_eq!(projects, vec![updated]);
+        let projects = repository.all(other).await.expect("[all] returned Err");
+        assert!(projects.is_empty());
+
+        // delete
+        assert!(repository.delete(other, project.id).await.is_err());
+        repository.delete(owner, project.id).await.expect("[delete] returned Err");
+        let res = repository.find(owner, project.id).await;
+        assert!(matches!(
+            res.unwrap_err().downcast_ref::<RepositoryError>(),
+            Some(RepositoryError::NotFound(id)) if *id == project.id
+        ));
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "database-test")]
+mod test {
+    use super::*;
+    use crate::repositories::test_utils::postgres_user;
+    use dotenv::dotenv;
+    use std::env;
+
+    #[tokio::test]
+    async fn crud_scenario() {
+        dotenv().ok();
+        let database_url = &env::var("DATABASE_URL").expect("undefined [DATABASE_URL]");
+        let pool = PgPool::connect(database_url)
+            .await
+            .unwrap_or_else(|_| panic!("fail connect database, url is [{}]", database_url));
+        let owner = postgres_user(&pool, "project_owner").await;
+        let other = postgres_user(&pool, "project_other").await;
+        let (other_email,): (String,) = sqlx::query_as("select email from users where id = $1")
+            .bind(other)
+            .fetch_one(&pool)
+            .await
+            .expect("failed find user");
+
+        test_utils::project_scenario(ProjectRepositoryForDb::new(pool), owner, other, &other_email).await;
+    }
+}
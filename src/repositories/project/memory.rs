@@ -0,0 +1,173 @@
+use axum::async_trait;
+
+use super::{
+    CreateProject, Project, ProjectMember, ProjectRepository, ProjectRole, UpdateProject,
+};
+use crate::repositories::{
+    memory::{MemoryStore, ProjectRecord, Tables},
+    RepositoryError,
+};
+
+#[derive(Debug, Clone)]
+pub struct ProjectRepositoryForMemory {
+    store: MemoryStore,
+}
+
+impl ProjectRepositoryForMemory {
+    pub fn new(store: MemoryStore) -> Self {
+        ProjectRepositoryForMemory { store }
+    }
+}
+
+// メンバーでないプロジェクトは、存在しないものとして扱う
+fn find_project(tables: &Tables, user_id: i32, id: i32) -> anyhow::Result<Project> {
+    let role = tables.project_role(user_id, id).ok_or(RepositoryError::NotFound(id))?;
+    let record = tables.projects.get(&id).ok_or(RepositoryError::NotFound(id))?;
+    Ok(Project {
+        id,
+        name: record.name.clone(),
+        role,
+    })
+}
+
+fn to_member(tables: &Tables, id: i32, member_id: i32) -> anyhow::Result<ProjectMember> {
+    let role = tables.project_role(member_id, id).ok_or(RepositoryError::NotFound(member_id))?;
+    let user = tables.users.get(&member_id).ok_or(RepositoryError::NotFound(member_id))?;
+    Ok(ProjectMember {
+        user_id: member_id,
+        email: user.email.clone(),
+        role,
+    })
+}
+
+// member_idのroleをroleに変えた（Noneなら外した）後にも、ownerが残るか
+fn ensure_owner_remains(tables: &Tables, id: i32, member_id: i32, role: Option<ProjectRole>) -> anyhow::Result<()> {
+    let other_owner = tables.project_members.iter().any(|(&(project_id, user_id), &member_role)| {
+        project_id == id && user_id != member_id && member_role == ProjectRole::Owner
+    });
+    if !other_owner && role != Some(ProjectRole::Owner) {
+        return Err(RepositoryError::NoOwner(id).into());
+    }
+    Ok(())
+}
+
+#[async_trait]
+impl ProjectRepository for ProjectRepositoryForMemory {
+    async fn create(&self, user_id: i32, payload: CreateProject) -> anyhow::Result<Project> {
+        let mut tables = self.store.write();
+        let record = ProjectRecord {
+            id: self.store.next_project_id(),
+            name: payload.name,
+        };
+        tables.project_members.insert((record.id, user_id), ProjectRole::Owner);
+        tables.projects.insert(record.id, record.clone());
+        find_project(&tables, user_id, record.id)
+    }
+
+    async fn find(&self, user_id: i32, id: i32) -> anyhow::Result<Project> {
+        let tables = self.store.read();
+        find_project(&tables, user_id, id)
+    }
+
+    async fn all(&self, user_id: i32) -> anyhow::Result<Vec<Project>> {
+        let tables = self.store.read();
+        let mut projects: Vec<Project> = tables
+            .projects
+            .keys()
+            .filter_map(|id| find_project(&tables, user_id, *id).ok())
+            .collect();
+        projects.sort_by_key(|project| project.id);
+        Ok(projects)
+    }
+
+    async fn update(&self, user_id: i32, id: i32, payload: UpdateProject) -> anyhow::Result<Project> {
+        let mut tables = self.store.write();
+        find_project(&tables, user_id, id)?;
+        let record = tables.projects.get_mut(&id).ok_or(RepositoryError::NotFound(id))?;
+        if let Some(name) = payload.name {
+            record.name = name;
+        }
+        find_project(&tables, user_id, id)
+    }
+
+    async fn delete(&self, user_id: i32, id: i32) -> anyhow::Result<()> {
+        let mut tables = self.store.write();
+        find_project(&tables, user_id, id)?;
+        let todos = tables.todos.values().filter(|todo| todo.project_id == Some(id)).count();
+        if todos > 0 {
+            return Err(RepositoryError::InUse(id, todos as i64).into());
+        }
+        tables.projects.remove(&id);
+        tables.project_members.retain(|(project_id, _), _| *project_id != id);
+        Ok(())
+    }
+
+    async fn members(&self, user_id: i32, id: i32) -> anyhow::Result<Vec<ProjectMember>> {
+        let tables = self.store.read();
+        find_project(&tables, user_id, id)?;
+        let mut member_ids: Vec<i32> = tables
+            .project_members
+            .keys()
+            .filter(|(project_id, _)| *project_id == id)
+            .map(|(_, member_id)| *member_id)
+            .collect();
+        // DBと同じく、ユーザーのid順に並べる
+        member_ids.sort_unstable();
+        member_ids
+            .into_iter()
+            .map(|member_id| to_member(&tables, id, member_id))
+            .collect()
+    }
+
+    async fn add_member(&self, user_id: i32, id: i32, email: String, role: ProjectRole) -> anyhow::Result<ProjectMember> {
+        let mut tables = self.store.write();
+        find_project(&tables, user_id, id)?;
+        let member_id = tables
+            .users
+            .values()
+            .find(|user| user.email == email)
+            .map(|user| user.id)
+            .ok_or(RepositoryError::UserNotFound)?;
+        if tables.project_role(member_id, id).is_some() {
+            return Err(RepositoryError::Duplicate(member_id).into());
+        }
+        tables.project_members.insert((id, member_id), role);
+        to_member(&tables, id, member_id)
+    }
+
+    async fn update_member(&self, user_id: i32, id: i32, member_id: i32, role: ProjectRole) -> anyhow::Result<ProjectMember> {
+        let mut tables = self.store.write();
+        find_project(&tables, user_id, id)?;
+        if tables.project_role(member_id, id).is_none() {
+            return Err(RepositoryError::NotFound(member_id).into());
+        }
+        ensure_owner_remains(&tables, id, member_id, Some(role))?;
+        tables.project_members.insert((id, member_id), role);
+        to_member(&tables, id, member_id)
+    }
+
+    async fn delete_member(&self, user_id: i32, id: i32, member_id: i32) -> anyhow::Result<()> {
+        let mut tables = self.store.write();
+        find_project(&tables, user_id, id)?;
+        if tables.project_role(member_id, id).is_none() {
+            return Err(RepositoryError::NotFound(member_id).into());
+        }
+        ensure_owner_remains(&tables, id, member_id, None)?;
+        tables.project_members.remove(&(id, member_id));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::repositories::project::test_utils::project_scenario;
+
+    #[tokio::test]
+    async fn project_crud_scenario() {
+        let store = MemoryStore::new();
+        let owner = store.add_session("owner@example.com", "owner-token");
+        let other = store.add_session("other@example.com", "other-token");
+        project_scenario(ProjectRepositoryForMemory::new(store), owner, other, "other@example.com").await;
+    }
+}
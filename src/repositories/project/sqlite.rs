@@ -0,0 +1,281 @@
+use axum::async_trait;
+use sqlx::{Sqlite, SqliteConnection, SqlitePool};
+
+use super::{
+    CreateProject, Project, ProjectMember, ProjectRepository, ProjectRole, UpdateProject,
+};
+use crate::repositories::{unit_of_work::DbConnection, RepositoryError};
+
+// SQLはPostgres版と同じ
+#[derive(Debug, Clone)]
+pub struct ProjectRepositoryForSqlite {
+    conn: DbConnection<Sqlite>,
+}
+
+impl ProjectRepositoryForSqlite {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { conn: DbConnection::Pool(pool) }
+    }
+}
+
+async fn find_project(conn: &mut SqliteConnection, user_id: i32, id: i32) -> anyhow::Result<Project> {
+    let project = sqlx::query_as::<_, Project>(
+        r#"
+            select projects.id, projects.name, pm.role from projects
+                inner join project_members pm on pm.project_id = projects.id and pm.user_id = $2
+            where projects.id = $1
+        "#
+    )
+    .bind(id)
+    .bind(user_id)
+    .fetch_optional(conn)
+    .await?
+    .ok_or(RepositoryError::NotFound(id))?;
+
+    Ok(project)
+}
+
+async fn find_member(conn: &mut SqliteConnection, id: i32, member_id: i32) -> anyhow::Result<ProjectMember> {
+    let member = sqlx::query_as::<_, ProjectMember>(
+        r#"
+            select users.id as user_id, users.email, pm.role from project_members pm
+                inner join users on users.id = pm.user_id
+            where pm.project_id = $1 and pm.user_id = $2
+        "#
+    )
+    .bind(id)
+    .bind(member_id)
+    .fetch_optional(conn)
+    .await?
+    .ok_or(RepositoryError::NotFound(member_id))?;
+
+    Ok(member)
+}
+
+async fn ensure_owner_exists(conn: &mut SqliteConnection, id: i32) -> anyhow::Result<()> {
+    let (owners,): (i64,) = sqlx::query_as(
+        r#"
+            select count(*) from project_members where project_id = $1 and role = $2
+        "#
+    )
+    .bind(id)
+    .bind(ProjectRole::Owner)
+    .fetch_one(conn)
+    .await?;
+
+    if owners == 0 {
+        return Err(RepositoryError::NoOwner(id).into());
+    }
+    Ok(())
+}
+
+#[async_trait]
+impl ProjectRepository for ProjectRepositoryForSqlite {
+    async fn create(&self, user_id: i32, payload: CreateProject) -> anyhow::Result<Project> {
+        self.conn.transaction(move |conn| Box::pin(async move {
+            let (id,): (i32,) = sqlx::query_as(
+                r#"
+                    insert into projects ( name ) values ( $1 )
+                    returning id
+                "#
+            )
+            .bind(payload.name)
+            .fetch_one(&mut *conn)
+            .await?;
+
+            sqlx::query(
+                r#"
+                    insert into project_members ( project_id, user_id, role )
+                    values ( $1, $2, $3 )
+                "#
+            )
+            .bind(id)
+            .bind(user_id)
+            .bind(ProjectRole::Owner)
+            .execute(&mut *conn)
+            .await?;
+
+            find_project(conn, user_id, id).await
+        })).await
+    }
+
+    async fn find(&self, user_id: i32, id: i32) -> anyhow::Result<Project> {
+        self.conn.run(move |conn| Box::pin(find_project(conn, user_id, id))).await
+    }
+
+    async fn all(&self, user_id: i32) -> anyhow::Result<Vec<Project>> {
+        self.conn.run(move |conn| Box::pin(async move {
+            let projects = sqlx::query_as::<_, Project>(
+                r#"
+                    select projects.id, projects.name, pm.role from projects
+                        inner join project_members pm on pm.project_id = projects.id and pm.user_id = $1
+                    order by projects.id asc
+                "#
+            )
+            .bind(user_id)
+            .fetch_all(conn)
+            .await?;
+            Ok(projects)
+        })).await
+    }
+
+    async fn update(&self, user_id: i32, id: i32, payload: UpdateProject) -> anyhow::Result<Project> {
+        self.conn.transaction(move |conn| Box::pin(async move {
+            let old_project = find_project(conn, user_id, id).await?;
+            sqlx::query(
+                r#"
+                    update projects set name = $1 where id = $2
+                "#
+            )
+            .bind(payload.name.unwrap_or(old_project.name))
+            .bind(id)
+            .execute(&mut *conn)
+            .await?;
+
+            find_project(conn, user_id, id).await
+        })).await
+    }
+
+    async fn delete(&self, user_id: i32, id: i32) -> anyhow::Result<()> {
+        self.conn.transaction(move |conn| Box::pin(async move {
+            find_project(conn, user_id, id).await?;
+
+            // todoが残っているプロジェクトは削除しない
+            let (todos,): (i64,) = sqlx::query_as(
+                r#"
+                    select count(*) from todos where project_id = $1
+                "#
+            )
+            .bind(id)
+            .fetch_one(&mut *conn)
+            .await?;
+            if todos > 0 {
+                return Err(RepositoryError::InUse(id, todos).into());
+            }
+
+            // メンバーはon delete cascadeで削除される
+            sqlx::query(
+                r#"
+                    delete from projects where id = $1
+                "#
+            )
+            .bind(id)
+            .execute(&mut *conn)
+            .await?;
+            Ok(())
+        })).await
+    }
+
+    async fn members(&self, user_id: i32, id: i32) -> anyhow::Result<Vec<ProjectMember>> {
+        self.conn.run(move |conn| Box::pin(async move {
+            find_project(conn, user_id, id).await?;
+            let members = sqlx::query_as::<_, ProjectMember>(
+                r#"
+                    select users.id as user_id, users.email, pm.role from project_members pm
+                        inner join users on users.id = pm.user_id
+                    where pm.project_id = $1
+                    order by users.id asc
+                "#
+            )
+            .bind(id)
+            .fetch_all(conn)
+            .await?;
+            Ok(members)
+        })).await
+    }
+
+    async fn add_member(&self, user_id: i32, id: i32, email: String, role: ProjectRole) -> anyhow::Result<ProjectMember> {
+        self.conn.transaction(move |conn| Box::pin(async move {
+            find_project(conn, user_id, id).await?;
+
+            let (member_id,): (i32,) = sqlx::query_as(
+                r#"
+                    select id from users where email = $1
+                "#
+            )
+            .bind(email)
+            .fetch_optional(&mut *conn)
+            .await?
+            .ok_or(RepositoryError::UserNotFound)?;
+
+            let result = sqlx::query(
+                r#"
+                    insert into project_members ( project_id, user_id, role )
+                    values ( $1, $2, $3 )
+                    on conflict ( project_id, user_id ) do nothing
+                "#
+            )
+            .bind(id)
+            .bind(member_id)
+            .bind(role)
+            .execute(&mut *conn)
+            .await?;
+            if result.rows_affected() == 0 {
+                return Err(RepositoryError::Duplicate(member_id).into());
+            }
+
+            find_member(conn, id, member_id).await
+        })).await
+    }
+
+    async fn update_member(&self, user_id: i32, id: i32, member_id: i32, role: ProjectRole) -> anyhow::Result<ProjectMember> {
+        self.conn.transaction(move |conn| Box::pin(async move {
+            find_project(conn, user_id, id).await?;
+
+            let result = sqlx::query(
+                r#"
+                    update project_members set role = $3 where project_id = $1 and user_id = $2
+                "#
+            )
+            .bind(id)
+            .bind(member_id)
+            .bind(role)
+            .execute(&mut *conn)
+            .await?;
+            if result.rows_affected() == 0 {
+                return Err(RepositoryError::NotFound(member_id).into());
+            }
+
+            ensure_owner_exists(conn, id).await?;
+            find_member(conn, id, member_id).await
+        })).await
+    }
+
+    async fn delete_member(&self, user_id: i32, id: i32, member_id: i32) -> anyhow::Result<()> {
+        self.conn.transaction(move |conn| Box::pin(async move {
+            find_project(conn, user_id, id).await?;
+
+            let result = sqlx::query(
+                r#"
+                    delete from project_members where project_id = $1 and user_id = $2
+                "#
+            )
+            .bind(id)
+            .bind(member_id)
+            .execute(&mut *conn)
+            .await?;
+            if result.rows_affected() == 0 {
+                return Err(RepositoryError::NotFound(member_id).into());
+            }
+
+            ensure_owner_exists(conn, id).await
+        })).await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::repositories::{
+        project::test_utils::project_scenario,
+        test_utils::{sqlite_pool, sqlite_user},
+    };
+
+    #[tokio::test]
+    async fn crud_scenario() {
+        let pool = sqlite_pool().await;
+        let owner = sqlite_user(&pool, "owner@example.com").await;
+        let other = sqlite_user(&pool, "other@example.com").await;
+        project_scenario(ProjectRepositoryForSqlite::new(pool), owner, other, "other@example.com").await;
+    }
+}
@@ -0,0 +1,203 @@
+use axum::async_trait;
+use serde::{Deserialize, Serialize};
+use sqlx::{migrate::Migrator, PgPool, SqlitePool};
+
+// バイナリに埋め込んだマイグレーション
+static POSTGRES_MIGRATOR: Migrator = sqlx::migrate!("./migrations");
+static SQLITE_MIGRATOR: Migrator = sqlx::migrate!("./migrations/sqlite");
+
+// データベースのスキーマを管理する
+#[async_trait]
+pub trait SchemaRepository: Clone + Send + Sync + 'static {
+    async fn version(&self) -> anyhow::Result<SchemaVersion>;
+    async fn migrate(&self) -> anyhow::Result<()>;
+}
+
+// current: データベースに適用済みの最新のマイグレーション
+// latest: このバイナリが知っている最新のマイグレーション
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub struct SchemaVersion {
+    pub current: Option<i64>,
+    pub latest: Option<i64>,
+    pub up_to_date: bool,
+}
+
+impl SchemaVersion {
+    pub fn new(current: Option<i64>, latest: Option<i64>) -> Self {
+        Self {
+            current,
+            latest,
+            up_to_date: current == latest,
+        }
+    }
+
+    // 新しいバイナリでマイグレーションされたデータベースに、古いバイナリで接続している
+    pub fn is_newer_than_binary(&self) -> bool {
+        self.current > self.latest
+    }
+}
+
+fn latest_version(migrator: &Migrator) -> Option<i64> {
+    migrator.iter().map(|migration| migration.version).max()
+}
+
+#[derive(Debug, Clone)]
+pub struct SchemaRepositoryForDb {
+    pool: PgPool,
+}
+
+impl SchemaRepositoryForDb {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl SchemaRepository for SchemaRepositoryForDb {
+    async fn version(&self) -> anyhow::Result<SchemaVersion> {
+        // 一度もマイグレーションしていなければ、管理用のテーブル自体がない
+        let (exists,): (bool,) = sqlx::query_as(
+            r#"
+                select to_regclass('_sqlx_migrations') is not null
+            "#
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        let current = if exists {
+            let (current,): (Option<i64>,) = sqlx::query_as(
+                r#"
+                    select max(version) from _sqlx_migrations where success
+                "#
+            )
+            .fetch_one(&self.pool)
+            .await?;
+            current
+        } else {
+            None
+        };
+        Ok(SchemaVersion::new(current, latest_version(&POSTGRES_MIGRATOR)))
+    }
+
+    async fn migrate(&self) -> anyhow::Result<()> {
+        POSTGRES_MIGRATOR.run(&self.pool).await?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SchemaRepositoryForSqlite {
+    pool: SqlitePool,
+}
+
+impl SchemaRepositoryForSqlite {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl SchemaRepository for SchemaRepositoryForSqlite {
+    async fn version(&self) -> anyhow::Result<SchemaVersion> {
+        let (exists,): (bool,) = sqlx::query_as(
+            r#"
+                select count(*) > 0 from sqlite_master
+                where type = 'table' and name = '_sqlx_migrations'
+            "#
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        let current = if exists {
+            let (current,): (Option<i64>,) = sqlx::query_as(
+                r#"
+                    select max(version) from _sqlx_migrations where success
+                "#
+            )
+            .fetch_one(&self.pool)
+            .await?;
+            current
+        } else {
+            None
+        };
+        Ok(SchemaVersion::new(current, latest_version(&SQLITE_MIGRATOR)))
+    }
+
+    async fn migrate(&self) -> anyhow::Result<()> {
+        SQLITE_MIGRATOR.run(&self.pool).await?;
+        Ok(())
+    }
+}
+
+// メモリ上のストアにはスキーマがないので、常に最新として扱う
+#[cfg(any(test, feature = "memory-storage"))]
+#[derive(Debug, Clone, Default)]
+pub struct SchemaRepositoryForMemory;
+
+#[cfg(any(test, feature = "memory-storage"))]
+#[async_trait]
+impl SchemaRepository for SchemaRepositoryForMemory {
+    async fn version(&self) -> anyhow::Result<SchemaVersion> {
+        Ok(SchemaVersion::new(None, None))
+    }
+
+    async fn migrate(&self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    #[test]
+    fn newer_schema_detection() {
+        assert!(SchemaVersion::new(Some(3), Some(2)).is_newer_than_binary());
+        assert!(!SchemaVersion::new(Some(2), Some(2)).is_newer_than_binary());
+        assert!(!SchemaVersion::new(None, Some(2)).is_newer_than_binary());
+        assert!(SchemaVersion::new(Some(2), Some(2)).up_to_date);
+        assert!(!SchemaVersion::new(Some(1), Some(2)).up_to_date);
+    }
+
+    #[tokio::test]
+    async fn sqlite_migrate_scenario() {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .expect("fail connect in-memory sqlite");
+        let repository = SchemaRepositoryForSqlite::new(pool.clone());
+        let latest = latest_version(&SQLITE_MIGRATOR);
+
+        let version = repository.version().await.expect("[version] returned Err");
+        assert_eq!(version, SchemaVersion::new(None, latest));
+
+        repository.migrate().await.expect("[migrate] returned Err");
+        let version = repository.version().await.expect("[version] returned Err");
+        assert_eq!(version, SchemaVersion::new(latest, latest));
+
+        // 同じマイグレーションを2回実行しても問題ない
+        repository.migrate().await.expect("[migrate] returned Err");
+
+        // 知らないバージョンが適用されたデータベースは新しすぎる
+        sqlx::query(
+            r#"
+                insert into _sqlx_migrations (version, description, success, checksum, execution_time)
+                values (99990101000000, 'from the future', true, x'00', 0)
+            "#
+        )
+        .execute(&pool)
+        .await
+        .expect("fail insert future migration");
+        let version = repository.version().await.expect("[version] returned Err");
+        assert!(version.is_newer_than_binary());
+    }
+
+    // Postgresとsqliteで、同じバージョンのマイグレーションを持っている
+    #[test]
+    fn migrations_are_in_sync() {
+        let versions = |migrator: &Migrator| -> Vec<i64> {
+            migrator.iter().map(|migration| migration.version).collect()
+        };
+        assert_eq!(versions(&POSTGRES_MIGRATOR), versions(&SQLITE_MIGRATOR));
+    }
+}
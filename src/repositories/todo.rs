@@ -1,27 +1,56 @@
+#[cfg(any(test, feature = "memory-storage"))]
+pub mod memory;
+pub mod sqlite;
+
 use anyhow::Ok;
 use axum::async_trait;
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use sqlx::{PgPool, FromRow};
+use sqlx::{FromRow, PgConnection, PgPool};
+use std::collections::HashMap;
 use validator::Validate;
 
 use super::{
     label::Label,
-    RepositoryError,
+    nullable,
+    unit_of_work::DbConnection,
+    Cursor, CursorOrder, Page, PageRequest, RepositoryError,
 };
 
+#[cfg(any(test, feature = "memory-storage"))]
+pub use memory::TodoRepositoryForMemory;
+pub use sqlite::TodoRepositoryForSqlite;
+
 // データレポジトリを作成
 
 // トレイトを用いてデータレポジトリの振る舞い（CRUD）を定義する
 // これを継承した構造体はCRUDできるようになる
 // CloneとSendとSyncを実装した型に対して実装するトレイト（SendとSyncは基本的にどの型にも実装されている）
 // 'staticとする事によってライフタイムをなくす
+// user_idは操作するユーザーで、見えないtodoは存在しないものとして扱う（NotFound）
+// 見えるのは、自分が作成したプロジェクト外のtodoと、メンバーになっているプロジェクトのtodo
+// プロジェクトのroleによる操作の制限はハンドラーで行う
 #[async_trait] // トレイトの各メソッドをasyncにする
 pub trait TodoRepository: Clone + Send + Sync + 'static {
-    async fn create(&self, payload: CreateTodo) -> anyhow::Result<TodoEntity>;
-    async fn find(&self, id: i32) -> anyhow::Result<TodoEntity>;
-    async fn all(&self) -> anyhow::Result<Vec<TodoEntity>>;
-    async fn update(&self, id: i32, payload: UpdateTodo) -> anyhow::Result<TodoEntity>;
-    async fn delete(&self, id: i32) -> anyhow::Result<()>;
+    async fn create(&self, user_id: i32, payload: CreateTodo) -> anyhow::Result<TodoEntity>;
+    async fn find(&self, user_id: i32, id: i32) -> anyhow::Result<TodoEntity>;
+    async fn all(&self, user_id: i32, query: TodoQuery, page: PageRequest) -> anyhow::Result<Page<TodoEntity>>;
+    // 子孫のtodoを、浅い順（同じ深さの中ではid順）に返す
+    async fn children(&self, user_id: i32, id: i32) -> anyhow::Result<Vec<TodoNode>>;
+    async fn update(&self, user_id: i32, id: i32, payload: UpdateTodo) -> anyhow::Result<TodoEntity>;
+    async fn delete(&self, user_id: i32, id: i32) -> anyhow::Result<()>;
+    // チェックリストの操作は、いずれも変更後のtodoを返す
+    async fn add_checklist_item(&self, user_id: i32, id: i32, payload: CreateChecklistItem) -> anyhow::Result<TodoEntity>;
+    async fn update_checklist_item(
+        &self,
+        user_id: i32,
+        id: i32,
+        item_id: i32,
+        payload: UpdateChecklistItem,
+    ) -> anyhow::Result<TodoEntity>;
+    // item_idsの順に並べ替える（todoの全ての項目を1回ずつ含める）
+    async fn reorder_checklist(&self, user_id: i32, id: i32, item_ids: Vec<i32>) -> anyhow::Result<TodoEntity>;
+    async fn delete_checklist_item(&self, user_id: i32, id: i32, item_id: i32) -> anyhow::Result<TodoEntity>;
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, FromRow)]
@@ -29,6 +58,8 @@ struct TodoFromRow {
     id: i32,
     text: String,
     completed: bool,
+    due_date: Option<DateTime<Utc>>,
+    priority: Priority,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, FromRow)]
@@ -36,40 +67,167 @@ pub struct TodoWithLabelFromRow {
     id: i32,
     text: String,
     completed: bool,
+    due_date: Option<DateTime<Utc>>,
+    priority: Priority,
+    project_id: Option<i32>,
+    parent_id: Option<i32>,
     label_id: Option<i32>,
     label_name: Option<String>,
+    label_color: Option<String>,
+    label_description: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct TodoEntity {
     pub id: i32,
     pub text: String,
     pub completed: bool,
+    pub due_date: Option<DateTime<Utc>>,
+    pub priority: Priority,
+    pub project_id: Option<i32>,
+    pub parent_id: Option<i32>,
     pub labels: Vec<Label>,
+    // progressは完了した項目の割合で、項目がなければnull
+    pub checklist: Vec<ChecklistItem>,
+    pub progress: Option<f64>,
+}
+
+impl TodoEntity {
+    // チェックリストを付け、進捗の割合も計算する
+    fn with_checklist(self, checklist: Vec<ChecklistItem>) -> Self {
+        let done = checklist.iter().filter(|item| item.done).count();
+        let progress = (!checklist.is_empty()).then(|| done as f64 / checklist.len() as f64);
+        Self {
+            checklist,
+            progress,
+            ..self
+        }
+    }
+
+    // sortで指定された並び順における、このtodoの位置
+    fn cursor(&self, sort: TodoSort) -> Cursor {
+        let key = match sort {
+            TodoSort::Priority => self.priority as i32,
+            TodoSort::Id => 0,
+        };
+        Cursor { order: sort.cursor_order(), key, id: self.id }
+    }
+}
+
+// GET /todos/:id/childrenで返す子孫のtodo
+// depthは指定したtodoの子が1、孫が2となる
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct TodoNode {
+    pub depth: i32,
+    #[serde(flatten)]
+    pub todo: TodoEntity,
+}
+
+// 浅い順に並んだ子孫のtodoに、rootからの深さを付ける
+fn to_nodes(root: i32, todos: Vec<TodoEntity>) -> Vec<TodoNode> {
+    let mut depths = HashMap::from([(root, 0)]);
+    todos
+        .into_iter()
+        .map(|todo| {
+            let parent_depth = todo.parent_id.and_then(|id| depths.get(&id)).copied().unwrap_or(0);
+            depths.insert(todo.id, parent_depth + 1);
+            TodoNode { depth: parent_depth + 1, todo }
+        })
+        .collect()
+}
+
+// todoのチェックリストの項目
+// positionはどのレポジトリでも1から始まる並び順
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct ChecklistItem {
+    pub id: i32,
+    pub text: String,
+    pub done: bool,
+    pub position: i32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, FromRow)]
+struct ChecklistItemFromRow {
+    id: i32,
+    todo_id: i32,
+    text: String,
+    done: bool,
+    position: i32,
+}
+
+// 並び替えに指定したidが、チェックリストの項目をちょうど1つずつ含んでいるか確かめる
+fn ensure_same_checklist(id: i32, checklist: &[ChecklistItem], item_ids: &[i32]) -> anyhow::Result<()> {
+    let mut item_ids = item_ids.to_vec();
+    item_ids.sort_unstable();
+    let mut current: Vec<i32> = checklist.iter().map(|item| item.id).collect();
+    current.sort_unstable();
+    if item_ids != current {
+        return Err(RepositoryError::ChecklistMismatch(id).into());
+    }
+    Ok(())
+}
+
+// 読み込んだチェックリストの項目を、それぞれのtodoに付ける
+// rowsはtodoごとに並び順になっている
+fn attach_checklists(todos: Vec<TodoEntity>, rows: Vec<ChecklistItemFromRow>) -> Vec<TodoEntity> {
+    let mut checklists: HashMap<i32, Vec<ChecklistItem>> = HashMap::new();
+    for row in rows {
+        checklists.entry(row.todo_id).or_default().push(ChecklistItem {
+            id: row.id,
+            text: row.text,
+            done: row.done,
+            position: row.position,
+        });
+    }
+    todos
+        .into_iter()
+        .map(|todo| {
+            let checklist = checklists.remove(&todo.id).unwrap_or_default();
+            todo.with_checklist(checklist)
+        })
+        .collect()
+}
+
+// todoの優先度
+// データベースにはsmallintとして保存し、値が大きいほど優先度が高い
+#[derive(
+    Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, sqlx::Type,
+)]
+#[serde(rename_all = "lowercase")]
+#[repr(i16)]
+pub enum Priority {
+    #[default]
+    None = 0,
+    Low = 1,
+    Medium = 2,
+    High = 3,
+    Urgent = 4,
 }
 
 // Vec<TodoWithLabelFromRow>からVec<TodoEntity>への変換
 fn fold_entities(rows: Vec<TodoWithLabelFromRow>) -> Vec<TodoEntity> {
-    let mut rows = rows.iter();
     let mut accum: Vec<TodoEntity> = vec![];
-    'outer: while let Some(row) = rows.next() {
-        let mut todos = accum.iter_mut();
-        while let Some(todo) = todos.next() {
+    'outer: for row in rows.iter() {
+        for todo in accum.iter_mut() {
             if todo.id == row.id {
                 todo.labels.push(
                     Label {
                         id: row.label_id.unwrap(),
                         name: row.label_name.clone().unwrap(),
+                        color: row.label_color.clone(),
+                        description: row.label_description.clone(),
                     }
                 );
                 continue 'outer;
             }
         }
-        let labels = if row.label_id.is_some() {
+        let labels = if let Some(label_id) = row.label_id {
             vec![
                 Label {
-                    id: row.label_id.unwrap(),
+                    id: label_id,
                     name: row.label_name.clone().unwrap(),
+                    color: row.label_color.clone(),
+                    description: row.label_description.clone(),
                 }
             ]
         } else {
@@ -81,7 +239,13 @@ fn fold_entities(rows: Vec<TodoWithLabelFromRow>) -> Vec<TodoEntity> {
                 id: row.id,
                 text: row.text.clone(),
                 completed: row.completed,
-                labels
+                due_date: row.due_date,
+                priority: row.priority,
+                project_id: row.project_id,
+                parent_id: row.parent_id,
+                labels,
+                checklist: vec![],
+                progress: None,
             }
         );
     }
@@ -94,6 +258,25 @@ pub struct CreateTodo {
     #[validate(length(max = 100, message = "Over text length"))]
     text: String,
     labels: Vec<i32>,
+    #[serde(default)]
+    due_date: Option<DateTime<Utc>>,
+    #[serde(default)]
+    priority: Priority,
+    #[serde(default)]
+    project_id: Option<i32>,
+    // サブタスクとして作るときの親のtodo
+    #[serde(default)]
+    parent_id: Option<i32>,
+}
+
+impl CreateTodo {
+    pub fn project_id(&self) -> Option<i32> {
+        self.project_id
+    }
+
+    pub fn parent_id(&self) -> Option<i32> {
+        self.parent_id
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Validate)]
@@ -103,169 +286,534 @@ pub struct UpdateTodo {
     text: Option<String>,
     completed: Option<bool>,
     labels: Option<Vec<i32>>,
+    // nullを指定すると期限を消す
+    #[serde(default, deserialize_with = "nullable")]
+    due_date: Option<Option<DateTime<Utc>>>,
+    priority: Option<Priority>,
+    // 別のプロジェクトに移動する（プロジェクト外に戻すことはできない）
+    project_id: Option<i32>,
+    // trueなら、completedの変更を子孫のtodoにも反映する
+    #[serde(default)]
+    cascade_completed: bool,
+}
+
+impl UpdateTodo {
+    pub fn project_id(&self) -> Option<i32> {
+        self.project_id
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Validate)]
+pub struct CreateChecklistItem {
+    #[validate(length(min = 1, message = "Can not be empty"))]
+    #[validate(length(max = 100, message = "Over text length"))]
+    text: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Validate)]
+pub struct UpdateChecklistItem {
+    #[validate(length(min = 1, message = "Can not be empty"))]
+    #[validate(length(max = 100, message = "Over text length"))]
+    text: Option<String>,
+    done: Option<bool>,
+}
+
+// GET /todosのクエリパラメータ
+// due_before: 期限がこの日時より前のtodoに絞り込む
+// overdue: trueなら期限切れかつ未完了のtodoに絞り込む
+// sort: priorityなら優先度の高い順、その後id順に並べる
+// completed: 完了状態で絞り込む
+// labels: 指定したラベルを持つtodoに絞り込む（label_matchがanyならいずれか、allなら全て）
+// text: 本文に含まれる文字列で絞り込む（大文字小文字は区別しない）
+// project_id: プロジェクトのtodoに絞り込む
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+pub struct TodoQuery {
+    pub due_before: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub overdue: bool,
+    #[serde(default)]
+    pub sort: TodoSort,
+    pub completed: Option<bool>,
+    #[serde(default, skip_deserializing)]
+    pub labels: Vec<i32>,
+    #[serde(default)]
+    pub label_match: LabelMatch,
+    pub text: Option<String>,
+    pub project_id: Option<i32>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LabelMatch {
+    #[default]
+    Any,
+    All,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TodoSort {
+    #[default]
+    Id,
+    Priority,
+}
+
+impl TodoSort {
+    // todoはどちらの並び順でも降順に並べる
+    pub fn cursor_order(self) -> CursorOrder {
+        match self {
+            TodoSort::Id => CursorOrder::IdDesc,
+            TodoSort::Priority => CursorOrder::PriorityDesc,
+        }
+    }
+}
+
+// ラベルでの絞り込み（all）は重複したidがあると件数が合わなくなるので取り除く
+fn dedup_label_ids(mut labels: Vec<i32>) -> Vec<i32> {
+    labels.sort_unstable();
+    labels.dedup();
+    labels
 }
 
 // データベースの操作を行うオブジェクト
 
 #[derive(Debug, Clone)]
 pub struct TodoRepositoryForDb {
-    pool: PgPool,
+    conn: DbConnection,
 }
 
 impl TodoRepositoryForDb {
     pub fn new(pool: PgPool) -> Self {
-        TodoRepositoryForDb { pool }
+        TodoRepositoryForDb { conn: DbConnection::Pool(pool) }
     }
+
+    pub fn with_connection(conn: DbConnection) -> Self {
+        TodoRepositoryForDb { conn }
+    }
+}
+
+// labelsテーブルに存在しない（または他のユーザーの）ラベルidがあればエラーにする
+async fn ensure_labels_exist(conn: &mut PgConnection, user_id: i32, labels: &[i32]) -> anyhow::Result<()> {
+    let missing: Vec<(i32,)> = sqlx::query_as(
+        r#"
+            select distinct t.id from unnest($1::integer[]) as t(id)
+            where not exists (select 1 from labels where labels.id = t.id and labels.user_id = $2)
+            order by t.id
+        "#
+    )
+    .bind(labels)
+    .bind(user_id)
+    .fetch_all(conn)
+    .await?;
+
+    if !missing.is_empty() {
+        let ids = missing.into_iter().map(|(id,)| id).collect();
+        return Err(RepositoryError::LabelNotFound(ids).into());
+    }
+    Ok(())
+}
+
+// ラベルはユーザーごとのものなので、共有されたtodoでも自分のラベルだけをjoinする
+// todoのチェックリストを並び順に読み込んで付ける
+async fn load_checklists(conn: &mut PgConnection, todos: Vec<TodoEntity>) -> anyhow::Result<Vec<TodoEntity>> {
+    let ids: Vec<i32> = todos.iter().map(|todo| todo.id).collect();
+    let rows = sqlx::query_as::<_, ChecklistItemFromRow>(
+        r#"
+            select id, todo_id, text, done, position from checklist_items
+            where todo_id = any($1)
+            order by todo_id, position, id
+        "#
+    )
+    .bind(ids)
+    .fetch_all(conn)
+    .await?;
+    Ok(attach_checklists(todos, rows))
+}
+
+async fn find_todo(conn: &mut PgConnection, user_id: i32, id: i32) -> anyhow::Result<TodoEntity> {
+    let items = sqlx::query_as::<_, TodoWithLabelFromRow>(
+        r#"
+            select todos.*, labels.id as label_id, labels.name as label_name,
+                labels.color as label_color, labels.description as label_description from todos
+                left outer join (
+                    todo_labels tl inner join labels on labels.id = tl.label_id and labels.user_id = $2
+                ) on todos.id = tl.todo_id
+            where todos.id=$1 and ((todos.project_id is null and todos.user_id=$2) or exists (
+                select 1 from project_members pm
+                where pm.project_id = todos.project_id and pm.user_id = $2
+            ));
+        "#
+    )
+    .bind(id)
+    .bind(user_id)
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(|e| match e {
+        sqlx::Error::RowNotFound => RepositoryError::NotFound(id),
+        _ => RepositoryError::Unexpected(e.to_string()),
+    })?;
+
+    let todos = load_checklists(conn, fold_entities(items)).await?;
+    let todo = todos.into_iter().next().ok_or(RepositoryError::NotFound(id))?;
+
+    Ok(todo)
+}
+
+// idの子孫のtodoを、指定したプロジェクトに移動し、completedの指定があればそろえる
+async fn update_descendants(
+    conn: &mut PgConnection,
+    id: i32,
+    project_id: Option<i32>,
+    completed: Option<bool>,
+) -> anyhow::Result<()> {
+    sqlx::query(
+        r#"
+            update todos set project_id = $2, completed = coalesce($3, completed)
+            where id in (
+                with recursive tree (id) as (
+                    select id from todos where parent_id = $1
+                    union all
+                    select todos.id from todos inner join tree on todos.parent_id = tree.id
+                )
+                select id from tree
+            )
+        "#
+    )
+    .bind(id)
+    .bind(project_id)
+    .bind(completed)
+    .execute(conn)
+    .await?;
+    Ok(())
 }
 
 #[async_trait]
 impl TodoRepository for TodoRepositoryForDb {
-    async fn create(&self, payload: CreateTodo) -> anyhow::Result<TodoEntity> {
-        let tx = self.pool.begin().await?;
-        let row = sqlx::query_as::<_, TodoFromRow>(
-            r#"
-                insert into todos (text, completed)
-                values ($1, false)
-                returning *
-            "#,)
-            .bind(payload.text.clone())
-            .fetch_one(&self.pool)
-            .await?;
+    async fn create(&self, user_id: i32, payload: CreateTodo) -> anyhow::Result<TodoEntity> {
+        self.conn.transaction(move |conn| Box::pin(async move {
+            ensure_labels_exist(conn, user_id, &payload.labels).await?;
+            // 親があれば、親と同じプロジェクトに入る
+            let project_id = match payload.parent_id {
+                Some(parent_id) => find_todo(conn, user_id, parent_id).await?.project_id,
+                None => payload.project_id,
+            };
 
-        sqlx::query(
-            r#"
-                insert into todo_labels (todo_id, label_id)
-                select $1, id
-                from unnest($2) as t(id)
-            "#,
-        )
-        .bind(row.id)
-        .bind(payload.labels)
-        .execute(&self.pool)
-        .await?;
+            let row = sqlx::query_as::<_, TodoFromRow>(
+                r#"
+                    insert into todos (text, completed, due_date, priority, user_id, project_id, parent_id)
+                    values ($1, false, $2, $3, $4, $5, $6)
+                    returning *
+                "#,)
+                .bind(payload.text.clone())
+                .bind(payload.due_date)
+                .bind(payload.priority)
+                .bind(user_id)
+                .bind(project_id)
+                .bind(payload.parent_id)
+                .fetch_one(&mut *conn)
+                .await?;
+
+            sqlx::query(
+                r#"
+                    insert into todo_labels (todo_id, label_id)
+                    select $1, id
+                    from unnest($2) as t(id)
+                "#,
+            )
+            .bind(row.id)
+            .bind(payload.labels)
+            .execute(&mut *conn)
+            .await?;
 
-        tx.commit().await?;
+            find_todo(conn, user_id, row.id).await
+        })).await
+    }
 
-        let todo = self.find(row.id).await?;
-        Ok(todo)
+    async fn find(&self, user_id: i32, id: i32) -> anyhow::Result<TodoEntity> {
+        self.conn.run(move |conn| Box::pin(find_todo(conn, user_id, id))).await
     }
 
-    async fn find(&self, id: i32) -> anyhow::Result<TodoEntity> {
-        let items = sqlx::query_as::<_, TodoWithLabelFromRow>(
-            r#"
-                select todos.*, labels.id as label_id, labels.name as label_name from todos
-                    left outer join todo_labels tl on todos.id = tl.todo_id
-                    left outer join labels on labels.id = tl.label_id where todos.id=$1;
-            "#
-        )
-        .bind(id)
-        .fetch_all(&self.pool)
-        .await
-        .map_err(|e| match e {
-            sqlx::Error::RowNotFound => RepositoryError::NotFound(id),
-            _ => RepositoryError::Unexpected(e.to_string()),
-        })?;
+    async fn all(&self, user_id: i32, query: TodoQuery, page: PageRequest) -> anyhow::Result<Page<TodoEntity>> {
+        // ラベルをjoinすると行数が増えるので、先にtodosだけでページを切り出してからjoinする
+        let sort = query.sort;
+        let todos = self.conn.run(move |conn| Box::pin(async move {
+            let items = sqlx::query_as::<_, TodoWithLabelFromRow>(
+                r#"
+                    select todos.*, labels.id as label_id, labels.name as label_name,
+                        labels.color as label_color, labels.description as label_description from (
+                        select * from todos
+                        where ((todos.project_id is null and todos.user_id = $11) or exists (
+                                select 1 from project_members pm
+                                where pm.project_id = todos.project_id and pm.user_id = $11
+                            ))
+                            and ($1::timestamptz is null or todos.due_date < $1)
+                            and (not $2 or (todos.due_date < now() and not todos.completed))
+                            and ($4::boolean is null or todos.completed = $4)
+                            and (cardinality($5::integer[]) = 0 or (
+                                select count(distinct filter_tl.label_id) from todo_labels filter_tl
+                                where filter_tl.todo_id = todos.id and filter_tl.label_id = any($5)
+                            ) >= (case when $6 then cardinality($5::integer[]) else 1 end))
+                            and ($7::text is null or strpos(lower(todos.text), lower($7)) > 0)
+                            and ($12::integer is null or todos.project_id = $12)
+                            and ($9::integer is null
+                                or ((case when $3 then todos.priority else 0 end), todos.id) < ($8, $9))
+                        order by (case when $3 then todos.priority else 0 end) desc, todos.id desc
+                        limit $10
+                    ) todos
+                        left outer join (
+                            todo_labels tl inner join labels on labels.id = tl.label_id and labels.user_id = $11
+                        ) on todos.id = tl.todo_id
+                    order by (case when $3 then todos.priority else 0 end) desc, todos.id desc;
+                "#
+            )
+            .bind(query.due_before)
+            .bind(query.overdue)
+            .bind(sort == TodoSort::Priority)
+            .bind(query.completed)
+            .bind(dedup_label_ids(query.labels))
+            .bind(query.label_match == LabelMatch::All)
+            .bind(query.text)
+            .bind(page.after.map(|cursor| cursor.key))
+            .bind(page.after.map(|cursor| cursor.id))
+            .bind(page.limit + 1)
+            .bind(user_id)
+            .bind(query.project_id)
+            .fetch_all(&mut *conn)
+            .await?;
+            load_checklists(conn, fold_entities(items)).await
+        })).await?;
+
+        Ok(Page::from_overfetched(
+            todos,
+            page.limit,
+            |todo| todo.cursor(sort),
+        ))
+    }
 
-        let todos = fold_entities(items);
-        let todo = todos.first().ok_or(RepositoryError::NotFound(id))?;
+    async fn children(&self, user_id: i32, id: i32) -> anyhow::Result<Vec<TodoNode>> {
+        let todos = self.conn.run(move |conn| Box::pin(async move {
+            find_todo(conn, user_id, id).await?;
+            let items = sqlx::query_as::<_, TodoWithLabelFromRow>(
+                r#"
+                    with recursive tree (id, depth) as (
+                        select id, 1 from todos where parent_id = $1
+                        union all
+                        select todos.id, tree.depth + 1 from todos inner join tree on todos.parent_id = tree.id
+                    )
+                    select todos.*, labels.id as label_id, labels.name as label_name,
+                        labels.color as label_color, labels.description as label_description from tree
+                        inner join todos on todos.id = tree.id
+                        left outer join (
+                            todo_labels tl inner join labels on labels.id = tl.label_id and labels.user_id = $2
+                        ) on todos.id = tl.todo_id
+                    order by tree.depth, todos.id;
+                "#
+            )
+            .bind(id)
+            .bind(user_id)
+            .fetch_all(&mut *conn)
+            .await?;
+            load_checklists(conn, fold_entities(items)).await
+        })).await?;
 
-        Ok(todo.clone())
+        Ok(to_nodes(id, todos))
     }
 
-    async fn all(&self) -> anyhow::Result<Vec<TodoEntity>> {
-        let items = sqlx::query_as::<_, TodoWithLabelFromRow>(
-            r#"
-                select todos.*, labels.id as label_id, labels.name as label_name from todos
-                    left outer join todo_labels tl on todos.id = tl.todo_id
-                    left outer join labels on labels.id = tl.label_id order by todos.id desc;
-            "#
-        )
-        .fetch_all(&self.pool)
-        .await?;
+    async fn update(&self, user_id: i32, id: i32, payload: UpdateTodo) -> anyhow::Result<TodoEntity> {
+        self.conn.transaction(move |conn| Box::pin(async move {
+            let old_todo = find_todo(conn, user_id, id).await?;
+            if let Some(labels) = &payload.labels {
+                ensure_labels_exist(conn, user_id, labels).await?;
+            }
+            let project_id = payload.project_id.or(old_todo.project_id);
+            // 親と別のプロジェクトに移動したtodoは、親から外れる
+            let parent_id = old_todo.parent_id.filter(|_| project_id == old_todo.project_id);
+            sqlx::query(
+                r#"
+                    update todos set text=$1, completed=$2, due_date=$3, priority=$4, project_id=$6,
+                        parent_id=$7
+                    where id=$5
+                    returning *
+                "#,
+            )
+            .bind(payload.text.unwrap_or(old_todo.text))
+            .bind(payload.completed.unwrap_or(old_todo.completed))
+            .bind(payload.due_date.unwrap_or(old_todo.due_date))
+            .bind(payload.priority.unwrap_or(old_todo.priority))
+            .bind(id)
+            .bind(project_id)
+            .bind(parent_id)
+            .fetch_one(&mut *conn)
+            .await?;
+
+            // 子孫のtodoも一緒に移動し、指定があれば完了状態もそろえる
+            let completed = payload.completed.filter(|_| payload.cascade_completed);
+            if project_id != old_todo.project_id || completed.is_some() {
+                update_descendants(conn, id, project_id, completed).await?;
+            }
 
-        Ok(fold_entities(items))
+            // 他のメンバーが付けたラベルは残し、自分のラベルだけを付け替える
+            if let Some(labels) = payload.labels {
+                sqlx::query(
+                    r#"
+                        delete from todo_labels
+                        where todo_id=$1 and label_id in (select id from labels where user_id=$2)
+                    "#
+                )
+                .bind(id)
+                .bind(user_id)
+                .execute(&mut *conn)
+                .await?;
+
+                sqlx::query(
+                    r#"
+                        insert into todo_labels (todo_id, label_id)
+                        select $1, id
+                        from unnest($2) as t(id);
+                    "#
+                )
+                .bind(id)
+                .bind(labels)
+                .execute(&mut *conn)
+                .await?;
+            };
+
+            find_todo(conn, user_id, id).await
+        })).await
     }
 
-    async fn update(&self, id: i32, payload: UpdateTodo) -> anyhow::Result<TodoEntity> {
-        let tx = self.pool.begin().await?;
+    async fn delete(&self, user_id: i32, id: i32) -> anyhow::Result<()> {
+        self.conn.transaction(move |conn| Box::pin(async move {
+            // 見えないtodoはNotFoundにする
+            find_todo(conn, user_id, id).await?;
 
-        let old_todo = self.find(id).await?;
-        sqlx::query(
-            r#"
-                update todos set text=$1, completed=$2
-                where id=$3
-                returning *
-            "#,
-        )
-        .bind(payload.text.unwrap_or(old_todo.text))
-        .bind(payload.completed.unwrap_or(old_todo.completed))
-        .bind(id)
-        .fetch_one(&self.pool)
-        .await?;
+            // 子孫のtodoもまとめて削除する
+            // todoラベルの削除
+            sqlx::query(
+                r#"
+                    delete from todo_labels where todo_id in (
+                        with recursive tree (id) as (
+                            select id from todos where id = $1
+                            union all
+                            select todos.id from todos inner join tree on todos.parent_id = tree.id
+                        )
+                        select id from tree
+                    )
+                "#
+            )
+            .bind(id)
+            .execute(&mut *conn)
+            .await
+            .map_err(|e| RepositoryError::Unexpected(e.to_string()))?;
 
-        if let Some(labels) = payload.labels {
+            // todoの削除
             sqlx::query(
                 r#"
-                    delete from todo_labels where todo_id=$1
+                    delete from todos where id in (
+                        with recursive tree (id) as (
+                            select id from todos where id = $1
+                            union all
+                            select todos.id from todos inner join tree on todos.parent_id = tree.id
+                        )
+                        select id from tree
+                    )
                 "#
             )
             .bind(id)
-            .execute(&self.pool)
-            .await?;
+            .execute(&mut *conn)
+            .await
+            .map_err(|e| RepositoryError::Unexpected(e.to_string()))?;
+
+            Ok(())
+        })).await
+    }
 
+    async fn add_checklist_item(&self, user_id: i32, id: i32, payload: CreateChecklistItem) -> anyhow::Result<TodoEntity> {
+        self.conn.transaction(move |conn| Box::pin(async move {
+            find_todo(conn, user_id, id).await?;
+            // 新しい項目は末尾に追加する
             sqlx::query(
                 r#"
-                    insert into todo_labels (todo_id, label_id)
-                    select $1, id
-                    from unnest($2) as t(id);
+                    insert into checklist_items (todo_id, text, position)
+                    select $1, $2, coalesce(max(position), 0) + 1 from checklist_items where todo_id = $1
                 "#
             )
             .bind(id)
-            .bind(labels)
-            .execute(&self.pool)
+            .bind(payload.text)
+            .execute(&mut *conn)
             .await?;
-        };
-
-        tx.commit().await?;
-        let todo = self.find(id).await?;
 
-        Ok(todo)
+            find_todo(conn, user_id, id).await
+        })).await
     }
 
-    async fn delete(&self, id: i32) -> anyhow::Result<()> {
-        let tx = self.pool.begin().await?;
+    async fn update_checklist_item(
+        &self,
+        user_id: i32,
+        id: i32,
+        item_id: i32,
+        payload: UpdateChecklistItem,
+    ) -> anyhow::Result<TodoEntity> {
+        self.conn.transaction(move |conn| Box::pin(async move {
+            find_todo(conn, user_id, id).await?;
+            let res = sqlx::query(
+                r#"
+                    update checklist_items set text = coalesce($3, text), done = coalesce($4, done)
+                    where todo_id = $1 and id = $2
+                "#
+            )
+            .bind(id)
+            .bind(item_id)
+            .bind(payload.text)
+            .bind(payload.done)
+            .execute(&mut *conn)
+            .await?;
+            if res.rows_affected() == 0 {
+                return Err(RepositoryError::NotFound(item_id).into());
+            }
 
-        // todoラベルの削除
-        sqlx::query(
-            r#"
-                delete from todo_labels where todo_id=$1
-            "#
-        )
-        .bind(id)
-        .execute(&self.pool)
-        .await
-        .map_err(|e| match e {
-            sqlx::Error::RowNotFound => RepositoryError::NotFound(id),
-            _ => RepositoryError::Unexpected(e.to_string()),
-        })?;
+            find_todo(conn, user_id, id).await
+        })).await
+    }
 
-        // todoの削除
-        sqlx::query(
-            r#"
-                delete from todos where id=$1
-            "#
-        )
-        .bind(id)
-        .execute(&self.pool)
-        .await
-        .map_err(|e| match e {
-            sqlx::Error::RowNotFound => RepositoryError::NotFound(id),
-            _ => RepositoryError::Unexpected(e.to_string()),
-        })?;
+    async fn reorder_checklist(&self, user_id: i32, id: i32, item_ids: Vec<i32>) -> anyhow::Result<TodoEntity> {
+        self.conn.transaction(move |conn| Box::pin(async move {
+            let todo = find_todo(conn, user_id, id).await?;
+            ensure_same_checklist(id, &todo.checklist, &item_ids)?;
+            sqlx::query(
+                r#"
+                    update checklist_items set position = t.position
+                    from unnest($2::integer[]) with ordinality as t(id, position)
+                    where checklist_items.todo_id = $1 and checklist_items.id = t.id
+                "#
+            )
+            .bind(id)
+            .bind(item_ids)
+            .execute(&mut *conn)
+            .await?;
+
+            find_todo(conn, user_id, id).await
+        })).await
+    }
 
-        tx.commit().await?;
+    async fn delete_checklist_item(&self, user_id: i32, id: i32, item_id: i32) -> anyhow::Result<TodoEntity> {
+        self.conn.transaction(move |conn| Box::pin(async move {
+            find_todo(conn, user_id, id).await?;
+            let res = sqlx::query(
+                r#"
+                    delete from checklist_items where todo_id = $1 and id = $2
+                "#
+            )
+            .bind(id)
+            .bind(item_id)
+            .execute(&mut *conn)
+            .await?;
+            if res.rows_affected() == 0 {
+                return Err(RepositoryError::NotFound(item_id).into());
+            }
 
-        Ok(())
+            find_todo(conn, user_id, id).await
+        })).await
     }
 }
 
@@ -273,97 +821,152 @@ impl TodoRepository for TodoRepositoryForDb {
 #[cfg(feature = "database-test")]
 mod test {
     use super::*;
+    use crate::repositories::test_utils::postgres_user;
     use dotenv::dotenv;
     use sqlx::PgPool;
     use std::env;
 
+    #[tokio::test]
+    async fn checklist_reorder_scenario() {
+        dotenv().ok();
+        let database_url = &env::var("DATABASE_URL").expect("undefined [DATABASE_URL]");
+        let pool = PgPool::connect(database_url)
+            .await
+            .unwrap_or_else(|_| panic!("fail connect database, url is [{}]", database_url));
+        let user_id = postgres_user(&pool, "checklist_reorder_scenario").await;
+
+        test_utils::checklist_reorder_scenario(TodoRepositoryForDb::new(pool), user_id).await;
+    }
+
     #[tokio::test]
     async fn crud_scenario() {
         dotenv().ok();
         let database_url = &env::var("DATABASE_URL").expect("undefined [DATABASE_URL]");
         let pool = PgPool::connect(database_url)
             .await
-            .expect(&format!("fail connect database, url is [{}]", database_url));
+            .unwrap_or_else(|_| panic!("fail connect database, url is [{}]", database_url));
+        let user_id = postgres_user(&pool, "crud_scenario").await;
 
         // ラベルデータの準備
-        let label_name = String::from("test label");
-        let optional_label = sqlx::query_as::<_, Label>(
+        // ユーザーは毎回新しく作るので、同じ名前のラベルがあっても重複しない
+        let label_1 = sqlx::query_as::<_, Label>(
             r#"
-                select * from labels where name = $1
-            "#
+                insert into labels ( name, user_id )
+                values ( $1, $2 )
+                returning *
+            "#,
         )
-        .bind(label_name.clone())
-        .fetch_optional(&pool)
+        .bind("test label")
+        .bind(user_id)
+        .fetch_one(&pool)
         .await
-        .expect("Failed to prepare label data.");
-        let label_1 = if let Some(label) = optional_label {
-            label
-        } else {
-            let label = sqlx::query_as::<_, Label>(
-                r#"
-                    insert into labels ( name )
-                    values ( $1 )
-                    returning *
-                "#,
-            )
-            .bind(label_name)
-            .fetch_one(&pool)
-            .await
-            .expect("Failed to insert label data.");
-            label
-        };
+        .expect("Failed to insert label data.");
 
         let repository = TodoRepositoryForDb::new(pool.clone());
         let todo_text = "[crud_scenario] text";
 
         // createのテスト
         let created = repository
-            .create(CreateTodo::new(todo_text.to_string(), vec![label_1.id]))
+            .create(user_id, CreateTodo::new(todo_text.to_string(), vec![label_1.id]))
             .await
             .expect("[create] returned Err");
         assert_eq!(created.text, todo_text);
         assert!(!created.completed);
         assert_eq!(*created.labels.first().unwrap(), label_1);
 
+        // 存在しないラベルは拒否される
+        let res = repository
+            .create(user_id, CreateTodo::new(todo_text.to_string(), vec![label_1.id, -1]))
+            .await;
+        assert!(matches!(
+            res.unwrap_err().downcast_ref::<RepositoryError>(),
+            Some(RepositoryError::LabelNotFound(ids)) if *ids == vec![-1]
+        ));
+
         // findのテスト
         let todo = repository
-            .find(created.id)
+            .find(user_id, created.id)
             .await
             .expect("[find] returned Err");
         assert_eq!(created, todo);
 
         // allのテスト
         let todos = repository
-            .all()
+            .all(user_id, TodoQuery::default(), PageRequest::default())
             .await
-            .expect("[all] returned Err");
+            .expect("[all] returned Err")
+            .items;
         let todo = todos.first().unwrap();
         assert_eq!(created, *todo);
 
+        // 他のユーザーからは見えず、削除もできない
+        let other_id = postgres_user(&pool, "crud_scenario_other").await;
+        let res = repository.find(other_id, created.id).await;
+        assert!(matches!(
+            res.unwrap_err().downcast_ref::<RepositoryError>(),
+            Some(RepositoryError::NotFound(_))
+        ));
+        let todos = repository
+            .all(other_id, TodoQuery::default(), PageRequest::default())
+            .await
+            .expect("[all] returned Err")
+            .items;
+        assert!(todos.is_empty());
+        assert!(repository.delete(other_id, created.id).await.is_err());
+
+        // allの絞り込みのテスト
+        let todos = repository
+            .all(user_id, TodoQuery {
+                completed: Some(false),
+                labels: vec![label_1.id, label_1.id],
+                label_match: LabelMatch::All,
+                text: Some("CRUD_SCENARIO".to_string()),
+                ..TodoQuery::default()
+            }, PageRequest::default())
+            .await
+            .expect("[all] returned Err")
+            .items;
+        assert!(todos.contains(&created));
+        let todos = repository
+            .all(user_id, TodoQuery {
+                completed: Some(true),
+                ..TodoQuery::default()
+            }, PageRequest::default())
+            .await
+            .expect("[all] returned Err")
+            .items;
+        assert!(!todos.contains(&created));
+
         // updateのテスト
         let updated_text = "[crud_scenario] updated text";
         let todo = repository
             .update(
+                user_id,
                 todo.id,
                 UpdateTodo {
                     text: Some(updated_text.to_string()),
                     completed: Some(true),
                     labels: Some(vec![]),
+                    due_date: None,
+                    priority: Some(Priority::High),
+                    project_id: None,
+                    cascade_completed: false,
                 }
             )
             .await
             .expect("[update] returned Err");
         assert_eq!(created.id, todo.id);
         assert_eq!(todo.text, updated_text);
-        assert!(todo.labels.len() == 0);
+        assert_eq!(todo.priority, Priority::High);
+        assert!(todo.labels.is_empty());
 
         // deleteのテスト
-        let _ = repository
-            .delete(todo.id)
+        repository
+            .delete(user_id, todo.id)
             .await
             .expect("[delete] returned Err");
         let res = repository
-            .find(created.id)
+            .find(user_id, created.id)
             .await;
         assert!(res.is_err());
 
@@ -375,7 +978,7 @@ mod test {
             .fetch_all(&pool)
             .await
             .expect("[delete] todo_labels fetch error");
-        assert!(todo_rows.len() == 0);
+        assert!(todo_rows.is_empty());
 
         let rows = sqlx::query(
             r#"
@@ -386,40 +989,52 @@ mod test {
         .fetch_all(&pool)
         .await
         .expect("[delete] todo_labels fetch error");
-        assert!(rows.len() == 0);
+        assert!(rows.is_empty());
     }
 
     #[test]
     fn fold_entities_test() {
-        let label_1 = Label {
-            id: 1,
-            name: String::from("label 1"),
-        };
-        let label_2 = Label {
-            id: 2,
-            name: String::from("label 2")
-        };
+        let label_1 = Label::new(1, String::from("label 1"));
+        let label_2 = Label::new(2, String::from("label 2"));
         let rows = vec![
             TodoWithLabelFromRow {
                 id: 1,
                 text: String::from("todo 1"),
                 completed: false,
+                due_date: None,
+                priority: Priority::None,
+                project_id: None,
+                parent_id: None,
                 label_id: Some(label_1.id),
                 label_name: Some(label_1.name.clone()),
+                label_color: None,
+                label_description: None,
             },
             TodoWithLabelFromRow {
                 id: 1,
                 text: String::from("todo 1"),
                 completed: false,
+                due_date: None,
+                priority: Priority::None,
+                project_id: None,
+                parent_id: None,
                 label_id: Some(label_2.id),
                 label_name: Some(label_2.name.clone()),
+                label_color: None,
+                label_description: None,
             },
             TodoWithLabelFromRow {
                 id: 2,
                 text: String::from("todo 2"),
                 completed: false,
+                due_date: None,
+                priority: Priority::None,
+                project_id: None,
+                parent_id: None,
                 label_id: Some(label_1.id),
                 label_name: Some(label_1.name.clone()),
+                label_color: None,
+                label_description: None,
             }
         ];
         let res = fold_entities(rows);
@@ -430,13 +1045,25 @@ mod test {
                     id: 1,
                     text: String::from("todo 1"),
                     completed: false,
+                    due_date: None,
+                    priority: Priority::None,
+                    project_id: None,
+                    parent_id: None,
                     labels: vec![label_1.clone(), label_2.clone()],
+                    checklist: vec![],
+                    progress: None,
                 },
                 TodoEntity {
                     id: 2,
                     text: String::from("todo 2"),
                     completed: false,
+                    due_date: None,
+                    priority: Priority::None,
+                    project_id: None,
+                    parent_id: None,
                     labels: vec![label_1.clone()],
+                    checklist: vec![],
+                    progress: None,
                 }
             ]
         );
@@ -445,13 +1072,6 @@ mod test {
 
 #[cfg(test)]
 pub mod test_utils {
-    use anyhow::Context;
-    use axum::async_trait;
-    use std::{
-        collections::HashMap,
-        sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard},
-    };
-
     use super::*;
 
     impl TodoEntity {
@@ -460,176 +1080,79 @@ pub mod test_utils {
                 id,
                 text,
                 completed: false,
+                due_date: None,
+                priority: Priority::None,
+                project_id: None,
+                parent_id: None,
                 labels,
+                checklist: vec![],
+                progress: None,
             }
         }
     }
 
     impl CreateTodo {
         pub fn new(text: String, labels: Vec<i32>) -> Self {
-            Self { text, labels }
-        }
-    }
-
-    // 仮想のデータベース（実態はHashMap）
-    // テストのみで使う
-    // RepositoryForMemoryでの各操作を定義する
-    // このレポジトリはhttpハンドラーで使われる
-
-    // とりあえずTODOデータをHashMapに保存する
-    type TodoDatas = HashMap<i32, TodoEntity>;
-
-    #[derive(Debug, Clone)]
-    pub struct TodoRepositoryForMemory {
-        store: Arc<RwLock<TodoDatas>>,
-        labels: Vec<Label>,
-    }
-
-    impl TodoRepositoryForMemory {
-        pub fn new(labels: Vec<Label>) -> Self {
-            TodoRepositoryForMemory {
-                store: Arc::default(),
+            Self {
+                text,
                 labels,
+                due_date: None,
+                priority: Priority::None,
+                project_id: None,
+                parent_id: None,
             }
         }
-
-        fn write_store_ref(&self) -> RwLockWriteGuard<TodoDatas> {
-            self.store.write().unwrap()
-        }
-
-        fn read_store_ref(&self) -> RwLockReadGuard<TodoDatas> {
-            self.store.read().unwrap()
-        }
-
-        // idのベクトルからLabelのベクトルに変換する
-        fn resolve_labels(&self, labels: Vec<i32>) -> Vec<Label> {
-            let mut label_list = self.labels.iter().cloned();
-            let labels = labels
-                .iter()
-                .map(|id| label_list.find(|label| label.id == *id).unwrap())
-                .collect();
-            labels
-        }
     }
 
-    #[async_trait]
-    impl TodoRepository for TodoRepositoryForMemory {
-        async fn create(&self, payload: CreateTodo) -> anyhow::Result<TodoEntity> {
-            let mut store = self.write_store_ref();
-            let id = (store.len() + 1) as i32;
-            let labels = self.resolve_labels(payload.labels);
-            let todo = TodoEntity::new(id, payload.text.clone(), labels);
-            store.insert(id, todo.clone());
-            Ok(todo)
-        }
-
-        async fn find(&self, id: i32) -> anyhow::Result<TodoEntity> {
-            let store = self.read_store_ref();
-            let todo = store
-                .get(&id)
-                .map(|todo| todo.clone())
-                .ok_or(RepositoryError::NotFound(id))?;
-            Ok(todo)
-        }
-
-        async fn all(&self) -> anyhow::Result<Vec<TodoEntity>> {
-            let store = self.read_store_ref();
-            Ok(Vec::from_iter(store.values().map(|todo| todo.clone())))
-        }
-
-        async fn update(&self, id: i32, payload: UpdateTodo) -> anyhow::Result<TodoEntity> {
-            let mut store = self.write_store_ref();
-            let todo = store.get(&id).context(RepositoryError::NotFound(id))?;
-            let text = payload.text.unwrap_or(todo.text.clone());
-            let completed = payload.completed.unwrap_or(todo.completed);
-            let labels = match payload.labels {
-                Some(label_ids) => self.resolve_labels(label_ids),
-                _ => todo.labels.clone(),
-            };
-            let todo = TodoEntity {
-                id,
-                text,
-                completed,
-                labels,
-            };
-            store.insert(id, todo.clone());
-            Ok(todo)
+    impl CreateChecklistItem {
+        pub fn new(text: String) -> Self {
+            Self { text }
         }
+    }
 
-        async fn delete(&self, id: i32) -> anyhow::Result<()> {
-            let mut store = self.write_store_ref();
-            store.remove(&id).ok_or(RepositoryError::NotFound(id))?;
-            Ok(())
+    impl UpdateChecklistItem {
+        pub fn done(done: bool) -> Self {
+            Self { text: None, done: Some(done) }
         }
     }
 
-    mod test {
-        use super::*;
-
-        #[tokio::test]
-        async fn todo_crud_scenario() {
-            let text = "todo text".to_string();
-            let id = 1;
-            let label_data = Label {
-                id: 1,
-                name: String::from("test label"),
-            };
-            let labels = vec![label_data.clone()];
-            let expected = TodoEntity {
-                id,
-                text: text.clone(),
-                completed: false,
-                labels: labels.clone(),
-            };
-
-            // create
-            let label_data = Label {
-                id: 1,
-                name: String::from("test label"),
-            };
-            let labels = vec![label_data.clone()];
-
-            let repository = TodoRepositoryForMemory::new(labels.clone());
-            let todo = repository
-                .create(CreateTodo::new(text, vec![label_data.id]))
-                .await
-                .expect("failed create todo");
-            assert_eq!(expected, todo);
-
-            // find
-            let todo = repository.find(todo.id).await.unwrap();
-            assert_eq!(expected, todo);
-
-            // all
-            let todo = repository.all().await.expect("failed get all todo");
-            assert_eq!(vec![expected], todo);
-
-            // update
-            let text = "update todo text".to_string();
-            let todo = repository
-                .update(
-                    1,
-                    UpdateTodo {
-                        text: Some(text.clone()),
-                        completed: Some(true),
-                        labels: Some(vec![])
-                    },
-                )
+    // チェックリストの並び順がどのバックエンドでも同じになることを確認するシナリオ
+    // user_idは作成済みのユーザー
+    pub async fn checklist_reorder_scenario<R: TodoRepository>(repository: R, user_id: i32) {
+        let mut todo = repository
+            .create(user_id, CreateTodo::new("[checklist_reorder_scenario] todo".to_string(), vec![]))
+            .await
+            .expect("[create] returned Err");
+        for text in ["first", "second", "third"] {
+            todo = repository
+                .add_checklist_item(user_id, todo.id, CreateChecklistItem::new(text.to_string()))
                 .await
-                .expect("failed update todo");
-            assert_eq!(
-                TodoEntity {
-                    id,
-                    text,
-                    completed: true,
-                    labels: vec![],
-                },
-                todo,
-            );
+                .expect("[add_checklist_item] returned Err");
+        }
+        let positions: Vec<i32> = todo.checklist.iter().map(|item| item.position).collect();
+        assert_eq!(positions, vec![1, 2, 3]);
+        let ids: Vec<i32> = todo.checklist.iter().map(|item| item.id).collect();
+        let (first, second, third) = (ids[0], ids[1], ids[2]);
 
-            // delete
-            let res = repository.delete(id).await;
-            assert!(res.is_ok());
+        // 並び替えた後も1から順に振り直される
+        let todo = repository
+            .reorder_checklist(user_id, todo.id, vec![third, first, second])
+            .await
+            .expect("[reorder_checklist] returned Err");
+        let items: Vec<(i32, i32)> = todo.checklist.iter().map(|item| (item.id, item.position)).collect();
+        assert_eq!(items, vec![(third, 1), (first, 2), (second, 3)]);
+        let found = repository.find(user_id, todo.id).await.expect("[find] returned Err");
+        assert_eq!(found, todo);
+
+        // 項目が足りない、重複している、他の項目を含む場合はエラーになり、並び順も変わらない
+        for item_ids in [vec![first, second], vec![first, first, second, third], vec![first, second, third, -1]] {
+            let res = repository.reorder_checklist(user_id, todo.id, item_ids).await;
+            assert!(matches!(
+                res.unwrap_err().downcast_ref::<RepositoryError>(),
+                Some(RepositoryError::ChecklistMismatch(id)) if *id == todo.id
+            ));
         }
+        let found = repository.find(user_id, todo.id).await.expect("[find] returned Err");
+        assert_eq!(found, todo);
     }
 }
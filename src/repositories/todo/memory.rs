@@ -0,0 +1,698 @@
+use axum::async_trait;
+use chrono::Utc;
+
+use super::{
+    ensure_same_checklist, to_nodes, ChecklistItem, CreateChecklistItem, CreateTodo, LabelMatch, TodoEntity, TodoNode,
+    TodoQuery, TodoRepository, UpdateChecklistItem, UpdateTodo,
+};
+use crate::repositories::{
+    label::Label,
+    memory::{MemoryStore, Tables, TodoRecord},
+    Page, PageRequest, RepositoryError,
+};
+
+// メモリ上にデータを保存するレポジトリ
+// ラベルはLabelRepositoryForMemoryと同じストアから解決する
+#[derive(Debug, Clone)]
+pub struct TodoRepositoryForMemory {
+    store: MemoryStore,
+}
+
+impl TodoRepositoryForMemory {
+    pub fn new(store: MemoryStore) -> Self {
+        TodoRepositoryForMemory { store }
+    }
+}
+
+// 存在しない（または他のユーザーの）idがあれば、DBのレポジトリと同じくLabelNotFoundを返す
+fn ensure_labels_exist(tables: &Tables, user_id: i32, labels: &[i32]) -> anyhow::Result<()> {
+    let mut missing: Vec<i32> = labels
+        .iter()
+        .filter(|id| tables.labels.get(id).is_none_or(|record| record.user_id != user_id))
+        .cloned()
+        .collect();
+    if !missing.is_empty() {
+        missing.sort_unstable();
+        missing.dedup();
+        return Err(RepositoryError::LabelNotFound(missing).into());
+    }
+    Ok(())
+}
+
+// DBと同じく、自分のプロジェクト外のtodoと、メンバーになっているプロジェクトのtodoが見える
+fn is_visible(tables: &Tables, user_id: i32, todo: &TodoRecord) -> bool {
+    match todo.project_id {
+        Some(project_id) => tables.project_role(user_id, project_id).is_some(),
+        None => todo.user_id == user_id,
+    }
+}
+
+// 見えないtodoは、存在しないものとして扱う
+fn find_record(tables: &Tables, user_id: i32, id: i32) -> anyhow::Result<&TodoRecord> {
+    let record = tables
+        .todos
+        .get(&id)
+        .filter(|todo| is_visible(tables, user_id, todo))
+        .ok_or(RepositoryError::NotFound(id))?;
+    Ok(record)
+}
+
+// DBの再帰クエリと同じく、idの子孫のidを浅い順（同じ深さの中ではid順）に返す
+fn descendants(tables: &Tables, id: i32) -> Vec<i32> {
+    let mut ids = vec![];
+    let mut parents = vec![id];
+    while !parents.is_empty() {
+        let mut children: Vec<i32> = tables
+            .todos
+            .values()
+            .filter(|todo| todo.parent_id.is_some_and(|parent_id| parents.contains(&parent_id)))
+            .map(|todo| todo.id)
+            .collect();
+        children.sort_unstable();
+        ids.extend(&children);
+        parents = children;
+    }
+    ids
+}
+
+// ラベルはユーザーごとのものなので、共有されたtodoでも自分のラベルだけを含める
+fn to_entity(tables: &Tables, user_id: i32, record: &TodoRecord) -> TodoEntity {
+    let labels: Vec<Label> = record
+        .labels
+        .iter()
+        .filter_map(|id| tables.labels.get(id))
+        .filter(|label| label.user_id == user_id)
+        .map(|label| label.label.clone())
+        .collect();
+    TodoEntity {
+        id: record.id,
+        text: record.text.clone(),
+        completed: record.completed,
+        due_date: record.due_date,
+        priority: record.priority,
+        project_id: record.project_id,
+        parent_id: record.parent_id,
+        labels,
+        checklist: vec![],
+        progress: None,
+    }
+    .with_checklist(record.checklist.clone())
+}
+
+// 見えるtodoのレコードを変更し、変更後のtodoを返す
+fn update_record(
+    tables: &mut Tables,
+    user_id: i32,
+    id: i32,
+    f: impl FnOnce(&mut TodoRecord) -> anyhow::Result<()>,
+) -> anyhow::Result<TodoEntity> {
+    find_record(tables, user_id, id)?;
+    let record = tables.todos.get_mut(&id).ok_or(RepositoryError::NotFound(id))?;
+    f(record)?;
+    let record = record.clone();
+    Ok(to_entity(tables, user_id, &record))
+}
+
+fn find_checklist_item(record: &mut TodoRecord, item_id: i32) -> anyhow::Result<&mut ChecklistItem> {
+    let item = record
+        .checklist
+        .iter_mut()
+        .find(|item| item.id == item_id)
+        .ok_or(RepositoryError::NotFound(item_id))?;
+    Ok(item)
+}
+
+#[async_trait]
+impl TodoRepository for TodoRepositoryForMemory {
+    async fn create(&self, user_id: i32, payload: CreateTodo) -> anyhow::Result<TodoEntity> {
+        let mut tables = self.store.write();
+        ensure_labels_exist(&tables, user_id, &payload.labels)?;
+        // 親があれば、親と同じプロジェクトに入る
+        let project_id = match payload.parent_id {
+            Some(parent_id) => find_record(&tables, user_id, parent_id)?.project_id,
+            None => payload.project_id,
+        };
+        let record = TodoRecord {
+            id: self.store.next_todo_id(),
+            user_id,
+            project_id,
+            parent_id: payload.parent_id,
+            text: payload.text,
+            completed: false,
+            due_date: payload.due_date,
+            priority: payload.priority,
+            labels: payload.labels,
+            checklist: vec![],
+        };
+        tables.todos.insert(record.id, record.clone());
+        Ok(to_entity(&tables, user_id, &record))
+    }
+
+    async fn find(&self, user_id: i32, id: i32) -> anyhow::Result<TodoEntity> {
+        let tables = self.store.read();
+        let record = find_record(&tables, user_id, id)?;
+        Ok(to_entity(&tables, user_id, record))
+    }
+
+    async fn all(&self, user_id: i32, query: TodoQuery, page: PageRequest) -> anyhow::Result<Page<TodoEntity>> {
+        let tables = self.store.read();
+        let now = Utc::now();
+        let mut todos = Vec::from_iter(
+            tables
+                .todos
+                .values()
+                .filter(|todo| is_visible(&tables, user_id, todo))
+                .filter(|todo| match query.due_before {
+                    Some(due_before) => todo.due_date.is_some_and(|due| due < due_before),
+                    None => true,
+                })
+                .filter(|todo| {
+                    !query.overdue
+                        || (!todo.completed && todo.due_date.is_some_and(|due| due < now))
+                })
+                .filter(|todo| query.completed.is_none_or(|completed| todo.completed == completed))
+                .filter(|todo| {
+                    let has_label = |id: &i32| todo.labels.contains(id);
+                    match query.label_match {
+                        _ if query.labels.is_empty() => true,
+                        LabelMatch::Any => query.labels.iter().any(has_label),
+                        LabelMatch::All => query.labels.iter().all(has_label),
+                    }
+                })
+                .filter(|todo| match &query.text {
+                    Some(text) => todo.text.to_lowercase().contains(&text.to_lowercase()),
+                    None => true,
+                })
+                .filter(|todo| query.project_id.is_none() || todo.project_id == query.project_id)
+                .map(|todo| to_entity(&tables, user_id, todo))
+                .filter(|todo| match page.after {
+                    Some(after) => {
+                        let cursor = todo.cursor(query.sort);
+                        (cursor.key, cursor.id) < (after.key, after.id)
+                    }
+                    None => true,
+                }),
+        );
+        // DBと同じく、id（とsortの指定があれば優先度）の降順に並べる
+        todos.sort_by_key(|todo| {
+            let cursor = todo.cursor(query.sort);
+            std::cmp::Reverse((cursor.key, cursor.id))
+        });
+        todos.truncate(page.limit as usize + 1);
+        Ok(Page::from_overfetched(todos, page.limit, |todo| todo.cursor(query.sort)))
+    }
+
+    async fn children(&self, user_id: i32, id: i32) -> anyhow::Result<Vec<TodoNode>> {
+        let tables = self.store.read();
+        find_record(&tables, user_id, id)?;
+        let todos = descendants(&tables, id)
+            .into_iter()
+            .map(|child_id| to_entity(&tables, user_id, &tables.todos[&child_id]))
+            .collect();
+        Ok(to_nodes(id, todos))
+    }
+
+    async fn update(&self, user_id: i32, id: i32, payload: UpdateTodo) -> anyhow::Result<TodoEntity> {
+        let mut tables = self.store.write();
+        let old_project_id = find_record(&tables, user_id, id)?.project_id;
+        if let Some(labels) = &payload.labels {
+            ensure_labels_exist(&tables, user_id, labels)?;
+        }
+        // 他のメンバーが付けたラベルは残し、自分のラベルだけを付け替える
+        let labels = payload.labels.map(|labels| {
+            let mut others: Vec<i32> = tables.todos[&id]
+                .labels
+                .iter()
+                .filter(|label_id| tables.labels.get(label_id).is_some_and(|label| label.user_id != user_id))
+                .cloned()
+                .collect();
+            others.extend(labels);
+            others
+        });
+        let todo = tables.todos.get_mut(&id).ok_or(RepositoryError::NotFound(id))?;
+        if let Some(text) = payload.text {
+            todo.text = text;
+        }
+        if let Some(completed) = payload.completed {
+            todo.completed = completed;
+        }
+        if let Some(due_date) = payload.due_date {
+            todo.due_date = due_date;
+        }
+        if let Some(priority) = payload.priority {
+            todo.priority = priority;
+        }
+        if let Some(project_id) = payload.project_id {
+            todo.project_id = Some(project_id);
+        }
+        // 親と別のプロジェクトに移動したtodoは、親から外れる
+        if todo.project_id != old_project_id {
+            todo.parent_id = None;
+        }
+        if let Some(labels) = labels {
+            todo.labels = labels;
+        }
+        let record = todo.clone();
+
+        // 子孫のtodoも一緒に移動し、指定があれば完了状態もそろえる
+        let completed = payload.completed.filter(|_| payload.cascade_completed);
+        for child_id in descendants(&tables, id) {
+            let child = tables.todos.get_mut(&child_id).ok_or(RepositoryError::NotFound(child_id))?;
+            child.project_id = record.project_id;
+            if let Some(completed) = completed {
+                child.completed = completed;
+            }
+        }
+        Ok(to_entity(&tables, user_id, &record))
+    }
+
+    async fn delete(&self, user_id: i32, id: i32) -> anyhow::Result<()> {
+        let mut tables = self.store.write();
+        find_record(&tables, user_id, id)?;
+        // 子孫のtodoもまとめて削除する
+        for child_id in descendants(&tables, id) {
+            tables.todos.remove(&child_id);
+        }
+        tables.todos.remove(&id);
+        Ok(())
+    }
+
+    async fn add_checklist_item(&self, user_id: i32, id: i32, payload: CreateChecklistItem) -> anyhow::Result<TodoEntity> {
+        let item_id = self.store.next_checklist_item_id();
+        update_record(&mut self.store.write(), user_id, id, |record| {
+            // DBのレポジトリと同じく、末尾の項目のposition + 1にする
+            let position = record.checklist.iter().map(|item| item.position).max().unwrap_or(0) + 1;
+            record.checklist.push(ChecklistItem {
+                id: item_id,
+                text: payload.text,
+                done: false,
+                position,
+            });
+            Ok(())
+        })
+    }
+
+    async fn update_checklist_item(
+        &self,
+        user_id: i32,
+        id: i32,
+        item_id: i32,
+        payload: UpdateChecklistItem,
+    ) -> anyhow::Result<TodoEntity> {
+        update_record(&mut self.store.write(), user_id, id, |record| {
+            let item = find_checklist_item(record, item_id)?;
+            if let Some(text) = payload.text {
+                item.text = text;
+            }
+            if let Some(done) = payload.done {
+                item.done = done;
+            }
+            Ok(())
+        })
+    }
+
+    async fn reorder_checklist(&self, user_id: i32, id: i32, item_ids: Vec<i32>) -> anyhow::Result<TodoEntity> {
+        update_record(&mut self.store.write(), user_id, id, |record| {
+            ensure_same_checklist(id, &record.checklist, &item_ids)?;
+            for item in record.checklist.iter_mut() {
+                item.position = item_ids.iter().position(|item_id| *item_id == item.id).unwrap_or_default() as i32 + 1;
+            }
+            record.checklist.sort_by_key(|item| item.position);
+            Ok(())
+        })
+    }
+
+    async fn delete_checklist_item(&self, user_id: i32, id: i32, item_id: i32) -> anyhow::Result<TodoEntity> {
+        update_record(&mut self.store.write(), user_id, id, |record| {
+            find_checklist_item(record, item_id)?;
+            record.checklist.retain(|item| item.id != item_id);
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::repositories::{
+        label::{CreateLabel, LabelRepository, LabelRepositoryForMemory, UpdateLabel},
+        todo::{self, Priority, TodoSort},
+    };
+
+    #[tokio::test]
+    async fn todo_crud_scenario() {
+        let user_id = 1;
+        let store = MemoryStore::new();
+        let label_data = LabelRepositoryForMemory::new(store.clone())
+            .create(user_id, CreateLabel::new("test label".to_string()))
+            .await
+            .expect("failed create label");
+        let text = "todo text".to_string();
+        let id = 1;
+        let expected = TodoEntity::new(id, text.clone(), vec![label_data.clone()]);
+
+        // create
+        let repository = TodoRepositoryForMemory::new(store);
+        let todo = repository
+            .create(user_id, CreateTodo::new(text, vec![label_data.id]))
+            .await
+            .expect("failed create todo");
+        assert_eq!(expected, todo);
+
+        // find
+        let todo = repository.find(user_id, todo.id).await.unwrap();
+        assert_eq!(expected, todo);
+
+        // all
+        let todo = repository
+            .all(user_id, TodoQuery::default(), PageRequest::default())
+            .await
+            .expect("failed get all todo")
+            .items;
+        assert_eq!(vec![expected], todo);
+
+        // update
+        let text = "update todo text".to_string();
+        let todo = repository
+            .update(
+                user_id,
+                1,
+                UpdateTodo {
+                    text: Some(text.clone()),
+                    completed: Some(true),
+                    labels: Some(vec![]),
+                    due_date: None,
+                    priority: None,
+                    project_id: None,
+                    cascade_completed: false,
+                },
+            )
+            .await
+            .expect("failed update todo");
+        assert_eq!(
+            TodoEntity {
+                completed: true,
+                ..TodoEntity::new(id, text, vec![])
+            },
+            todo,
+        );
+
+        // delete
+        let res = repository.delete(user_id, id).await;
+        assert!(res.is_ok());
+
+        // 削除後に作ったtodoに同じidは使われない
+        let todo = repository
+            .create(user_id, CreateTodo::new("another todo".to_string(), vec![]))
+            .await
+            .expect("failed create todo");
+        assert_eq!(todo.id, 2);
+    }
+
+    #[tokio::test]
+    async fn todo_label_resolution() {
+        let user_id = 1;
+        let store = MemoryStore::new();
+        let labels = LabelRepositoryForMemory::new(store.clone());
+        let repository = TodoRepositoryForMemory::new(store);
+        let label = labels
+            .create(user_id, CreateLabel::new("before".to_string()))
+            .await
+            .expect("failed create label");
+        let todo = repository
+            .create(user_id, CreateTodo::new("todo".to_string(), vec![label.id]))
+            .await
+            .expect("failed create todo");
+
+        // ラベルの変更はtodoにも反映される
+        let label = labels
+            .update(user_id, label.id, UpdateLabel::new("after".to_string()))
+            .await
+            .expect("failed update label");
+        let found = repository.find(user_id, todo.id).await.expect("failed find todo");
+        assert_eq!(found.labels, vec![label]);
+
+        // 存在しないラベルは指定できない
+        let res = repository
+            .create(user_id, CreateTodo::new("todo".to_string(), vec![-1]))
+            .await;
+        assert!(matches!(
+            res.unwrap_err().downcast_ref::<RepositoryError>(),
+            Some(RepositoryError::LabelNotFound(ids)) if ids == &vec![-1]
+        ));
+    }
+
+    #[tokio::test]
+    async fn todo_due_date_query() {
+        let user_id = 1;
+        let now = Utc::now();
+        let repository = TodoRepositoryForMemory::new(MemoryStore::new());
+        for (text, due_date) in [
+            ("past", Some(now - chrono::Duration::days(1))),
+            ("future", Some(now + chrono::Duration::days(1))),
+            ("no due date", None),
+        ] {
+            repository
+                .create(user_id, CreateTodo {
+                    due_date,
+                    ..CreateTodo::new(text.to_string(), vec![])
+                })
+                .await
+                .expect("failed create todo");
+        }
+
+        // 期限がnow以前のtodo
+        let todos = repository
+            .all(user_id, TodoQuery {
+                due_before: Some(now),
+                ..TodoQuery::default()
+            }, PageRequest::default())
+            .await
+            .expect("failed get todos")
+            .items;
+        assert_eq!(todos.len(), 1);
+        assert_eq!(todos[0].text, "past");
+
+        // 期限切れのtodo
+        let todos = repository
+            .all(user_id, TodoQuery {
+                overdue: true,
+                ..TodoQuery::default()
+            }, PageRequest::default())
+            .await
+            .expect("failed get todos")
+            .items;
+        assert_eq!(todos.len(), 1);
+        assert_eq!(todos[0].text, "past");
+
+        // 完了済みのtodoは期限切れに含まれない
+        repository
+            .update(
+                user_id,
+                todos[0].id,
+                UpdateTodo {
+                    text: None,
+                    completed: Some(true),
+                    labels: None,
+                    due_date: None,
+                    priority: None,
+                    project_id: None,
+                    cascade_completed: false,
+                },
+            )
+            .await
+            .expect("failed update todo");
+        let todos = repository
+            .all(user_id, TodoQuery {
+                overdue: true,
+                ..TodoQuery::default()
+            }, PageRequest::default())
+            .await
+            .expect("failed get todos")
+            .items;
+        assert!(todos.is_empty());
+    }
+
+    #[tokio::test]
+    async fn todo_priority_sort() {
+        let user_id = 1;
+        let repository = TodoRepositoryForMemory::new(MemoryStore::new());
+        for (text, priority) in [
+            ("low", Priority::Low),
+            ("urgent", Priority::Urgent),
+            ("none", Priority::None),
+            ("another low", Priority::Low),
+        ] {
+            repository
+                .create(user_id, CreateTodo {
+                    priority,
+                    ..CreateTodo::new(text.to_string(), vec![])
+                })
+                .await
+                .expect("failed create todo");
+        }
+
+        let todos = repository
+            .all(user_id, TodoQuery {
+                sort: TodoSort::Priority,
+                ..TodoQuery::default()
+            }, PageRequest::default())
+            .await
+            .expect("failed get todos")
+            .items;
+        let texts: Vec<&str> = todos.iter().map(|todo| todo.text.as_str()).collect();
+        assert_eq!(texts, vec!["urgent", "another low", "low", "none"]);
+
+        let todos = repository
+            .all(user_id, TodoQuery::default(), PageRequest::default())
+            .await
+            .expect("failed get todos")
+            .items;
+        let ids: Vec<i32> = todos.iter().map(|todo| todo.id).collect();
+        assert_eq!(ids, vec![4, 3, 2, 1]);
+    }
+
+    #[tokio::test]
+    async fn todo_filter_query() {
+        let user_id = 1;
+        let store = MemoryStore::with_labels(user_id, vec![
+            Label::new(1, String::from("label 1")),
+            Label::new(2, String::from("label 2")),
+        ]);
+        let repository = TodoRepositoryForMemory::new(store);
+        for (text, labels) in [
+            ("Buy milk", vec![1]),
+            ("buy bread", vec![1, 2]),
+            ("write report", vec![]),
+        ] {
+            repository
+                .create(user_id, CreateTodo::new(text.to_string(), labels))
+                .await
+                .expect("failed create todo");
+        }
+        repository
+            .update(
+                user_id,
+                3,
+                UpdateTodo {
+                    text: None,
+                    completed: Some(true),
+                    labels: None,
+                    due_date: None,
+                    priority: None,
+                    project_id: None,
+                    cascade_completed: false,
+                },
+            )
+            .await
+            .expect("failed update todo");
+
+        let texts = |todos: Vec<TodoEntity>| -> Vec<String> {
+            todos.into_iter().map(|todo| todo.text).collect()
+        };
+
+        let todos = repository
+            .all(user_id, TodoQuery {
+                completed: Some(true),
+                ..TodoQuery::default()
+            }, PageRequest::default())
+            .await
+            .expect("failed get todos")
+            .items;
+        assert_eq!(texts(todos), vec!["write report"]);
+
+        let todos = repository
+            .all(user_id, TodoQuery {
+                labels: vec![1, 2],
+                ..TodoQuery::default()
+            }, PageRequest::default())
+            .await
+            .expect("failed get todos")
+            .items;
+        assert_eq!(texts(todos), vec!["buy bread", "Buy milk"]);
+
+        let todos = repository
+            .all(user_id, TodoQuery {
+                labels: vec![1, 2],
+                label_match: LabelMatch::All,
+                ..TodoQuery::default()
+            }, PageRequest::default())
+            .await
+            .expect("failed get todos")
+            .items;
+        assert_eq!(texts(todos), vec!["buy bread"]);
+
+        let todos = repository
+            .all(user_id, TodoQuery {
+                text: Some("BUY".to_string()),
+                ..TodoQuery::default()
+            }, PageRequest::default())
+            .await
+            .expect("failed get todos")
+            .items;
+        assert_eq!(texts(todos), vec!["buy bread", "Buy milk"]);
+    }
+
+    #[tokio::test]
+    async fn todo_owner_isolation() {
+        let (owner, other) = (1, 2);
+        let store = MemoryStore::new();
+        let label = LabelRepositoryForMemory::new(store.clone())
+            .create(owner, CreateLabel::new("label".to_string()))
+            .await
+            .expect("failed create label");
+        let repository = TodoRepositoryForMemory::new(store);
+        let todo = repository
+            .create(owner, CreateTodo::new("todo".to_string(), vec![label.id]))
+            .await
+            .expect("failed create todo");
+
+        // 他のユーザーからは見えず、更新も削除もできない
+        let not_found = |res: anyhow::Result<_>| {
+            matches!(
+                res.unwrap_err().downcast_ref::<RepositoryError>(),
+                Some(RepositoryError::NotFound(id)) if *id == todo.id
+            )
+        };
+        assert!(not_found(repository.find(other, todo.id).await.map(|_| ())));
+        assert!(not_found(
+            repository
+                .update(other, todo.id, UpdateTodo {
+                    text: None,
+                    completed: Some(true),
+                    labels: None,
+                    due_date: None,
+                    priority: None,
+                    project_id: None,
+                    cascade_completed: false,
+                })
+                .await
+                .map(|_| ())
+        ));
+        assert!(not_found(repository.delete(other, todo.id).await));
+        let todos = repository
+            .all(other, TodoQuery::default(), PageRequest::default())
+            .await
+            .expect("failed get todos")
+            .items;
+        assert!(todos.is_empty());
+
+        // 他のユーザーのラベルは付けられない
+        let res = repository
+            .create(other, CreateTodo::new("todo".to_string(), vec![label.id]))
+            .await;
+        assert!(matches!(
+            res.unwrap_err().downcast_ref::<RepositoryError>(),
+            Some(RepositoryError::LabelNotFound(ids)) if ids == &vec![label.id]
+        ));
+
+        let found = repository.find(owner, todo.id).await.expect("failed find todo");
+        assert_eq!(found, todo);
+    }
+
+    #[tokio::test]
+    async fn checklist_reorder_scenario() {
+        let repository = TodoRepositoryForMemory::new(MemoryStore::new());
+        todo::test_utils::checklist_reorder_scenario(repository, 1).await;
+    }
+}
@@ -0,0 +1,865 @@
+use axum::async_trait;
+use chrono::Utc;
+use sqlx::{Sqlite, SqliteConnection, SqlitePool};
+
+use super::{
+    attach_checklists, dedup_label_ids, ensure_same_checklist, fold_entities, to_nodes,
+    ChecklistItemFromRow, CreateChecklistItem, CreateTodo, LabelMatch, TodoEntity, TodoFromRow,
+    TodoNode, TodoQuery, TodoRepository, TodoSort, TodoWithLabelFromRow, UpdateChecklistItem,
+    UpdateTodo,
+};
+use crate::repositories::{unit_of_work::DbConnection, Page, PageRequest, RepositoryError};
+
+// SQLiteを使うレポジトリ
+// SQLiteには配列型がないので、idのリストはJSONの文字列にしてjson_eachで展開する
+#[derive(Debug, Clone)]
+pub struct TodoRepositoryForSqlite {
+    conn: DbConnection<Sqlite>,
+}
+
+impl TodoRepositoryForSqlite {
+    pub fn new(pool: SqlitePool) -> Self {
+        TodoRepositoryForSqlite { conn: DbConnection::Pool(pool) }
+    }
+
+    pub fn with_connection(conn: DbConnection<Sqlite>) -> Self {
+        TodoRepositoryForSqlite { conn }
+    }
+}
+
+fn ids_json(ids: &[i32]) -> String {
+    serde_json::to_string(ids).expect("integer array is always serializable")
+}
+
+async fn ensure_labels_exist(conn: &mut SqliteConnection, user_id: i32, labels: &[i32]) -> anyhow::Result<()> {
+    let missing: Vec<(i32,)> = sqlx::query_as(
+        r#"
+            select distinct t.value from json_each($1) as t
+            where not exists (select 1 from labels where labels.id = t.value and labels.user_id = $2)
+            order by t.value
+        "#
+    )
+    .bind(ids_json(labels))
+    .bind(user_id)
+    .fetch_all(conn)
+    .await?;
+
+    if !missing.is_empty() {
+        let ids = missing.into_iter().map(|(id,)| id).collect();
+        return Err(RepositoryError::LabelNotFound(ids).into());
+    }
+    Ok(())
+}
+
+async fn insert_todo_labels(conn: &mut SqliteConnection, id: i32, labels: &[i32]) -> anyhow::Result<()> {
+    sqlx::query(
+        r#"
+            insert into todo_labels (todo_id, label_id)
+            select $1, t.value from json_each($2) as t
+        "#
+    )
+    .bind(id)
+    .bind(ids_json(labels))
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+// todoのチェックリストを並び順に読み込んで付ける
+async fn load_checklists(conn: &mut SqliteConnection, todos: Vec<TodoEntity>) -> anyhow::Result<Vec<TodoEntity>> {
+    let ids: Vec<i32> = todos.iter().map(|todo| todo.id).collect();
+    let rows = sqlx::query_as::<_, ChecklistItemFromRow>(
+        r#"
+            select id, todo_id, text, done, position from checklist_items
+            where todo_id in (select t.value from json_each($1) as t)
+            order by todo_id, position, id
+        "#
+    )
+    .bind(ids_json(&ids))
+    .fetch_all(conn)
+    .await?;
+    Ok(attach_checklists(todos, rows))
+}
+
+async fn find_todo(conn: &mut SqliteConnection, user_id: i32, id: i32) -> anyhow::Result<TodoEntity> {
+    let items = sqlx::query_as::<_, TodoWithLabelFromRow>(
+        r#"
+            select todos.*, labels.id as label_id, labels.name as label_name,
+                labels.color as label_color, labels.description as label_description from todos
+                left outer join (
+                    todo_labels tl inner join labels on labels.id = tl.label_id and labels.user_id = $2
+                ) on todos.id = tl.todo_id
+            where todos.id=$1 and ((todos.project_id is null and todos.user_id=$2) or exists (
+                select 1 from project_members pm
+                where pm.project_id = todos.project_id and pm.user_id = $2
+            ));
+        "#
+    )
+    .bind(id)
+    .bind(user_id)
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(|e| RepositoryError::Unexpected(e.to_string()))?;
+
+    let todos = load_checklists(conn, fold_entities(items)).await?;
+    let todo = todos.into_iter().next().ok_or(RepositoryError::NotFound(id))?;
+
+    Ok(todo)
+}
+
+// idの子孫のtodoを、指定したプロジェクトに移動し、completedの指定があればそろえる
+async fn update_descendants(
+    conn: &mut SqliteConnection,
+    id: i32,
+    project_id: Option<i32>,
+    completed: Option<bool>,
+) -> anyhow::Result<()> {
+    sqlx::query(
+        r#"
+            update todos set project_id = $2, completed = coalesce($3, completed)
+            where id in (
+                with recursive tree (id) as (
+                    select id from todos where parent_id = $1
+                    union all
+                    select todos.id from todos inner join tree on todos.parent_id = tree.id
+                )
+                select id from tree
+            )
+        "#
+    )
+    .bind(id)
+    .bind(project_id)
+    .bind(completed)
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+#[async_trait]
+impl TodoRepository for TodoRepositoryForSqlite {
+    async fn create(&self, user_id: i32, payload: CreateTodo) -> anyhow::Result<TodoEntity> {
+        self.conn.transaction(move |conn| Box::pin(async move {
+            ensure_labels_exist(conn, user_id, &payload.labels).await?;
+            // 親があれば、親と同じプロジェクトに入る
+            let project_id = match payload.parent_id {
+                Some(parent_id) => find_todo(conn, user_id, parent_id).await?.project_id,
+                None => payload.project_id,
+            };
+
+            let row = sqlx::query_as::<_, TodoFromRow>(
+                r#"
+                    insert into todos (text, completed, due_date, priority, user_id, project_id, parent_id)
+                    values ($1, false, $2, $3, $4, $5, $6)
+                    returning *
+                "#,)
+                .bind(payload.text.clone())
+                .bind(payload.due_date)
+                .bind(payload.priority)
+                .bind(user_id)
+                .bind(project_id)
+                .bind(payload.parent_id)
+                .fetch_one(&mut *conn)
+                .await?;
+
+            insert_todo_labels(conn, row.id, &payload.labels).await?;
+
+            find_todo(conn, user_id, row.id).await
+        })).await
+    }
+
+    async fn find(&self, user_id: i32, id: i32) -> anyhow::Result<TodoEntity> {
+        self.conn.run(move |conn| Box::pin(find_todo(conn, user_id, id))).await
+    }
+
+    async fn all(&self, user_id: i32, query: TodoQuery, page: PageRequest) -> anyhow::Result<Page<TodoEntity>> {
+        // Postgres版と同じく、先にtodosだけでページを切り出してからラベルをjoinする
+        // SQLiteにはnow()がないので、期限切れの判定に使う現在時刻はバインドする
+        let sort = query.sort;
+        let labels = dedup_label_ids(query.labels);
+        let todos = self.conn.run(move |conn| Box::pin(async move {
+            let items = sqlx::query_as::<_, TodoWithLabelFromRow>(
+                r#"
+                    select todos.*, labels.id as label_id, labels.name as label_name,
+                        labels.color as label_color, labels.description as label_description from (
+                        select * from todos
+                        where ((todos.project_id is null and todos.user_id = $13) or exists (
+                                select 1 from project_members pm
+                                where pm.project_id = todos.project_id and pm.user_id = $13
+                            ))
+                            and ($1 is null or todos.due_date < $1)
+                            and (not $2 or (todos.due_date < $11 and not todos.completed))
+                            and ($4 is null or todos.completed = $4)
+                            and ($5 = 0 or (
+                                select count(distinct filter_tl.label_id) from todo_labels filter_tl
+                                where filter_tl.todo_id = todos.id
+                                    and filter_tl.label_id in (select t.value from json_each($12) as t)
+                            ) >= (case when $6 then $5 else 1 end))
+                            and ($7 is null or instr(lower(todos.text), lower($7)) > 0)
+                            and ($14 is null or todos.project_id = $14)
+                            and ($9 is null
+                                or ((case when $3 then todos.priority else 0 end), todos.id) < ($8, $9))
+                        order by (case when $3 then todos.priority else 0 end) desc, todos.id desc
+                        limit $10
+                    ) todos
+                        left outer join (
+                            todo_labels tl inner join labels on labels.id = tl.label_id and labels.user_id = $13
+                        ) on todos.id = tl.todo_id
+                    order by (case when $3 then todos.priority else 0 end) desc, todos.id desc;
+                "#
+            )
+            .bind(query.due_before)
+            .bind(query.overdue)
+            .bind(sort == TodoSort::Priority)
+            .bind(query.completed)
+            .bind(labels.len() as i64)
+            .bind(query.label_match == LabelMatch::All)
+            .bind(query.text)
+            .bind(page.after.map(|cursor| cursor.key))
+            .bind(page.after.map(|cursor| cursor.id))
+            .bind(page.limit + 1)
+            .bind(Utc::now())
+            .bind(ids_json(&labels))
+            .bind(user_id)
+            .bind(query.project_id)
+            .fetch_all(&mut *conn)
+            .await?;
+            load_checklists(conn, fold_entities(items)).await
+        })).await?;
+
+        Ok(Page::from_overfetched(
+            todos,
+            page.limit,
+            |todo| todo.cursor(sort),
+        ))
+    }
+
+    async fn children(&self, user_id: i32, id: i32) -> anyhow::Result<Vec<TodoNode>> {
+        let todos = self.conn.run(move |conn| Box::pin(async move {
+            find_todo(conn, user_id, id).await?;
+            let items = sqlx::query_as::<_, TodoWithLabelFromRow>(
+                r#"
+                    with recursive tree (id, depth) as (
+                        select id, 1 from todos where parent_id = $1
+                        union all
+                        select todos.id, tree.depth + 1 from todos inner join tree on todos.parent_id = tree.id
+                    )
+                    select todos.*, labels.id as label_id, labels.name as label_name,
+                        labels.color as label_color, labels.description as label_description from tree
+                        inner join todos on todos.id = tree.id
+                        left outer join (
+                            todo_labels tl inner join labels on labels.id = tl.label_id and labels.user_id = $2
+                        ) on todos.id = tl.todo_id
+                    order by tree.depth, todos.id;
+                "#
+            )
+            .bind(id)
+            .bind(user_id)
+            .fetch_all(&mut *conn)
+            .await?;
+            load_checklists(conn, fold_entities(items)).await
+        })).await?;
+
+        Ok(to_nodes(id, todos))
+    }
+
+    async fn update(&self, user_id: i32, id: i32, payload: UpdateTodo) -> anyhow::Result<TodoEntity> {
+        self.conn.transaction(move |conn| Box::pin(async move {
+            let old_todo = find_todo(conn, user_id, id).await?;
+            if let Some(labels) = &payload.labels {
+                ensure_labels_exist(conn, user_id, labels).await?;
+            }
+            let project_id = payload.project_id.or(old_todo.project_id);
+            // 親と別のプロジェクトに移動したtodoは、親から外れる
+            let parent_id = old_todo.parent_id.filter(|_| project_id == old_todo.project_id);
+            sqlx::query(
+                r#"
+                    update todos set text=$1, completed=$2, due_date=$3, priority=$4, project_id=$6,
+                        parent_id=$7
+                    where id=$5
+                "#,
+            )
+            .bind(payload.text.unwrap_or(old_todo.text))
+            .bind(payload.completed.unwrap_or(old_todo.completed))
+            .bind(payload.due_date.unwrap_or(old_todo.due_date))
+            .bind(payload.priority.unwrap_or(old_todo.priority))
+            .bind(id)
+            .bind(project_id)
+            .bind(parent_id)
+            .execute(&mut *conn)
+            .await?;
+
+            // 子孫のtodoも一緒に移動し、指定があれば完了状態もそろえる
+            let completed = payload.completed.filter(|_| payload.cascade_completed);
+            if project_id != old_todo.project_id || completed.is_some() {
+                update_descendants(conn, id, project_id, completed).await?;
+            }
+
+            // 他のメンバーが付けたラベルは残し、自分のラベルだけを付け替える
+            if let Some(labels) = payload.labels {
+                sqlx::query(
+                    r#"
+                        delete from todo_labels
+                        where todo_id=$1 and label_id in (select id from labels where user_id=$2)
+                    "#
+                )
+                .bind(id)
+                .bind(user_id)
+                .execute(&mut *conn)
+                .await?;
+
+                insert_todo_labels(conn, id, &labels).await?;
+            };
+
+            find_todo(conn, user_id, id).await
+        })).await
+    }
+
+    async fn delete(&self, user_id: i32, id: i32) -> anyhow::Result<()> {
+        self.conn.transaction(move |conn| Box::pin(async move {
+            // 見えないtodoはNotFoundにする
+            find_todo(conn, user_id, id).await?;
+
+            // 子孫のtodoもまとめて削除する
+            // todoラベルの削除
+            sqlx::query(
+                r#"
+                    delete from todo_labels where todo_id in (
+                        with recursive tree (id) as (
+                            select id from todos where id = $1
+                            union all
+                            select todos.id from todos inner join tree on todos.parent_id = tree.id
+                        )
+                        select id from tree
+                    )
+                "#
+            )
+            .bind(id)
+            .execute(&mut *conn)
+            .await
+            .map_err(|e| RepositoryError::Unexpected(e.to_string()))?;
+
+            // todoの削除
+            sqlx::query(
+                r#"
+                    delete from todos where id in (
+                        with recursive tree (id) as (
+                            select id from todos where id = $1
+                            union all
+                            select todos.id from todos inner join tree on todos.parent_id = tree.id
+                        )
+                        select id from tree
+                    )
+                "#
+            )
+            .bind(id)
+            .execute(&mut *conn)
+            .await
+            .map_err(|e| RepositoryError::Unexpected(e.to_string()))?;
+
+            Ok(())
+        })).await
+    }
+
+    async fn add_checklist_item(&self, user_id: i32, id: i32, payload: CreateChecklistItem) -> anyhow::Result<TodoEntity> {
+        self.conn.transaction(move |conn| Box::pin(async move {
+            find_todo(conn, user_id, id).await?;
+            // 新しい項目は末尾に追加する
+            sqlx::query(
+                r#"
+                    insert into checklist_items (todo_id, text, position)
+                    select $1, $2, coalesce(max(position), 0) + 1 from checklist_items where todo_id = $1
+                "#
+            )
+            .bind(id)
+            .bind(payload.text)
+            .execute(&mut *conn)
+            .await?;
+
+            find_todo(conn, user_id, id).await
+        })).await
+    }
+
+    async fn update_checklist_item(
+        &self,
+        user_id: i32,
+        id: i32,
+        item_id: i32,
+        payload: UpdateChecklistItem,
+    ) -> anyhow::Result<TodoEntity> {
+        self.conn.transaction(move |conn| Box::pin(async move {
+            find_todo(conn, user_id, id).await?;
+            let res = sqlx::query(
+                r#"
+                    update checklist_items set text = coalesce($3, text), done = coalesce($4, done)
+                    where todo_id = $1 and id = $2
+                "#
+            )
+            .bind(id)
+            .bind(item_id)
+            .bind(payload.text)
+            .bind(payload.done)
+            .execute(&mut *conn)
+            .await?;
+            if res.rows_affected() == 0 {
+                return Err(RepositoryError::NotFound(item_id).into());
+            }
+
+            find_todo(conn, user_id, id).await
+        })).await
+    }
+
+    async fn reorder_checklist(&self, user_id: i32, id: i32, item_ids: Vec<i32>) -> anyhow::Result<TodoEntity> {
+        self.conn.transaction(move |conn| Box::pin(async move {
+            let todo = find_todo(conn, user_id, id).await?;
+            ensure_same_checklist(id, &todo.checklist, &item_ids)?;
+            // json_eachのkeyは0から始まるので、Postgresのwith ordinalityに合わせて1から始める
+            sqlx::query(
+                r#"
+                    update checklist_items
+                    set position = (select t.key + 1 from json_each($2) as t where t.value = checklist_items.id)
+                    where todo_id = $1 and id in (select t.value from json_each($2) as t)
+                "#
+            )
+            .bind(id)
+            .bind(ids_json(&item_ids))
+            .execute(&mut *conn)
+            .await?;
+
+            find_todo(conn, user_id, id).await
+        })).await
+    }
+
+    async fn delete_checklist_item(&self, user_id: i32, id: i32, item_id: i32) -> anyhow::Result<TodoEntity> {
+        self.conn.transaction(move |conn| Box::pin(async move {
+            find_todo(conn, user_id, id).await?;
+            let res = sqlx::query(
+                r#"
+                    delete from checklist_items where todo_id = $1 and id = $2
+                "#
+            )
+            .bind(id)
+            .bind(item_id)
+            .execute(&mut *conn)
+            .await?;
+            if res.rows_affected() == 0 {
+                return Err(RepositoryError::NotFound(item_id).into());
+            }
+
+            find_todo(conn, user_id, id).await
+        })).await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::repositories::{
+        label::{CreateLabel, LabelRepository, LabelRepositoryForSqlite},
+        project::{CreateProject, ProjectRepository, ProjectRepositoryForSqlite, ProjectRole},
+        test_utils::{sqlite_pool, sqlite_user},
+        todo::{self, Priority},
+    };
+
+    #[tokio::test]
+    async fn crud_scenario() {
+        let pool = sqlite_pool().await;
+        let user_id = sqlite_user(&pool, "crud_scenario@example.com").await;
+        let labels = LabelRepositoryForSqlite::new(pool.clone());
+        let repository = TodoRepositoryForSqlite::new(pool.clone());
+
+        let label = labels
+            .create(user_id, CreateLabel::new("test label".to_string()))
+            .await
+            .expect("[create label] returned Err");
+
+        // 存在しないラベルを指定するとエラーになり、todoも作られない
+        let res = repository
+            .create(user_id, CreateTodo::new("[crud_scenario] unknown label".to_string(), vec![-1]))
+            .await;
+        let err = res.expect_err("[create] with unknown label returned Ok");
+        assert!(matches!(
+            err.downcast_ref::<RepositoryError>(),
+            Some(RepositoryError::LabelNotFound(ids)) if ids == &vec![-1]
+        ));
+
+        // create
+        let todo_text = "[crud_scenario] text";
+        let created = repository
+            .create(user_id, CreateTodo::new(todo_text.to_string(), vec![label.id]))
+            .await
+            .expect("[create] returned Err");
+        assert_eq!(created.text, todo_text);
+        assert_eq!(created.labels, vec![label.clone()]);
+
+        // find
+        let todo = repository.find(user_id, created.id).await.expect("[find] returned Err");
+        assert_eq!(created, todo);
+
+        // all
+        let page = repository
+            .all(user_id, TodoQuery::default(), PageRequest::default())
+            .await
+            .expect("[all] returned Err");
+        assert_eq!(page.items, vec![created.clone()]);
+
+        let query = TodoQuery {
+            labels: vec![label.id],
+            text: Some("TEXT".to_string()),
+            ..TodoQuery::default()
+        };
+        let page = repository
+            .all(user_id, query, PageRequest::default())
+            .await
+            .expect("[all] with query returned Err");
+        assert_eq!(page.items, vec![created.clone()]);
+
+        // 期限は文字列として保存されているが、日時の順に比較できる
+        let overdue = repository
+            .create(user_id, CreateTodo {
+                due_date: Some(Utc::now() - chrono::Duration::days(1)),
+                ..CreateTodo::new("[crud_scenario] overdue".to_string(), vec![])
+            })
+            .await
+            .expect("[create] overdue returned Err");
+        let query = TodoQuery {
+            overdue: true,
+            ..TodoQuery::default()
+        };
+        let page = repository
+            .all(user_id, query, PageRequest::default())
+            .await
+            .expect("[all] overdue returned Err");
+        assert_eq!(page.items, vec![overdue.clone()]);
+        repository.delete(user_id, overdue.id).await.expect("[delete] overdue returned Err");
+
+        // update
+        let updated_text = "[crud_scenario] updated text";
+        let todo = repository
+            .update(
+                user_id,
+                todo.id,
+                UpdateTodo {
+                    text: Some(updated_text.to_string()),
+                    completed: Some(true),
+                    labels: Some(vec![]),
+                    due_date: Some(Some(Utc::now())),
+                    priority: Some(Priority::High),
+                    project_id: None,
+                    cascade_completed: false,
+                },
+            )
+            .await
+            .expect("[update] returned Err");
+        assert_eq!(created.id, todo.id);
+        assert_eq!(todo.text, updated_text);
+        assert!(todo.completed);
+        assert!(todo.due_date.is_some());
+        assert_eq!(todo.priority, Priority::High);
+        assert!(todo.labels.is_empty());
+
+        // nullを指定すると期限を消せる
+        let todo = repository
+            .update(user_id, todo.id, serde_json::from_str(r#"{ "due_date": null }"#).unwrap())
+            .await
+            .expect("[update] returned Err");
+        assert_eq!(todo.due_date, None);
+        assert_eq!(todo.text, updated_text);
+
+        // delete
+        repository.delete(user_id, todo.id).await.expect("[delete] returned Err");
+        let res = repository.find(user_id, created.id).await;
+        assert!(res.is_err());
+        let res = repository.delete(user_id, created.id).await;
+        assert!(res.is_err());
+    }
+
+    #[tokio::test]
+    async fn owner_scenario() {
+        let pool = sqlite_pool().await;
+        let owner = sqlite_user(&pool, "owner@example.com").await;
+        let other = sqlite_user(&pool, "other@example.com").await;
+        let labels = LabelRepositoryForSqlite::new(pool.clone());
+        let repository = TodoRepositoryForSqlite::new(pool.clone());
+
+        let label = labels
+            .create(owner, CreateLabel::new("label".to_string()))
+            .await
+            .expect("[create label] returned Err");
+        let todo = repository
+            .create(owner, CreateTodo::new("[owner_scenario] todo".to_string(), vec![label.id]))
+            .await
+            .expect("[create] returned Err");
+
+        // 他のユーザーからは見えず、更新も削除もできない
+        assert!(repository.find(other, todo.id).await.is_err());
+        let page = repository
+            .all(other, TodoQuery::default(), PageRequest::default())
+            .await
+            .expect("[all] returned Err");
+        assert!(page.items.is_empty());
+        let res = repository
+            .update(other, todo.id, UpdateTodo {
+                text: Some("[owner_scenario] updated".to_string()),
+                completed: None,
+                labels: None,
+                due_date: None,
+                priority: None,
+                project_id: None,
+                cascade_completed: false,
+            })
+            .await;
+        assert!(matches!(
+            res.unwrap_err().downcast_ref::<RepositoryError>(),
+            Some(RepositoryError::NotFound(_))
+        ));
+        let res = repository.delete(other, todo.id).await;
+        assert!(matches!(
+            res.unwrap_err().downcast_ref::<RepositoryError>(),
+            Some(RepositoryError::NotFound(_))
+        ));
+
+        // 他のユーザーのラベルは付けられない
+        let res = repository
+            .create(other, CreateTodo::new("[owner_scenario] other".to_string(), vec![label.id]))
+            .await;
+        assert!(matches!(
+            res.unwrap_err().downcast_ref::<RepositoryError>(),
+            Some(RepositoryError::LabelNotFound(ids)) if ids == &vec![label.id]
+        ));
+
+        let found = repository.find(owner, todo.id).await.expect("[find] returned Err");
+        assert_eq!(found, todo);
+    }
+
+    #[tokio::test]
+    async fn project_scenario() {
+        let pool = sqlite_pool().await;
+        let owner = sqlite_user(&pool, "owner@example.com").await;
+        let member = sqlite_user(&pool, "member@example.com").await;
+        let labels = LabelRepositoryForSqlite::new(pool.clone());
+        let projects = ProjectRepositoryForSqlite::new(pool.clone());
+        let repository = TodoRepositoryForSqlite::new(pool.clone());
+
+        let project = projects
+            .create(owner, CreateProject::new("shared"))
+            .await
+            .expect("[create project] returned Err");
+        let owner_label = labels
+            .create(owner, CreateLabel::new("owner label".to_string()))
+            .await
+            .expect("[create label] returned Err");
+        let member_label = labels
+            .create(member, CreateLabel::new("member label".to_string()))
+            .await
+            .expect("[create label] returned Err");
+        let todo = repository
+            .create(owner, CreateTodo {
+                project_id: Some(project.id),
+                ..CreateTodo::new("[project_scenario] todo".to_string(), vec![owner_label.id])
+            })
+            .await
+            .expect("[create] returned Err");
+        assert_eq!(todo.project_id, Some(project.id));
+
+        // メンバーになるとプロジェクトのtodoが見える
+        assert!(repository.find(member, todo.id).await.is_err());
+        projects
+            .add_member(owner, project.id, "member@example.com".to_string(), ProjectRole::Editor)
+            .await
+            .expect("[add_member] returned Err");
+        let page = repository
+            .all(member, TodoQuery::default(), PageRequest::default())
+            .await
+            .expect("[all] returned Err");
+        assert_eq!(page.items.iter().map(|todo| todo.id).collect::<Vec<_>>(), vec![todo.id]);
+
+        // ラベルは自分のものだけが見え、付け替えても他のメンバーのラベルは残る
+        let updated = repository
+            .update(member, todo.id, UpdateTodo {
+                text: None,
+                completed: None,
+                labels: Some(vec![member_label.id]),
+                due_date: None,
+                priority: None,
+                project_id: None,
+                cascade_completed: false,
+            })
+            .await
+            .expect("[update] returned Err");
+        assert_eq!(updated.labels, vec![member_label]);
+        let found = repository.find(owner, todo.id).await.expect("[find] returned Err");
+        assert_eq!(found.labels, vec![owner_label]);
+
+        // メンバーから外れると見えなくなる
+        projects
+            .delete_member(owner, project.id, member)
+            .await
+            .expect("[delete_member] returned Err");
+        assert!(repository.find(member, todo.id).await.is_err());
+        assert!(repository.delete(member, todo.id).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn subtask_scenario() {
+        let pool = sqlite_pool().await;
+        let user_id = sqlite_user(&pool, "subtask_scenario@example.com").await;
+        let projects = ProjectRepositoryForSqlite::new(pool.clone());
+        let repository = TodoRepositoryForSqlite::new(pool.clone());
+
+        let project = projects
+            .create(user_id, CreateProject::new("subtasks"))
+            .await
+            .expect("[create project] returned Err");
+        let subtask = |text: &str, parent_id: i32| CreateTodo {
+            parent_id: Some(parent_id),
+            ..CreateTodo::new(text.to_string(), vec![])
+        };
+        let root = repository
+            .create(user_id, CreateTodo {
+                project_id: Some(project.id),
+                ..CreateTodo::new("[subtask_scenario] root".to_string(), vec![])
+            })
+            .await
+            .expect("[create] root returned Err");
+        let child = repository
+            .create(user_id, subtask("[subtask_scenario] child", root.id))
+            .await
+            .expect("[create] child returned Err");
+        let grandchild = repository
+            .create(user_id, subtask("[subtask_scenario] grandchild", child.id))
+            .await
+            .expect("[create] grandchild returned Err");
+        let sibling = repository
+            .create(user_id, subtask("[subtask_scenario] sibling", root.id))
+            .await
+            .expect("[create] sibling returned Err");
+
+        // 子は親と同じプロジェクトに入る
+        assert_eq!(grandchild.parent_id, Some(child.id));
+        assert_eq!(grandchild.project_id, Some(project.id));
+
+        // 子孫は浅い順に、深さを付けて返る
+        let nodes = repository.children(user_id, root.id).await.expect("[children] returned Err");
+        let nodes: Vec<(i32, i32)> = nodes.iter().map(|node| (node.todo.id, node.depth)).collect();
+        assert_eq!(nodes, vec![(child.id, 1), (sibling.id, 1), (grandchild.id, 2)]);
+
+        // 完了状態を子孫にもそろえる
+        repository
+            .update(user_id, root.id, UpdateTodo {
+                text: None,
+                completed: Some(true),
+                labels: None,
+                due_date: None,
+                priority: None,
+                project_id: None,
+                cascade_completed: true,
+            })
+            .await
+            .expect("[update] returned Err");
+        let found = repository.find(user_id, grandchild.id).await.expect("[find] returned Err");
+        assert!(found.completed);
+
+        // 別のプロジェクトに移動すると親から外れ、子孫も一緒に移動する
+        let other_project = projects
+            .create(user_id, CreateProject::new("other"))
+            .await
+            .expect("[create project] returned Err");
+        let moved = repository
+            .update(user_id, child.id, UpdateTodo {
+                text: None,
+                completed: None,
+                labels: None,
+                due_date: None,
+                priority: None,
+                project_id: Some(other_project.id),
+                cascade_completed: false,
+            })
+            .await
+            .expect("[update] returned Err");
+        assert_eq!(moved.parent_id, None);
+        let found = repository.find(user_id, grandchild.id).await.expect("[find] returned Err");
+        assert_eq!(found.project_id, Some(other_project.id));
+        assert_eq!(found.parent_id, Some(child.id));
+
+        // 親を削除すると子孫もまとめて削除される
+        repository.delete(user_id, child.id).await.expect("[delete] returned Err");
+        assert!(repository.find(user_id, grandchild.id).await.is_err());
+        let nodes = repository.children(user_id, root.id).await.expect("[children] returned Err");
+        assert_eq!(nodes.iter().map(|node| node.todo.id).collect::<Vec<_>>(), vec![sibling.id]);
+    }
+
+    #[tokio::test]
+    async fn checklist_scenario() {
+        let pool = sqlite_pool().await;
+        let user_id = sqlite_user(&pool, "checklist_scenario@example.com").await;
+        let repository = TodoRepositoryForSqlite::new(pool.clone());
+
+        let todo = repository
+            .create(user_id, CreateTodo::new("[checklist_scenario] todo".to_string(), vec![]))
+            .await
+            .expect("[create] returned Err");
+        assert!(todo.checklist.is_empty());
+        assert_eq!(todo.progress, None);
+
+        // 項目は末尾に追加される
+        let mut todo = todo;
+        for text in ["first", "second"] {
+            todo = repository
+                .add_checklist_item(user_id, todo.id, CreateChecklistItem::new(text.to_string()))
+                .await
+                .expect("[add_checklist_item] returned Err");
+        }
+        let texts: Vec<&str> = todo.checklist.iter().map(|item| item.text.as_str()).collect();
+        assert_eq!(texts, vec!["first", "second"]);
+        let (first, second) = (todo.checklist[0].id, todo.checklist[1].id);
+
+        // 完了した項目の割合が進捗になる
+        let todo = repository
+            .update_checklist_item(user_id, todo.id, first, UpdateChecklistItem::done(true))
+            .await
+            .expect("[update_checklist_item] returned Err");
+        assert!(todo.checklist[0].done);
+        assert_eq!(todo.progress, Some(0.5));
+
+        let todo = repository
+            .reorder_checklist(user_id, todo.id, vec![second, first])
+            .await
+            .expect("[reorder_checklist] returned Err");
+        let ids: Vec<i32> = todo.checklist.iter().map(|item| item.id).collect();
+        assert_eq!(ids, vec![second, first]);
+        let found = repository.find(user_id, todo.id).await.expect("[find] returned Err");
+        assert_eq!(found, todo);
+
+        // 他のtodoの項目は変更できない
+        let other = repository
+            .create(user_id, CreateTodo::new("[checklist_scenario] other".to_string(), vec![]))
+            .await
+            .expect("[create] returned Err");
+        let res = repository.delete_checklist_item(user_id, other.id, first).await;
+        assert!(matches!(
+            res.unwrap_err().downcast_ref::<RepositoryError>(),
+            Some(RepositoryError::NotFound(id)) if *id == first
+        ));
+
+        let todo = repository
+            .delete_checklist_item(user_id, todo.id, second)
+            .await
+            .expect("[delete_checklist_item] returned Err");
+        assert_eq!(todo.progress, Some(1.0));
+
+        // todoを削除すると項目も削除される
+        repository.delete(user_id, todo.id).await.expect("[delete] returned Err");
+        let (count,): (i64,) = sqlx::query_as("select count(*) from checklist_items")
+            .fetch_one(&pool)
+            .await
+            .expect("failed count checklist items");
+        assert_eq!(count, 0);
+    }
+
+    #[tokio::test]
+    async fn checklist_reorder_scenario() {
+        let pool = sqlite_pool().await;
+        let user_id = sqlite_user(&pool, "checklist_reorder_scenario@example.com").await;
+        todo::test_utils::checklist_reorder_scenario(TodoRepositoryForSqlite::new(pool), user_id).await;
+    }
+}
@@ -0,0 +1,331 @@
+use axum::async_trait;
+use sqlx::{Connection, Database, PgPool, Pool, Postgres, Sqlite, SqlitePool, Transaction};
+use std::{fmt, future::Future, pin::Pin, sync::Arc};
+use tokio::sync::Mutex;
+
+use super::{
+    label::{LabelRepository, LabelRepositoryForDb, LabelRepositoryForSqlite},
+    todo::{TodoRepository, TodoRepositoryForDb, TodoRepositoryForSqlite},
+    RepositoryError,
+};
+
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+type SharedTransaction<DB> = Arc<Mutex<Option<Transaction<'static, DB>>>>;
+
+// レポジトリがSQLを発行する先
+// 普段はコネクションプールを使い、UnitOfWorkの中ではその作業のトランザクションを共有する
+pub enum DbConnection<DB: Database = Postgres> {
+    Pool(Pool<DB>),
+    Transaction(SharedTransaction<DB>),
+}
+
+impl<DB: Database> Clone for DbConnection<DB> {
+    fn clone(&self) -> Self {
+        match self {
+            DbConnection::Pool(pool) => DbConnection::Pool(pool.clone()),
+            DbConnection::Transaction(shared) => DbConnection::Transaction(shared.clone()),
+        }
+    }
+}
+
+impl<DB: Database> fmt::Debug for DbConnection<DB> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DbConnection::Pool(pool) => f.debug_tuple("Pool").field(pool).finish(),
+            DbConnection::Transaction(_) => f.write_str("Transaction"),
+        }
+    }
+}
+
+impl<DB: Database> DbConnection<DB> {
+    // 読み取りのように、トランザクションを張る必要のない処理を実行する
+    pub async fn run<T, F>(&self, f: F) -> anyhow::Result<T>
+    where
+        T: Send,
+        F: for<'c> FnOnce(&'c mut DB::Connection) -> BoxFuture<'c, anyhow::Result<T>> + Send,
+    {
+        match self {
+            DbConnection::Pool(pool) => {
+                let mut conn = pool.acquire().await?;
+                f(&mut *conn).await
+            }
+            DbConnection::Transaction(shared) => {
+                let mut guard = shared.lock().await;
+                let tx = guard.as_mut().ok_or_else(finished_error)?;
+                f(&mut **tx).await
+            }
+        }
+    }
+
+    // 処理全体を1つのトランザクションで実行し、エラーがあればロールバックする
+    // UnitOfWorkの中ではセーブポイントになるので、失敗してもその処理の分だけが取り消される
+    pub async fn transaction<T, F>(&self, f: F) -> anyhow::Result<T>
+    where
+        T: Send,
+        F: for<'c> FnOnce(&'c mut DB::Connection) -> BoxFuture<'c, anyhow::Result<T>> + Send,
+    {
+        match self {
+            DbConnection::Pool(pool) => {
+                let mut tx = pool.begin().await?;
+                let result = f(&mut *tx).await?;
+                tx.commit().await?;
+                Ok(result)
+            }
+            DbConnection::Transaction(shared) => {
+                let mut guard = shared.lock().await;
+                let outer = guard.as_mut().ok_or_else(finished_error)?;
+                let mut tx = outer.begin().await?;
+                let result = f(&mut *tx).await?;
+                tx.commit().await?;
+                Ok(result)
+            }
+        }
+    }
+}
+
+fn finished_error() -> RepositoryError {
+    RepositoryError::Unexpected("transaction has already been finished".to_string())
+}
+
+async fn begin_shared<DB: Database>(pool: &Pool<DB>) -> anyhow::Result<SharedTransaction<DB>> {
+    let tx = pool.begin().await?;
+    Ok(Arc::new(Mutex::new(Some(tx))))
+}
+
+async fn commit_shared<DB: Database>(shared: &SharedTransaction<DB>) -> anyhow::Result<()> {
+    let tx = shared.lock().await.take().ok_or_else(finished_error)?;
+    tx.commit().await?;
+    Ok(())
+}
+
+// 複数のレポジトリ操作をまとめて1つの単位として実行するための抽象
+// beginで作業を開始し、作業から取り出したレポジトリでの操作は、commitするまで確定しない
+#[async_trait]
+pub trait UnitOfWork: Clone + Send + Sync + 'static {
+    type Work: Work;
+    async fn begin(&self) -> anyhow::Result<Self::Work>;
+}
+
+#[async_trait]
+pub trait Work: Send + Sync {
+    type Todo: TodoRepository;
+    type Label: LabelRepository;
+    fn todos(&self) -> Self::Todo;
+    fn labels(&self) -> Self::Label;
+    async fn commit(self) -> anyhow::Result<()>;
+}
+
+#[derive(Debug, Clone)]
+pub struct UnitOfWorkForDb {
+    pool: PgPool,
+}
+
+impl UnitOfWorkForDb {
+    pub fn new(pool: PgPool) -> Self {
+        UnitOfWorkForDb { pool }
+    }
+}
+
+#[async_trait]
+impl UnitOfWork for UnitOfWorkForDb {
+    type Work = WorkForDb;
+
+    async fn begin(&self) -> anyhow::Result<WorkForDb> {
+        Ok(WorkForDb {
+            tx: begin_shared(&self.pool).await?,
+        })
+    }
+}
+
+// commitせずにdropされた場合、トランザクションはロールバックされる
+pub struct WorkForDb {
+    tx: SharedTransaction<Postgres>,
+}
+
+#[async_trait]
+impl Work for WorkForDb {
+    type Todo = TodoRepositoryForDb;
+    type Label = LabelRepositoryForDb;
+
+    fn todos(&self) -> TodoRepositoryForDb {
+        TodoRepositoryForDb::with_connection(DbConnection::Transaction(self.tx.clone()))
+    }
+
+    fn labels(&self) -> LabelRepositoryForDb {
+        LabelRepositoryForDb::with_connection(DbConnection::Transaction(self.tx.clone()))
+    }
+
+    async fn commit(self) -> anyhow::Result<()> {
+        commit_shared(&self.tx).await
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct UnitOfWorkForSqlite {
+    pool: SqlitePool,
+}
+
+impl UnitOfWorkForSqlite {
+    pub fn new(pool: SqlitePool) -> Self {
+        UnitOfWorkForSqlite { pool }
+    }
+}
+
+#[async_trait]
+impl UnitOfWork for UnitOfWorkForSqlite {
+    type Work = WorkForSqlite;
+
+    async fn begin(&self) -> anyhow::Result<WorkForSqlite> {
+        Ok(WorkForSqlite {
+            tx: begin_shared(&self.pool).await?,
+        })
+    }
+}
+
+pub struct WorkForSqlite {
+    tx: SharedTransaction<Sqlite>,
+}
+
+#[async_trait]
+impl Work for WorkForSqlite {
+    type Todo = TodoRepositoryForSqlite;
+    type Label = LabelRepositoryForSqlite;
+
+    fn todos(&self) -> TodoRepositoryForSqlite {
+        TodoRepositoryForSqlite::with_connection(DbConnection::Transaction(self.tx.clone()))
+    }
+
+    fn labels(&self) -> LabelRepositoryForSqlite {
+        LabelRepositoryForSqlite::with_connection(DbConnection::Transaction(self.tx.clone()))
+    }
+
+    async fn commit(self) -> anyhow::Result<()> {
+        commit_shared(&self.tx).await
+    }
+}
+
+#[cfg(test)]
+mod sqlite_test {
+    use super::*;
+    use crate::repositories::{
+        label::CreateLabel,
+        test_utils::{sqlite_pool, sqlite_user},
+        todo::CreateTodo,
+    };
+
+    #[tokio::test]
+    async fn unit_of_work_scenario() {
+        let pool = sqlite_pool().await;
+        let user_id = sqlite_user(&pool, "unit_of_work_scenario@example.com").await;
+        let unit_of_work = UnitOfWorkForSqlite::new(pool.clone());
+        let todos = TodoRepositoryForSqlite::new(pool.clone());
+
+        // commitしなければ、作業中の操作はすべて取り消される
+        let work = unit_of_work.begin().await.expect("[begin] returned Err");
+        let label = work
+            .labels()
+            .create(user_id, CreateLabel::new("rollback".to_string()))
+            .await
+            .expect("[create label] returned Err");
+        let todo = work
+            .todos()
+            .create(user_id, CreateTodo::new("rollback".to_string(), vec![label.id]))
+            .await
+            .expect("[create todo] returned Err");
+        drop(work);
+        assert!(todos.find(user_id, todo.id).await.is_err());
+
+        // 途中で失敗した操作は、その操作の分だけが取り消される
+        let work = unit_of_work.begin().await.expect("[begin] returned Err");
+        let label = work
+            .labels()
+            .create(user_id, CreateLabel::new("commit".to_string()))
+            .await
+            .expect("[create label] returned Err");
+        let res = work
+            .todos()
+            .create(user_id, CreateTodo::new("commit".to_string(), vec![-1]))
+            .await;
+        assert!(res.is_err());
+        let todo = work
+            .todos()
+            .create(user_id, CreateTodo::new("commit".to_string(), vec![label.id]))
+            .await
+            .expect("[create todo] returned Err");
+        work.commit().await.expect("[commit] returned Err");
+
+        let found = todos.find(user_id, todo.id).await.expect("[find] returned Err");
+        assert_eq!(found.labels, vec![label]);
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "database-test")]
+mod test {
+    use super::*;
+    use crate::repositories::{
+        label::CreateLabel,
+        test_utils::postgres_user,
+        todo::CreateTodo,
+    };
+    use dotenv::dotenv;
+    use std::env;
+
+    #[tokio::test]
+    async fn unit_of_work_scenario() {
+        dotenv().ok();
+        let database_url = &env::var("DATABASE_URL").expect("undefined [DATABASE_URL]");
+        let pool = PgPool::connect(database_url)
+            .await
+            .unwrap_or_else(|_| panic!("fail connect database, url is [{}]", database_url));
+        let user_id = postgres_user(&pool, "unit_of_work_scenario").await;
+        let unit_of_work = UnitOfWorkForDb::new(pool.clone());
+        let todos = TodoRepositoryForDb::new(pool.clone());
+        let labels = LabelRepositoryForDb::new(pool.clone());
+
+        // commitしなければ、作業中の操作はすべて取り消される
+        let work = unit_of_work.begin().await.expect("[begin] returned Err");
+        let label = work
+            .labels()
+            .create(user_id, CreateLabel::new("[unit_of_work_scenario] rollback".to_string()))
+            .await
+            .expect("[create label] returned Err");
+        let todo = work
+            .todos()
+            .create(user_id, CreateTodo::new("[unit_of_work_scenario] rollback".to_string(), vec![label.id]))
+            .await
+            .expect("[create todo] returned Err");
+        drop(work);
+        assert!(labels.find(user_id, label.id).await.is_err());
+        assert!(todos.find(user_id, todo.id).await.is_err());
+
+        // 途中で失敗した操作は、その操作の分だけが取り消される
+        let work = unit_of_work.begin().await.expect("[begin] returned Err");
+        let label = work
+            .labels()
+            .create(user_id, CreateLabel::new("[unit_of_work_scenario] commit".to_string()))
+            .await
+            .expect("[create label] returned Err");
+        let res = work
+            .todos()
+            .create(user_id, CreateTodo::new("[unit_of_work_scenario] commit".to_string(), vec![-1]))
+            .await;
+        assert!(res.is_err());
+        let todo = work
+            .todos()
+            .create(user_id, CreateTodo::new("[unit_of_work_scenario] commit".to_string(), vec![label.id]))
+            .await
+            .expect("[create todo] returned Err");
+        work.commit().await.expect("[commit] returned Err");
+
+        let found = todos.find(user_id, todo.id).await.expect("[find] returned Err");
+        assert_eq!(found.labels, vec![label.clone()]);
+
+        todos.delete(user_id, todo.id).await.expect("[delete todo] returned Err");
+        labels
+            .delete(user_id, label.id, Default::default())
+            .await
+            .expect("[delete label] returned Err");
+    }
+}
@@ -0,0 +1,461 @@
+#[cfg(any(test, feature = "memory-storage"))]
+pub mod memory;
+pub mod sqlite;
+
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool};
+
+use super::{is_unique_violation, unit_of_work::DbConnection, RepositoryError};
+
+#[cfg(any(test, feature = "memory-storage"))]
+pub use memory::UserRepositoryForMemory;
+pub use sqlite::UserRepositoryForSqlite;
+
+// ユーザーとログインセッションを管理する
+// パスワードやトークンはハッシュ化してから渡す
+#[async_trait]
+pub trait UserRepository: Clone + Send + Sync + 'static {
+    async fn create(&self, payload: CreateUser) -> anyhow::Result<User>;
+    async fn find_by_email(&self, email: String) -> anyhow::Result<Option<UserWithPassword>>;
+    async fn create_session(&self, payload: CreateSession) -> anyhow::Result<()>;
+    // 期限切れのセッションは見つからなかったものとして扱う
+    async fn find_by_session(&self, token_hash: String) -> anyhow::Result<Option<User>>;
+    async fn delete_session(&self, token_hash: String) -> anyhow::Result<()>;
+    async fn create_api_token(&self, payload: CreateApiToken) -> anyhow::Result<ApiToken>;
+    async fn api_tokens(&self, user_id: i32) -> anyhow::Result<Vec<ApiToken>>;
+    // 見つかったトークンは、使われた日時を更新する
+    async fn find_by_api_token(&self, token_hash: String) -> anyhow::Result<Option<(User, TokenScope)>>;
+    async fn delete_api_token(&self, user_id: i32, id: i32) -> anyhow::Result<()>;
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, FromRow)]
+pub struct User {
+    pub id: i32,
+    pub email: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, FromRow)]
+pub struct UserWithPassword {
+    pub id: i32,
+    pub email: String,
+    pub password_hash: String,
+}
+
+impl UserWithPassword {
+    pub fn into_user(self) -> User {
+        User {
+            id: self.id,
+            email: self.email,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CreateUser {
+    pub email: String,
+    pub password_hash: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CreateSession {
+    pub token_hash: String,
+    pub user_id: i32,
+    pub expires_at: DateTime<Utc>,
+}
+
+// アクセストークンで許可する操作の範囲
+// データベースにはsmallintとして保存し、値が大きいほど多くの操作ができる
+#[derive(
+    Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, sqlx::Type,
+)]
+#[serde(rename_all = "kebab-case")]
+#[repr(i16)]
+pub enum TokenScope {
+    ReadOnly = 1,
+    ReadWrite = 2,
+    Admin = 3,
+}
+
+// トークン自体は作成時にだけ返すので、ここには含めない
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, FromRow)]
+pub struct ApiToken {
+    pub id: i32,
+    pub name: String,
+    pub scope: TokenScope,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CreateApiToken {
+    pub user_id: i32,
+    pub name: String,
+    pub scope: TokenScope,
+    pub token_hash: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, FromRow)]
+struct ApiTokenOwnerFromRow {
+    id: i32,
+    email: String,
+    scope: TokenScope,
+}
+
+impl ApiTokenOwnerFromRow {
+    fn into_owner(self) -> (User, TokenScope) {
+        (User { id: self.id, email: self.email }, self.scope)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct UserRepositoryForDb {
+    conn: DbConnection,
+}
+
+impl UserRepositoryForDb {
+    pub fn new(pool: PgPool) -> Self {
+        Self { conn: DbConnection::Pool(pool) }
+    }
+}
+
+#[async_trait]
+impl UserRepository for UserRepositoryForDb {
+    async fn create(&self, payload: CreateUser) -> anyhow::Result<User> {
+        // 登録済みかどうかは一意制約で判定し、同時に同じメールアドレスで登録されてもDuplicateにする
+        // 制約違反の後に既存のユーザーを引けるように、トランザクションは張らない
+        self.conn.run(move |conn| Box::pin(async move {
+            let inserted = sqlx::query_as::<_, User>(
+                r#"
+                    insert into users ( email, password_hash )
+                    values ( $1, $2 )
+                    returning id, email
+                "#
+            )
+            .bind(payload.email.clone())
+            .bind(payload.password_hash)
+            .fetch_one(&mut *conn)
+            .await;
+
+            match inserted {
+                Err(e) if is_unique_violation(&e) => {
+                    let (id,): (i32,) = sqlx::query_as(
+                        r#"
+                            select id from users where email = $1
+                        "#
+                    )
+                    .bind(payload.email)
+                    .fetch_one(&mut *conn)
+                    .await?;
+                    Err(RepositoryError::Duplicate(id).into())
+                }
+                inserted => Ok(inserted?),
+            }
+        })).await
+    }
+
+    async fn find_by_email(&self, email: String) -> anyhow::Result<Option<UserWithPassword>> {
+        self.conn.run(move |conn| Box::pin(async move {
+            let user = sqlx::query_as::<_, UserWithPassword>(
+                r#"
+                    select * from users where email = $1
+                "#
+            )
+            .bind(email)
+            .fetch_optional(conn)
+            .await?;
+            Ok(user)
+        })).await
+    }
+
+    async fn create_session(&self, payload: CreateSession) -> anyhow::Result<()> {
+        self.conn.transaction(move |conn| Box::pin(async move {
+            // ログインのたびに、そのユーザーの期限切れのセッションを片付ける
+            sqlx::query(
+                r#"
+                    delete from sessions where user_id = $1 and expires_at <= now()
+                "#
+            )
+            .bind(payload.user_id)
+            .execute(&mut *conn)
+            .await?;
+
+            sqlx::query(
+                r#"
+                    insert into sessions ( token_hash, user_id, expires_at )
+                    values ( $1, $2, $3 )
+                "#
+            )
+            .bind(payload.token_hash)
+            .bind(payload.user_id)
+            .bind(payload.expires_at)
+            .execute(&mut *conn)
+            .await?;
+
+            Ok(())
+        })).await
+    }
+
+    async fn find_by_session(&self, token_hash: String) -> anyhow::Result<Option<User>> {
+        self.conn.run(move |conn| Box::pin(async move {
+            let user = sqlx::query_as::<_, User>(
+                r#"
+                    select users.id, users.email from sessions
+                    inner join users on users.id = sessions.user_id
+                    where sessions.token_hash = $1 and sessions.expires_at > now()
+                "#
+            )
+            .bind(token_hash)
+            .fetch_optional(conn)
+            .await?;
+            Ok(user)
+        })).await
+    }
+
+    async fn delete_session(&self, token_hash: String) -> anyhow::Result<()> {
+        self.conn.run(move |conn| Box::pin(async move {
+            sqlx::query(
+                r#"
+                    delete from sessions where token_hash = $1
+                "#
+            )
+            .bind(token_hash)
+            .execute(conn)
+            .await?;
+            Ok(())
+        })).await
+    }
+
+    async fn create_api_token(&self, payload: CreateApiToken) -> anyhow::Result<ApiToken> {
+        self.conn.run(move |conn| Box::pin(async move {
+            let token = sqlx::query_as::<_, ApiToken>(
+                r#"
+                    insert into api_tokens ( user_id, name, scope, token_hash, created_at )
+                    values ( $1, $2, $3, $4, now() )
+                    returning id, name, scope, created_at, last_used_at
+                "#
+            )
+            .bind(payload.user_id)
+            .bind(payload.name)
+            .bind(payload.scope)
+            .bind(payload.token_hash)
+            .fetch_one(conn)
+            .await?;
+            Ok(token)
+        })).await
+    }
+
+    async fn api_tokens(&self, user_id: i32) -> anyhow::Result<Vec<ApiToken>> {
+        self.conn.run(move |conn| Box::pin(async move {
+            let tokens = sqlx::query_as::<_, ApiToken>(
+                r#"
+                    select id, name, scope, created_at, last_used_at from api_tokens
+                    where user_id = $1
+                    order by id asc
+                "#
+            )
+            .bind(user_id)
+            .fetch_all(conn)
+            .await?;
+            Ok(tokens)
+        })).await
+    }
+
+    async fn find_by_api_token(&self, token_hash: String) -> anyhow::Result<Option<(User, TokenScope)>> {
+        self.conn.run(move |conn| Box::pin(async move {
+            let owner = sqlx::query_as::<_, ApiTokenOwnerFromRow>(
+                r#"
+                    update api_tokens set last_used_at = now()
+                    from users
+                    where api_tokens.token_hash = $1 and users.id = api_tokens.user_id
+                    returning users.id, users.email, api_tokens.scope
+                "#
+            )
+            .bind(token_hash)
+            .fetch_optional(conn)
+            .await?;
+            Ok(owner.map(ApiTokenOwnerFromRow::into_owner))
+        })).await
+    }
+
+    async fn delete_api_token(&self, user_id: i32, id: i32) -> anyhow::Result<()> {
+        self.conn.run(move |conn| Box::pin(async move {
+            let result = sqlx::query(
+                r#"
+                    delete from api_tokens where id = $1 and user_id = $2
+                "#
+            )
+            .bind(id)
+            .bind(user_id)
+            .execute(conn)
+            .await?;
+            if result.rows_affected() == 0 {
+                return Err(RepositoryError::NotFound(id).into());
+            }
+            Ok(())
+        })).await
+    }
+}
+
+#[cfg(test)]
+pub mod test_utils {
+    use super::*;
+
+    impl CreateUser {
+        pub fn new(email: &str) -> Self {
+            Self {
+                email: email.to_string(),
+                password_hash: "hash".to_string(),
+            }
+        }
+    }
+
+    impl CreateSession {
+        pub fn new(token_hash: &str, user_id: i32, expires_at: DateTime<Utc>) -> Self {
+            Self {
+                token_hash: token_hash.to_string(),
+                user_id,
+                expires_at,
+            }
+        }
+    }
+
+    impl CreateApiToken {
+        pub fn new(user_id: i32, name: &str, scope: TokenScope, token_hash: &str) -> Self {
+            Self {
+                user_id,
+                name: name.to_string(),
+                scope,
+                token_hash: token_hash.to_string(),
+            }
+        }
+    }
+
+    // どのバックエンドでも同じように動くことを確認するシナリオ
+    pub async fn user_scenario<R: UserRepository>(repository: R, email: &str) {
+        // create
+        let user = repository
+            .create(CreateUser::new(email))
+            .await
+            .expect("[create] returned Err");
+        assert_eq!(user.email, email);
+        let res = repository.create(CreateUser::new(email)).await;
+        assert!(matches!(
+            res.unwrap_err().downcast_ref::<RepositoryError>(),
+            Some(RepositoryError::Duplicate(id)) if *id == user.id
+        ));
+
+        // find_by_email
+        let found = repository
+            .find_by_email(email.to_string())
+            .await
+            .expect("[find_by_email] returned Err")
+            .expect("[find_by_email] returned None");
+        assert_eq!(found.password_hash, "hash");
+        assert_eq!(found.into_user(), user);
+
+        // session
+        let token_hash = format!("{}-token", email);
+        repository
+            .create_session(CreateSession::new(&token_hash, user.id, Utc::now() + chrono::Duration::hours(1)))
+            .await
+            .expect("[create_session] returned Err");
+        let found = repository
+            .find_by_session(token_hash.clone())
+            .await
+            .expect("[find_by_session] returned Err");
+        assert_eq!(found, Some(user.clone()));
+
+        // 期限切れのセッションでは見つからない
+        let expired_hash = format!("{}-expired", email);
+        repository
+            .create_session(CreateSession::new(&expired_hash, user.id, Utc::now() - chrono::Duration::hours(1)))
+            .await
+            .expect("[create_session] returned Err");
+        let found = repository
+            .find_by_session(expired_hash)
+            .await
+            .expect("[find_by_session] returned Err");
+        assert_eq!(found, None);
+
+        // delete_session
+        repository
+            .delete_session(token_hash.clone())
+            .await
+            .expect("[delete_session] returned Err");
+        let found = repository
+            .find_by_session(token_hash)
+            .await
+            .expect("[find_by_session] returned Err");
+        assert_eq!(found, None);
+
+        // api token
+        let token_hash = format!("{}-api-token", email);
+        let token = repository
+            .create_api_token(CreateApiToken::new(user.id, "script", TokenScope::ReadOnly, &token_hash))
+            .await
+            .expect("[create_api_token] returned Err");
+        assert_eq!(token.name, "script");
+        assert_eq!(token.scope, TokenScope::ReadOnly);
+        assert_eq!(token.last_used_at, None);
+        let found = repository
+            .find_by_api_token(token_hash.clone())
+            .await
+            .expect("[find_by_api_token] returned Err");
+        assert_eq!(found, Some((user.clone(), TokenScope::ReadOnly)));
+
+        // 使われたトークンには最終使用日時が記録される
+        let tokens = repository
+            .api_tokens(user.id)
+            .await
+            .expect("[api_tokens] returned Err");
+        assert_eq!(tokens.len(), 1);
+        assert_eq!(tokens[0].id, token.id);
+        assert!(tokens[0].last_used_at.is_some());
+
+        // 他のユーザーのトークンは削除できない
+        let res = repository.delete_api_token(user.id + 1, token.id).await;
+        assert!(matches!(
+            res.unwrap_err().downcast_ref::<RepositoryError>(),
+            Some(RepositoryError::NotFound(id)) if *id == token.id
+        ));
+
+        // delete_api_token
+        repository
+            .delete_api_token(user.id, token.id)
+            .await
+            .expect("[delete_api_token] returned Err");
+        let found = repository
+            .find_by_api_token(token_hash)
+            .await
+            .expect("[find_by_api_token] returned Err");
+        assert_eq!(found, None);
+        let tokens = repository
+            .api_tokens(user.id)
+            .await
+            .expect("[api_tokens] returned Err");
+        assert!(tokens.is_empty());
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "database-test")]
+mod test {
+    use super::*;
+    use dotenv::dotenv;
+    use std::env;
+
+    #[tokio::test]
+    async fn crud_scenario() {
+        dotenv().ok();
+        let database_url = &env::var("DATABASE_URL").expect("undefined [DATABASE_URL]");
+        let pool = PgPool::connect(database_url)
+            .await
+            .unwrap_or_else(|_| panic!("fail connect database, url is [{}]", database_url));
+
+        // 同じデータベースで何度実行しても重複しないように、メールアドレスを変える
+        let email = format!("crud_scenario_{}@example.com", Utc::now().timestamp_micros());
+        test_utils::user_scenario(UserRepositoryForDb::new(pool), &email).await;
+    }
+}
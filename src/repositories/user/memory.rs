@@ -0,0 +1,150 @@
+use axum::async_trait;
+use chrono::Utc;
+
+use super::{
+    ApiToken, CreateApiToken, CreateSession, CreateUser, TokenScope, User, UserRepository,
+    UserWithPassword,
+};
+use crate::repositories::{
+    memory::{ApiTokenRecord, MemoryStore, SessionRecord},
+    RepositoryError,
+};
+
+#[derive(Debug, Clone)]
+pub struct UserRepositoryForMemory {
+    store: MemoryStore,
+}
+
+impl UserRepositoryForMemory {
+    pub fn new(store: MemoryStore) -> Self {
+        UserRepositoryForMemory { store }
+    }
+}
+
+#[async_trait]
+impl UserRepository for UserRepositoryForMemory {
+    async fn create(&self, payload: CreateUser) -> anyhow::Result<User> {
+        let mut tables = self.store.write();
+        if let Some(user) = tables.users.values().find(|user| user.email == payload.email) {
+            return Err(RepositoryError::Duplicate(user.id).into());
+        }
+
+        let user = UserWithPassword {
+            id: self.store.next_user_id(),
+            email: payload.email,
+            password_hash: payload.password_hash,
+        };
+        tables.users.insert(user.id, user.clone());
+        Ok(user.into_user())
+    }
+
+    async fn find_by_email(&self, email: String) -> anyhow::Result<Option<UserWithPassword>> {
+        let tables = self.store.read();
+        let user = tables.users.values().find(|user| user.email == email).cloned();
+        Ok(user)
+    }
+
+    async fn create_session(&self, payload: CreateSession) -> anyhow::Result<()> {
+        let mut tables = self.store.write();
+        let now = Utc::now();
+        tables
+            .sessions
+            .retain(|_, session| session.user_id != payload.user_id || session.expires_at > now);
+        tables.sessions.insert(
+            payload.token_hash,
+            SessionRecord {
+                user_id: payload.user_id,
+                expires_at: payload.expires_at,
+            },
+        );
+        Ok(())
+    }
+
+    async fn find_by_session(&self, token_hash: String) -> anyhow::Result<Option<User>> {
+        let tables = self.store.read();
+        let user = tables
+            .sessions
+            .get(&token_hash)
+            .filter(|session| session.expires_at > Utc::now())
+            .and_then(|session| tables.users.get(&session.user_id))
+            .cloned()
+            .map(UserWithPassword::into_user);
+        Ok(user)
+    }
+
+    async fn delete_session(&self, token_hash: String) -> anyhow::Result<()> {
+        self.store.write().sessions.remove(&token_hash);
+        Ok(())
+    }
+
+    async fn create_api_token(&self, payload: CreateApiToken) -> anyhow::Result<ApiToken> {
+        let token = ApiToken {
+            id: self.store.next_api_token_id(),
+            name: payload.name,
+            scope: payload.scope,
+            created_at: Utc::now(),
+            last_used_at: None,
+        };
+        self.store.write().api_tokens.insert(
+            token.id,
+            ApiTokenRecord {
+                user_id: payload.user_id,
+                token_hash: payload.token_hash,
+                token: token.clone(),
+            },
+        );
+        Ok(token)
+    }
+
+    async fn api_tokens(&self, user_id: i32) -> anyhow::Result<Vec<ApiToken>> {
+        let tables = self.store.read();
+        let mut tokens = Vec::from_iter(
+            tables
+                .api_tokens
+                .values()
+                .filter(|record| record.user_id == user_id)
+                .map(|record| record.token.clone()),
+        );
+        tokens.sort_by_key(|token| token.id);
+        Ok(tokens)
+    }
+
+    async fn find_by_api_token(&self, token_hash: String) -> anyhow::Result<Option<(User, TokenScope)>> {
+        let mut tables = self.store.write();
+        let Some(record) = tables
+            .api_tokens
+            .values_mut()
+            .find(|record| record.token_hash == token_hash)
+        else {
+            return Ok(None);
+        };
+        record.token.last_used_at = Some(Utc::now());
+        let (user_id, scope) = (record.user_id, record.token.scope);
+        let owner = tables
+            .users
+            .get(&user_id)
+            .cloned()
+            .map(|user| (user.into_user(), scope));
+        Ok(owner)
+    }
+
+    async fn delete_api_token(&self, user_id: i32, id: i32) -> anyhow::Result<()> {
+        let mut tables = self.store.write();
+        if tables.api_tokens.get(&id).is_none_or(|record| record.user_id != user_id) {
+            return Err(RepositoryError::NotFound(id).into());
+        }
+        tables.api_tokens.remove(&id);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::repositories::user::test_utils::user_scenario;
+
+    #[tokio::test]
+    async fn user_crud_scenario() {
+        user_scenario(UserRepositoryForMemory::new(MemoryStore::new()), "memory@example.com").await;
+    }
+}
@@ -0,0 +1,223 @@
+use axum::async_trait;
+use chrono::Utc;
+use sqlx::{Sqlite, SqlitePool};
+
+use super::{
+    ApiToken, ApiTokenOwnerFromRow, CreateApiToken, CreateSession, CreateUser, TokenScope, User,
+    UserRepository, UserWithPassword,
+};
+use crate::repositories::{is_unique_violation, unit_of_work::DbConnection, RepositoryError};
+
+#[derive(Debug, Clone)]
+pub struct UserRepositoryForSqlite {
+    conn: DbConnection<Sqlite>,
+}
+
+impl UserRepositoryForSqlite {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { conn: DbConnection::Pool(pool) }
+    }
+}
+
+// SQLiteにはnow()がないので、期限切れの判定に使う現在時刻はバインドする
+#[async_trait]
+impl UserRepository for UserRepositoryForSqlite {
+    async fn create(&self, payload: CreateUser) -> anyhow::Result<User> {
+        // 登録済みかどうかは一意制約で判定し、同時に同じメールアドレスで登録されてもDuplicateにする
+        // 制約違反の後に既存のユーザーを引けるように、トランザクションは張らない
+        self.conn.run(move |conn| Box::pin(async move {
+            let inserted = sqlx::query_as::<_, User>(
+                r#"
+                    insert into users ( email, password_hash )
+                    values ( $1, $2 )
+                    returning id, email
+                "#
+            )
+            .bind(payload.email.clone())
+            .bind(payload.password_hash)
+            .fetch_one(&mut *conn)
+            .await;
+
+            match inserted {
+                Err(e) if is_unique_violation(&e) => {
+                    let (id,): (i32,) = sqlx::query_as(
+                        r#"
+                            select id from users where email = $1
+                        "#
+                    )
+                    .bind(payload.email)
+                    .fetch_one(&mut *conn)
+                    .await?;
+                    Err(RepositoryError::Duplicate(id).into())
+                }
+                inserted => Ok(inserted?),
+            }
+        })).await
+    }
+
+    async fn find_by_email(&self, email: String) -> anyhow::Result<Option<UserWithPassword>> {
+        self.conn.run(move |conn| Box::pin(async move {
+            let user = sqlx::query_as::<_, UserWithPassword>(
+                r#"
+                    select * from users where email = $1
+                "#
+            )
+            .bind(email)
+            .fetch_optional(conn)
+            .await?;
+            Ok(user)
+        })).await
+    }
+
+    async fn create_session(&self, payload: CreateSession) -> anyhow::Result<()> {
+        self.conn.transaction(move |conn| Box::pin(async move {
+            sqlx::query(
+                r#"
+                    delete from sessions where user_id = $1 and expires_at <= $2
+                "#
+            )
+            .bind(payload.user_id)
+            .bind(Utc::now())
+            .execute(&mut *conn)
+            .await?;
+
+            sqlx::query(
+                r#"
+                    insert into sessions ( token_hash, user_id, expires_at )
+                    values ( $1, $2, $3 )
+                "#
+            )
+            .bind(payload.token_hash)
+            .bind(payload.user_id)
+            .bind(payload.expires_at)
+            .execute(&mut *conn)
+            .await?;
+
+            Ok(())
+        })).await
+    }
+
+    async fn find_by_session(&self, token_hash: String) -> anyhow::Result<Option<User>> {
+        self.conn.run(move |conn| Box::pin(async move {
+            let user = sqlx::query_as::<_, User>(
+                r#"
+                    select users.id, users.email from sessions
+                    inner join users on users.id = sessions.user_id
+                    where sessions.token_hash = $1 and sessions.expires_at > $2
+                "#
+            )
+            .bind(token_hash)
+            .bind(Utc::now())
+            .fetch_optional(conn)
+            .await?;
+            Ok(user)
+        })).await
+    }
+
+    async fn delete_session(&self, token_hash: String) -> anyhow::Result<()> {
+        self.conn.run(move |conn| Box::pin(async move {
+            sqlx::query(
+                r#"
+                    delete from sessions where token_hash = $1
+                "#
+            )
+            .bind(token_hash)
+            .execute(conn)
+            .await?;
+            Ok(())
+        })).await
+    }
+
+    async fn create_api_token(&self, payload: CreateApiToken) -> anyhow::Result<ApiToken> {
+        self.conn.run(move |conn| Box::pin(async move {
+            let token = sqlx::query_as::<_, ApiToken>(
+                r#"
+                    insert into api_tokens ( user_id, name, scope, token_hash, created_at )
+                    values ( $1, $2, $3, $4, $5 )
+                    returning id, name, scope, created_at, last_used_at
+                "#
+            )
+            .bind(payload.user_id)
+            .bind(payload.name)
+            .bind(payload.scope)
+            .bind(payload.token_hash)
+            .bind(Utc::now())
+            .fetch_one(conn)
+            .await?;
+            Ok(token)
+        })).await
+    }
+
+    async fn api_tokens(&self, user_id: i32) -> anyhow::Result<Vec<ApiToken>> {
+        self.conn.run(move |conn| Box::pin(async move {
+            let tokens = sqlx::query_as::<_, ApiToken>(
+                r#"
+                    select id, name, scope, created_at, last_used_at from api_tokens
+                    where user_id = $1
+                    order by id asc
+                "#
+            )
+            .bind(user_id)
+            .fetch_all(conn)
+            .await?;
+            Ok(tokens)
+        })).await
+    }
+
+    async fn find_by_api_token(&self, token_hash: String) -> anyhow::Result<Option<(User, TokenScope)>> {
+        // SQLiteのreturningではfromで指定したテーブルを参照できないので、更新と取得を分ける
+        self.conn.transaction(move |conn| Box::pin(async move {
+            sqlx::query(
+                r#"
+                    update api_tokens set last_used_at = $2 where token_hash = $1
+                "#
+            )
+            .bind(token_hash.clone())
+            .bind(Utc::now())
+            .execute(&mut *conn)
+            .await?;
+
+            let owner = sqlx::query_as::<_, ApiTokenOwnerFromRow>(
+                r#"
+                    select users.id, users.email, api_tokens.scope from api_tokens
+                    inner join users on users.id = api_tokens.user_id
+                    where api_tokens.token_hash = $1
+                "#
+            )
+            .bind(token_hash)
+            .fetch_optional(&mut *conn)
+            .await?;
+            Ok(owner.map(ApiTokenOwnerFromRow::into_owner))
+        })).await
+    }
+
+    async fn delete_api_token(&self, user_id: i32, id: i32) -> anyhow::Result<()> {
+        self.conn.run(move |conn| Box::pin(async move {
+            let result = sqlx::query(
+                r#"
+                    delete from api_tokens where id = $1 and user_id = $2
+                "#
+            )
+            .bind(id)
+            .bind(user_id)
+            .execute(conn)
+            .await?;
+            if result.rows_affected() == 0 {
+                return Err(RepositoryError::NotFound(id).into());
+            }
+            Ok(())
+        })).await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::repositories::{test_utils::sqlite_pool, user::test_utils::user_scenario};
+
+    #[tokio::test]
+    async fn crud_scenario() {
+        let pool = sqlite_pool().await;
+        user_scenario(UserRepositoryForSqlite::new(pool), "sqlite@example.com").await;
+    }
+}
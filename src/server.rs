@@ -0,0 +1,138 @@
+use axum::Router;
+use std::{future::Future, net::TcpListener, time::Duration};
+use tokio::{sync::oneshot, time::timeout};
+
+// シグナルを受けたら新しい接続の受け付けをやめ、処理中のリクエストが終わるのを待つ
+// deadlineを過ぎたら、終わっていないリクエストは待たずに戻る
+pub async fn serve<F>(
+    listener: TcpListener,
+    app: Router,
+    signal: F,
+    deadline: Duration,
+) -> anyhow::Result<()>
+where
+    F: Future<Output = ()>,
+{
+    let (shutdown_tx, shutdown_rx) = oneshot::channel::<()>();
+    let server = axum::Server::from_tcp(listener)?
+        .serve(app.into_make_service())
+        .with_graceful_shutdown(async {
+            shutdown_rx.await.ok();
+        });
+    tokio::pin!(server);
+
+    tokio::select! {
+        result = &mut server => return Ok(result?),
+        _ = signal => {}
+    }
+
+    tracing::info!("shutting down, waiting up to {:?} for in-flight requests", deadline);
+    shutdown_tx.send(()).ok();
+    match timeout(deadline, server).await {
+        Ok(result) => Ok(result?),
+        Err(_) => {
+            tracing::warn!("shutdown deadline exceeded, dropping remaining connections");
+            Ok(())
+        }
+    }
+}
+
+// SIGINT（Ctrl+C）かSIGTERMを受けるまで待つ
+pub async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("fail install Ctrl+C handler");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("fail install SIGTERM handler")
+            .recv()
+            .await;
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+    tracing::info!("shutdown signal received");
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use axum::routing::get;
+    use hyper::{body::to_bytes, Client, StatusCode};
+    use std::net::SocketAddr;
+    use tokio::time::{sleep, Instant};
+
+    async fn slow(duration: Duration) -> &'static str {
+        sleep(duration).await;
+        "done"
+    }
+
+    // 遅いハンドラーを持つサーバーを立ち上げ、シグナルを送るためのSenderとアドレスを返す
+    fn spawn_server(
+        handler_duration: Duration,
+        deadline: Duration,
+    ) -> (oneshot::Sender<()>, SocketAddr, tokio::task::JoinHandle<anyhow::Result<()>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let app = Router::new().route("/slow", get(move || slow(handler_duration)));
+        let (signal_tx, signal_rx) = oneshot::channel::<()>();
+        let server = tokio::spawn(serve(
+            listener,
+            app,
+            async {
+                signal_rx.await.ok();
+            },
+            deadline,
+        ));
+        (signal_tx, addr, server)
+    }
+
+    #[tokio::test]
+    async fn should_complete_in_flight_request_during_shutdown() {
+        let (signal_tx, addr, server) =
+            spawn_server(Duration::from_millis(500), Duration::from_secs(5));
+
+        let request = tokio::spawn(async move {
+            let uri = format!("http://{}/slow", addr).parse().unwrap();
+            Client::new().get(uri).await
+        });
+        // リクエストがハンドラーに届いてからシグナルを送る
+        sleep(Duration::from_millis(100)).await;
+        signal_tx.send(()).unwrap();
+
+        let res = request.await.unwrap().expect("in-flight request was dropped");
+        assert_eq!(StatusCode::OK, res.status());
+        assert_eq!(to_bytes(res.into_body()).await.unwrap(), "done");
+        server.await.unwrap().expect("server returned Err");
+
+        // シャットダウン後は接続を受け付けない
+        let uri = format!("http://{}/slow", addr).parse().unwrap();
+        assert!(Client::new().get(uri).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn should_stop_waiting_after_deadline() {
+        let (signal_tx, addr, server) =
+            spawn_server(Duration::from_secs(10), Duration::from_millis(200));
+
+        tokio::spawn(async move {
+            let uri = format!("http://{}/slow", addr).parse().unwrap();
+            Client::new().get(uri).await
+        });
+        sleep(Duration::from_millis(100)).await;
+        let started = Instant::now();
+        signal_tx.send(()).unwrap();
+
+        // ハンドラーの終了を待たずに、deadlineで戻ってくる
+        server.await.unwrap().expect("server returned Err");
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+}
@@ -0,0 +1,55 @@
+use axum::{
+    extract::MatchedPath,
+    http::{HeaderValue, Request, Response},
+};
+use std::time::Duration;
+use tower_http::request_id::{MakeRequestId, RequestId};
+use tracing::{field, Span};
+use uuid::Uuid;
+
+// X-Request-Idがないリクエストには、UUIDを振る
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MakeRequestUuid;
+
+impl MakeRequestId for MakeRequestUuid {
+    fn make_request_id<B>(&mut self, _request: &Request<B>) -> Option<RequestId> {
+        let id = Uuid::new_v4().to_string();
+        HeaderValue::from_str(&id).ok().map(RequestId::new)
+    }
+}
+
+// リクエストごとのspan
+// この中で出力したログ（レポジトリのエラーやSQLのログも含む）には、request_idが付く
+// ルーティングの前に作るので、どのルートにも一致しないリクエスト（404）にもspanが付く
+// routeはルーティングの後にrecord_routeで記録する
+pub fn make_span<B>(request: &Request<B>) -> Span {
+    let request_id = request
+        .extensions()
+        .get::<RequestId>()
+        .and_then(|id| id.header_value().to_str().ok())
+        .unwrap_or_default();
+    tracing::info_span!(
+        "request",
+        request_id,
+        method = %request.method(),
+        path = %request.uri().path(),
+        route = field::Empty,
+        status = field::Empty,
+        latency_ms = field::Empty,
+    )
+}
+
+// 一致したルートのテンプレートを、make_spanで作ったspanに記録する
+// route_layerで使い、TraceLayerのspanの中で呼ばれる
+pub fn record_route<B>(request: Request<B>) -> Request<B> {
+    if let Some(path) = request.extensions().get::<MatchedPath>() {
+        Span::current().record("route", path.as_str());
+    }
+    request
+}
+
+pub fn on_response<B>(response: &Response<B>, latency: Duration, span: &Span) {
+    span.record("status", response.status().as_u16());
+    span.record("latency_ms", latency.as_millis() as u64);
+    tracing::info!("finished processing request");
+}
@@ -1,41 +1,65 @@
 import { FC, useState, useEffect } from "react"
 import 'modern-css-reset'
 import { ThemeProvider, createTheme } from "@mui/material/styles"
-import { Box, Stack, Typography } from '@mui/material'
+import { Box, Button, Stack, Typography } from '@mui/material'
 import { NewTodoPayload, Todo, UpdateTodoPayload, NewLabelPayload, Label } from "./types/todos"
+import { Credentials } from "./types/auth"
 import TodoForm from './components/TodoForm'
 import TodoList from "./components/TodoList"
 import SideNav from "./components/SideNav"
+import LoginForm from "./components/LoginForm"
 import { addTodoItem, getTodoItems, updateTodoItem, deleteTodoItem } from "./lib/api/todo"
 import { addLabelItem, getLabelItem, deleteLabelItem } from "./lib/api/label"
+import { login, logout, register } from "./lib/api/auth"
+import { getToken, setUnauthorizedHandler } from "./lib/api/client"
 
 const TodoApp: FC = () => {
   const [todos, setTodos] = useState<Todo[]>([])
   const [labels, setLabels] = useState<Label[]>([])
   const [filterLabelId, setFilterLabelId] = useState<number | null>(null)
+  // APIは全てログインが必要なので、トークンがなければログイン画面を出す
+  const [loggedIn, setLoggedIn] = useState(getToken() !== null)
+
+  const onLogin = async (credentials: Credentials) => {
+    await login(credentials)
+    setLoggedIn(true)
+  }
+
+  const onRegister = async (credentials: Credentials) => {
+    await register(credentials)
+    await onLogin(credentials)
+  }
+
+  const onLogout = async () => {
+    await logout()
+    setLoggedIn(false)
+  }
 
   const onSubmit = async (payload: NewTodoPayload) => {
     if (!payload.text) return
 
     await addTodoItem(payload)
-    const todos = await getTodoItems() // APIから再度Todo配列を取得する
+    const todos = await getTodoItems(filterLabelId) // APIから再度Todo配列を取得する
     setTodos(todos)
   }
 
   const onUpdate = async (updateTodo: UpdateTodoPayload) => {
     await updateTodoItem(updateTodo)
-    const todos = await getTodoItems() // APIから再度Todo配列を取得する
+    const todos = await getTodoItems(filterLabelId) // APIから再度Todo配列を取得する
     setTodos(todos)
   }
 
   const onDelete = async (id: number) => {
     await deleteTodoItem(id)
-    const todos = await getTodoItems() // APIから再度Todo配列を取得する
+    const todos = await getTodoItems(filterLabelId) // APIから再度Todo配列を取得する
     setTodos(todos)
   }
 
-  const onSelectLabel = (label: Label | null) => {
-    setFilterLabelId(label?.id ?? null)
+  const onSelectLabel = async (label: Label | null) => {
+    const labelId = label?.id ?? null
+    setFilterLabelId(labelId)
+    const todos = await getTodoItems(labelId) // ラベルでの絞り込みはAPI側で行う
+    setTodos(todos)
   }
 
   const onSubmitNewLabel = async (newLabel: NewLabelPayload) => {
@@ -50,20 +74,44 @@ const TodoApp: FC = () => {
     setLabels((prev) => prev.filter((label) => label.id !== id))
   }
 
-  const dispTodo = filterLabelId
-    ? todos.filter((todo) =>
-      todo.labels.some((label) => label.id === filterLabelId)
-    )
-    : todos
+  useEffect(() => {
+    // セッションが切れたら、ログイン画面に戻す
+    setUnauthorizedHandler(() => setLoggedIn(false))
+  }, [])
 
   useEffect(() => {
+    if (!loggedIn) {
+      setTodos([])
+      setLabels([])
+      setFilterLabelId(null)
+      return
+    }
     ;(async () => {
       const todos = await getTodoItems()
       setTodos(todos)
       const labelResponse = await getLabelItem()
       setLabels(labelResponse)
     })()
-  }, [])
+  }, [loggedIn])
+
+  if (!loggedIn) {
+    return (
+      <Box
+        sx={{
+          display: 'flex',
+          justifyContent: 'center',
+          p: 5,
+        }}
+      >
+        <Box maxWidth={400} width="100%">
+          <Stack spacing={5}>
+            <Typography variant="h1">Todo App</Typography>
+            <LoginForm onLogin={onLogin} onRegister={onRegister} />
+          </Stack>
+        </Box>
+      </Box>
+    )
+  }
 
   return (
     <>
@@ -73,6 +121,7 @@ const TodoApp: FC = () => {
           borderBottom: '1px solid gray',
           display: 'flex',
           alignItems: 'center',
+          justifyContent: 'space-between',
           position: 'fixed',
           top: 0,
           p: 2,
@@ -82,6 +131,9 @@ const TodoApp: FC = () => {
         }}
       >
         <Typography variant="h1">Todo App</Typography>
+        <Button onClick={onLogout} color='secondary'>
+          logout
+        </Button>
       </Box>
       <Box
         sx={{
@@ -114,7 +166,7 @@ const TodoApp: FC = () => {
           <Stack spacing={5}>
             <TodoForm onSubmit={onSubmit} labels={labels}/>
             <TodoList
-              todos={dispTodo}
+              todos={todos}
               onUpdate={onUpdate}
               onDelete={onDelete}
               labels={labels}
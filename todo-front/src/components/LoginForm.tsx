@@ -0,0 +1,78 @@
+import { FC, useState } from 'react'
+import { Box, Button, Grid, Paper, TextField, Typography } from '@mui/material'
+import { Credentials } from '../types/auth'
+
+type Props = {
+  onLogin: (credentials: Credentials) => Promise<void>
+  onRegister: (credentials: Credentials) => Promise<void>
+}
+
+const LoginForm: FC<Props> = ({ onLogin, onRegister }) => {
+  const [email, setEmail] = useState('')
+  const [password, setPassword] = useState('')
+  const [error, setError] = useState<string | null>(null)
+
+  const submit = async (action: Props['onLogin'], message: string) => {
+    if (!email || !password) return
+
+    try {
+      setError(null)
+      await action({ email, password })
+    } catch {
+      setError(message)
+    }
+  }
+
+  return (
+    <Paper elevation={2}>
+      <Box sx={{ p: 2 }}>
+        <Grid container rowSpacing={2} columnSpacing={5}>
+          <Grid item xs={12}>
+            <TextField
+              label="email"
+              type="email"
+              variant='filled'
+              value={email}
+              onChange={(e) => setEmail(e.target.value)}
+              fullWidth
+            />
+          </Grid>
+          <Grid item xs={12}>
+            <TextField
+              label="password"
+              type="password"
+              variant='filled'
+              value={password}
+              onChange={(e) => setPassword(e.target.value)}
+              fullWidth
+            />
+          </Grid>
+          {error && (
+            <Grid item xs={12}>
+              <Typography color="error">{error}</Typography>
+            </Grid>
+          )}
+          <Grid item xs={6}>
+            <Button
+              onClick={() => submit(onRegister, 'could not register with this email and password')}
+              fullWidth
+              color='secondary'
+            >
+              register
+            </Button>
+          </Grid>
+          <Grid item xs={6}>
+            <Button
+              onClick={() => submit(onLogin, 'invalid email or password')}
+              fullWidth
+            >
+              login
+            </Button>
+          </Grid>
+        </Grid>
+      </Box>
+    </Paper>
+  )
+}
+
+export default LoginForm
@@ -0,0 +1,37 @@
+import type { Credentials, Session } from '../../types/auth'
+import { apiFetch, setToken } from './client'
+
+export const register = async (credentials: Credentials) => {
+  const res = await apiFetch('/auth/register', {
+    method: 'POST',
+    headers: {
+      'Content-Type': 'application/json',
+    },
+    body: JSON.stringify(credentials),
+  })
+  if (!res.ok) {
+    throw new Error('register request failed')
+  }
+}
+
+export const login = async (credentials: Credentials) => {
+  const res = await apiFetch('/auth/login', {
+    method: 'POST',
+    headers: {
+      'Content-Type': 'application/json',
+    },
+    body: JSON.stringify(credentials),
+  })
+  if (!res.ok) {
+    throw new Error('login request failed')
+  }
+  const json: Session = await res.json()
+  setToken(json.token)
+  return json
+}
+
+export const logout = async () => {
+  // サーバー側でセッションを消せなくても、手元のトークンは捨てる
+  await apiFetch('/auth/logout', { method: 'POST' }).catch(() => undefined)
+  setToken(null)
+}
@@ -0,0 +1,34 @@
+// APIを呼び出すときに、ログインで受け取ったセッショントークンを付ける
+const API_URL = 'http://localhost:3000'
+const TOKEN_KEY = 'todo-app-token'
+
+let onUnauthorized: () => void = () => {}
+
+export const getToken = () => localStorage.getItem(TOKEN_KEY)
+
+export const setToken = (token: string | null) => {
+  if (token === null) {
+    localStorage.removeItem(TOKEN_KEY)
+  } else {
+    localStorage.setItem(TOKEN_KEY, token)
+  }
+}
+
+// セッションが切れたときに、ログイン画面に戻すための処理を登録する
+export const setUnauthorizedHandler = (handler: () => void) => {
+  onUnauthorized = handler
+}
+
+export const apiFetch = async (path: string, init: RequestInit = {}) => {
+  const headers = new Headers(init.headers)
+  const token = getToken()
+  if (token !== null) {
+    headers.set('Authorization', `Bearer ${token}`)
+  }
+  const res = await fetch(`${API_URL}${path}`, { ...init, headers })
+  if (res.status === 401 && token !== null) {
+    setToken(null)
+    onUnauthorized()
+  }
+  return res
+}
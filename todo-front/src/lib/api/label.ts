@@ -1,16 +1,25 @@
-import type { Label, NewLabelPayload } from '../../types/todos'
+import type { Label, NewLabelPayload, Page } from '../../types/todos'
+import { apiFetch } from './client'
 
 export const getLabelItem = async () => {
-  const res = await fetch('http://localhost:3000/labels')
-  if (!res.ok) {
-    throw new Error('get label request failed')
-  }
-  const json: Label[] = await res.json()
-  return json
+  const labels: Label[] = []
+  let cursor: string | null = null
+  // next_cursorがなくなるまでページを辿る
+  do {
+    const query: string = cursor === null ? '' : `?cursor=${cursor}`
+    const res = await apiFetch(`/labels${query}`)
+    if (!res.ok) {
+      throw new Error('get label request failed')
+    }
+    const json: Page<Label> = await res.json()
+    labels.push(...json.items)
+    cursor = json.next_cursor
+  } while (cursor !== null)
+  return labels
 }
 
 export const addLabelItem = async (payload: NewLabelPayload) => {
-  const res = await fetch('http://localhost:3000/labels', {
+  const res = await apiFetch('/labels', {
     method: 'POST',
     headers: {
       'Content-Type': 'application/json',
@@ -25,7 +34,8 @@ export const addLabelItem = async (payload: NewLabelPayload) => {
 }
 
 export const deleteLabelItem = async (id: number) => {
-  const res = await fetch(`http://localhost:3000/labels/${id}`, {
+  // todoに付いているラベルも、todoから外して削除する
+  const res = await apiFetch(`/labels/${id}?mode=detach`, {
     method: 'DELETE',
   })
   if(!res.ok) {
@@ -1,7 +1,8 @@
-import type { NewTodoPayload, Todo, UpdateTodoPayload } from '../../types/todos'
+import type { NewTodoPayload, Page, Todo, UpdateTodoPayload } from '../../types/todos'
+import { apiFetch } from './client'
 
 export const addTodoItem = async (payload: NewTodoPayload) => {
-  const res = await fetch('http://localhost:3000/todos', {
+  const res = await apiFetch('/todos', {
     method: 'POST',
     headers: {
       'Content-Type': 'application/json'
@@ -15,18 +16,28 @@ export const addTodoItem = async (payload: NewTodoPayload) => {
   return json
 }
 
-export const getTodoItems = async () => {
-  const res = await fetch('http://localhost:3000/todos')
-  if (!res.ok) {
-    throw new Error('get todo request failed')
-  }
-  const json: Todo[] = await res.json()
-  return json
+export const getTodoItems = async (labelId: number | null = null) => {
+  const todos: Todo[] = []
+  let cursor: string | null = null
+  // next_cursorがなくなるまでページを辿る
+  do {
+    const params = new URLSearchParams()
+    if (labelId !== null) params.set('label', String(labelId))
+    if (cursor !== null) params.set('cursor', cursor)
+    const res = await apiFetch(`/todos?${params}`)
+    if (!res.ok) {
+      throw new Error('get todo request failed')
+    }
+    const json: Page<Todo> = await res.json()
+    todos.push(...json.items)
+    cursor = json.next_cursor
+  } while (cursor !== null)
+  return todos
 }
 
 export const updateTodoItem = async (todo: UpdateTodoPayload) => {
   const { id, ...updateTodo } = todo
-  const res = await fetch(`http://localhost:3000/todos/${id}`, {
+  const res = await apiFetch(`/todos/${id}`, {
     method: 'PATCH',
     headers: {
       'Content-Type': 'application/json'
@@ -41,7 +52,7 @@ export const updateTodoItem = async (todo: UpdateTodoPayload) => {
 }
 
 export const deleteTodoItem = async (id: number) => {
-  const res = await fetch(`http://localhost:3000/todos/${id}`, {
+  const res = await apiFetch(`/todos/${id}`, {
     method: 'DELETE',
   })
   if (!res.ok) {
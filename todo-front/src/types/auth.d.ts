@@ -0,0 +1,16 @@
+export type Credentials = {
+  email: string
+  password: string
+}
+
+export type User = {
+  id: number
+  email: string
+}
+
+export type Session = {
+  token: string
+  token_type: string
+  expires_at: string
+  user: User
+}
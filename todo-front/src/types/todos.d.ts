@@ -2,12 +2,18 @@ export type Todo = {
   id: number
   text: string
   completed: boolean
+  due_date: string | null
+  priority: Priority
   labels: Label[]
 }
 
+export type Priority = 'none' | 'low' | 'medium' | 'high' | 'urgent'
+
 export type NewTodoPayload = {
   text: string
   labels: number[]
+  due_date?: string
+  priority?: Priority
 }
 
 export type UpdateTodoPayload = {
@@ -15,13 +21,25 @@ export type UpdateTodoPayload = {
   text?: string
   completed?: boolean
   labels?: number[]
+  // nullを指定すると期限を消す
+  due_date?: string | null
+  priority?: Priority
 }
 
 export type Label = {
   id: number
   name: string
+  color: string | null
+  description: string | null
+}
+
+export type Page<T> = {
+  items: T[]
+  next_cursor: string | null
 }
 
 export type NewLabelPayload = {
   name: string
+  color?: string
+  description?: string
 }